@@ -5,14 +5,14 @@ pub struct Item<T> {
     elem: Wrapper<T>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 #[derive(PartialEq)]
 struct Node<T> {
     item: T,
     next: Wrapper<T>
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Bag<T> {
     first: Wrapper<T>,
     n: i32
@@ -46,6 +46,19 @@ impl<T> Bag<T> {
         self.n += 1;
     }
 
+    /// удаляет первый элемент, для которого f возвращает true
+    pub fn remove_first<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> Option<T> {
+        let mut cur = &mut self.first;
+        while cur.as_ref().is_some_and(|node| !f(&node.item)) {
+            cur = &mut cur.as_mut().unwrap().next;
+        }
+        let node = cur.take()?;
+        let Node { item, next } = *node;
+        *cur = next;
+        self.n -= 1;
+        Some(item)
+    }
+
     pub fn iter(&self) -> Iter<'_, T> {
         Iter { next: self.first.as_deref() }
     }
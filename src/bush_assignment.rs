@@ -0,0 +1,573 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::frank_wolf::{edge_vector_len, LinkCost, Objective};
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::index_min_pq::IndexMinPQ;
+use crate::structure_xml::Demand;
+
+/// относительная разность стоимостей самого длинного и самого короткого путей куста, ниже которой поток не переносится
+const SHIFT_EPS: f64 = 1e-14;
+/// порог потока куста относительно его суммарной корреспонденции
+const FLOW_EPS: f64 = 1e-12;
+/// число шагов Ньютона при выравнивании стоимостей двух сегментов
+const SEGMENT_ITERATIONS: usize = 20;
+
+/// Стоимость дуг для распределения по кустам: cost - производная целевой функции по потоку дуги
+/// (время проезда для равновесия пользователей, предельная стоимость для оптимума системы)
+pub trait ArcCosts {
+    fn objective(&self, x: &[f64]) -> f64;
+    fn cost(&self, x: &[f64], arc: usize) -> f64;
+    fn derivative(&self, x: &[f64], arc: usize) -> f64;
+}
+
+impl ArcCosts for LinkCost {
+    fn objective(&self, x: &[f64]) -> f64 {
+        self.value(&x.to_vec().into())
+    }
+
+    fn cost(&self, x: &[f64], arc: usize) -> f64 {
+        self.arc_cost(x, arc)
+    }
+
+    fn derivative(&self, x: &[f64], arc: usize) -> f64 {
+        self.arc_derivative(x, arc)
+    }
+}
+
+/// Параметры Algorithm B
+#[derive(Debug, Clone, Copy)]
+pub struct BushSettings {
+    max_iterations: i32,
+    tolerance: f64,         // относительный зазор, при котором расчет останавливается
+    inner_iterations: i32,  // проходы переноса потока по кусту источника за итерацию
+    first_thru_node: i32    // вершины с меньшими номерами - зоны, транзит через них запрещен
+}
+
+impl BushSettings {
+
+    pub fn new() -> BushSettings {
+        BushSettings {
+            max_iterations: 200,
+            tolerance: 1e-10,
+            inner_iterations: 3,
+            first_thru_node: 0
+        }
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: i32) -> BushSettings {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f64) -> BushSettings {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn with_inner_iterations(mut self, inner_iterations: i32) -> BushSettings {
+        self.inner_iterations = inner_iterations;
+        self
+    }
+
+    pub fn with_first_thru_node(mut self, first_thru_node: i32) -> BushSettings {
+        self.first_thru_node = first_thru_node;
+        self
+    }
+}
+
+/// Результат распределения по кустам
+#[derive(Debug, Clone)]
+pub struct BushAssignment {
+    edge_flows: Vec<(DirectedEdge, f64)>,
+    unrouted: Vec<i32>,
+    objective: f64,
+    relative_gap: f64,                           // (TSTT - SPTT) / TSTT: доля стоимости, на которую текущие пути дороже кратчайших
+    iterations: i32,
+    bush_arcs: usize,                            // суммарное число дуг во всех кустах
+    history: Vec<(i32, f64, f64, Duration)>      // итерация, целевая функция, относительный зазор, время
+}
+
+impl BushAssignment {
+
+    /// поток на каждой работающей дуге графа
+    pub fn get_edge_flows(&self) -> &[(DirectedEdge, f64)] {
+        &self.edge_flows
+    }
+
+    pub fn get_unrouted(&self) -> &[i32] {
+        &self.unrouted
+    }
+
+    /// запись истории сходимости: строка "iteration value relative_gap seconds" на итерацию
+    pub fn write_history(&self, path: &str) {
+        let mut data = String::from("# iteration value relative_gap seconds\n");
+        for (iteration, value, gap, elapsed) in &self.history {
+            data.push_str(&format!("{} {} {} {}\n", iteration, value, gap, elapsed.as_secs_f64()));
+        }
+        fs::write(path, data).expect("Не удалось записать историю сходимости");
+    }
+}
+
+impl fmt::Display for BushAssignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "objective: {}, relative gap: {:e}, iterations: {}, bush links: {}",
+                 self.objective, self.relative_gap, self.iterations, self.bush_arcs)
+    }
+}
+
+/// Дуги графа в виде массивов, индекс - id дуги
+struct Arcs {
+    from: Vec<usize>,
+    to: Vec<usize>,
+    out_arcs: Vec<Vec<usize>>,
+    in_arcs: Vec<Vec<usize>>,
+    enabled: Vec<bool>,
+    through: Vec<bool>        // через вершину разрешен транзит
+}
+
+impl Arcs {
+
+    fn new(graph_adj: &EdgeWeightedDigraph, first_thru_node: i32) -> Arcs {
+        let n = graph_adj.get_v_count() as usize;
+        let m = edge_vector_len(graph_adj);
+        let mut arcs = Arcs {
+            from: vec![0; m],
+            to: vec![0; m],
+            out_arcs: vec![vec![]; n],
+            in_arcs: vec![vec![]; n],
+            enabled: vec![false; m],
+            through: (0..n as i32).map(|v| v >= first_thru_node).collect()
+        };
+        for e in graph_adj.edges().into_iter().filter(|e| graph_adj.is_edge_enabled(e)) {
+            let a = e.get_id() as usize;
+            arcs.from[a] = e.from() as usize;
+            arcs.to[a] = e.to() as usize;
+            arcs.enabled[a] = true;
+            arcs.out_arcs[e.from() as usize].push(a);
+            arcs.in_arcs[e.to() as usize].push(a);
+        }
+        arcs
+    }
+
+    /// из вершины v можно ехать дальше по пути от origin
+    fn can_leave(&self, v: usize, origin: usize) -> bool {
+        v == origin || self.through[v]
+    }
+
+    /// кратчайшие расстояния от origin и дуги-предшественники при стоимостях cost
+    fn shortest_tree(&self, cost: &[f64], origin: usize) -> (Vec<f64>, Vec<Option<usize>>) {
+        let n = self.out_arcs.len();
+        let mut dist = vec![f64::INFINITY; n];
+        let mut pred = vec![None; n];
+        let mut pq = IndexMinPQ::get_index_from_size(n);
+        dist[origin] = 0f64;
+        pq.insert(origin, 0f64).expect("Источник вне графа");
+        while let Ok(v) = pq.del_min() {
+            if !self.can_leave(v, origin) {
+                continue;
+            }
+            for &a in &self.out_arcs[v] {
+                let w = self.to[a];
+                let candidate = dist[v] + cost[a];
+                if candidate < dist[w] {
+                    dist[w] = candidate;
+                    pred[w] = Some(a);
+                    if pq.contains(w) {
+                        pq.decrease_key(w, candidate).expect("Ключ в очереди не уменьшился");
+                    } else {
+                        pq.insert(w, candidate).expect("Вершина вне графа");
+                    }
+                }
+            }
+        }
+        (dist, pred)
+    }
+}
+
+/// Куст источника: ациклический подграф, по которому идет весь поток из origin, и потоки этого источника на дугах
+struct Bush {
+    origin: usize,
+    targets: Vec<(usize, f64)>,
+    in_bush: Vec<bool>,
+    flows: Vec<f64>,
+    min_flow: f64,      // поток меньше этого порога считается остатком округления и не образует используемый путь
+    order: Vec<usize>   // топологический порядок вершин куста
+}
+
+/// Метки куста: кратчайший путь по всем дугам куста, самый длинный путь по всем дугам
+/// и самый длинный путь по дугам с потоком, с дугами-предшественниками
+struct Labels {
+    min: Vec<f64>,
+    min_pred: Vec<Option<usize>>,
+    max: Vec<f64>,
+    used_max: Vec<f64>,
+    used_pred: Vec<Option<usize>>
+}
+
+impl Bush {
+
+    /// начальный куст - дуги, ведущие от вершин, более близких к origin, к более дальним; поток - по дереву кратчайших путей
+    fn new(arcs: &Arcs, cost: &[f64], origin: usize, targets: Vec<(usize, f64)>, x: &mut [f64]) -> Bush {
+        let (dist, pred) = arcs.shortest_tree(cost, origin);
+        let m = arcs.from.len();
+        let min_flow = FLOW_EPS * targets.iter().fold(0f64, |sum, &(_, d)| sum + d);
+        let mut bush = Bush {
+            origin,
+            targets,
+            in_bush: vec![false; m],
+            flows: vec![0f64; m],
+            min_flow,
+            order: vec![]
+        };
+        for v in 0..arcs.out_arcs.len() {
+            if !dist[v].is_finite() || !arcs.can_leave(v, origin) {
+                continue;
+            }
+            for &a in &arcs.out_arcs[v] {
+                if dist[v] < dist[arcs.to[a]] && dist[arcs.to[a]].is_finite() {
+                    bush.in_bush[a] = true;
+                }
+            }
+        }
+        for &a in pred.iter().flatten() {
+            bush.in_bush[a] = true;
+        }
+        for k in 0..bush.targets.len() {
+            let (t, demand) = bush.targets[k];
+            let mut v = t;
+            while let Some(a) = pred[v] {
+                bush.flows[a] += demand;
+                x[a] += demand;
+                v = arcs.from[a];
+            }
+        }
+        bush.sort(arcs);
+        bush
+    }
+
+    /// топологическая сортировка вершин, достижимых из origin по дугам куста
+    fn sort(&mut self, arcs: &Arcs) {
+        let n = arcs.out_arcs.len();
+        let mut indegree = vec![0; n];
+        for a in (0..self.in_bush.len()).filter(|&a| self.in_bush[a]) {
+            indegree[arcs.to[a]] += 1;
+        }
+        self.order.clear();
+        let mut stack = vec![self.origin];
+        while let Some(v) = stack.pop() {
+            self.order.push(v);
+            for &a in arcs.out_arcs[v].iter().filter(|&&a| self.in_bush[a]) {
+                indegree[arcs.to[a]] -= 1;
+                if indegree[arcs.to[a]] == 0 {
+                    stack.push(arcs.to[a]);
+                }
+            }
+        }
+    }
+
+    fn labels(&self, arcs: &Arcs, cost: &[f64]) -> Labels {
+        let n = arcs.out_arcs.len();
+        let mut labels = Labels {
+            min: vec![f64::INFINITY; n],
+            min_pred: vec![None; n],
+            max: vec![f64::NEG_INFINITY; n],
+            used_max: vec![f64::NEG_INFINITY; n],
+            used_pred: vec![None; n]
+        };
+        labels.min[self.origin] = 0f64;
+        labels.max[self.origin] = 0f64;
+        labels.used_max[self.origin] = 0f64;
+        for &v in &self.order {
+            for &a in arcs.in_arcs[v].iter().filter(|&&a| self.in_bush[a]) {
+                let i = arcs.from[a];
+                if labels.min[i] + cost[a] < labels.min[v] {
+                    labels.min[v] = labels.min[i] + cost[a];
+                    labels.min_pred[v] = Some(a);
+                }
+                labels.max[v] = f64::max(labels.max[v], labels.max[i] + cost[a]);
+                if self.flows[a] > self.min_flow && labels.used_max[i] + cost[a] > labels.used_max[v] {
+                    labels.used_max[v] = labels.used_max[i] + cost[a];
+                    labels.used_pred[v] = Some(a);
+                }
+            }
+        }
+        labels
+    }
+
+    /// Из куста удаляются дуги без потока, кроме дуг кратчайших путей куста, и добавляются дуги (i, j),
+    /// для которых max_i + c_ij < max_j. Самый длинный путь строго растет вдоль дуг куста, поэтому куст остается ациклическим.
+    /// Дуги с остатками потока завышают max, поэтому если таких дуг нет, добавляются дуги, сокращающие кратчайший путь
+    /// куста и идущие вперед по топологическому порядку - они тоже не создают циклов
+    fn update(&mut self, arcs: &Arcs, cost: &[f64]) {
+        let labels = self.labels(arcs, cost);
+        for a in 0..self.in_bush.len() {
+            if self.in_bush[a] && self.flows[a] <= 0f64 && labels.min_pred[arcs.to[a]] != Some(a) {
+                self.in_bush[a] = false;
+                self.flows[a] = 0f64;
+            }
+        }
+        self.sort(arcs);
+        let labels = self.labels(arcs, cost);
+        let candidates: Vec<usize> = (0..self.in_bush.len())
+            .filter(|&a| {
+                let (i, j) = (arcs.from[a], arcs.to[a]);
+                !self.in_bush[a] && arcs.enabled[a] && arcs.can_leave(i, self.origin) && labels.min[i].is_finite() && labels.min[j].is_finite()
+            })
+            .collect();
+        let mut added: Vec<usize> = candidates.iter().copied()
+            .filter(|&a| labels.max[arcs.from[a]] + cost[a] < labels.max[arcs.to[a]])
+            .collect();
+        if added.is_empty() {
+            let mut position = vec![usize::MAX; arcs.out_arcs.len()];
+            for (k, &v) in self.order.iter().enumerate() {
+                position[v] = k;
+            }
+            added = candidates.into_iter()
+                .filter(|&a| {
+                    let (i, j) = (arcs.from[a], arcs.to[a]);
+                    position[i] < position[j] && labels.min[i] + cost[a] < labels.min[j] * (1f64 - SHIFT_EPS)
+                })
+                .collect();
+        }
+        for &a in &added {
+            self.in_bush[a] = true;
+        }
+        if !added.is_empty() {
+            self.sort(arcs);
+        }
+    }
+
+    /// Перенос потока с самого длинного используемого пути на кратчайший путь куста: вершины обходятся
+    /// от дальних к ближним, для каждой находится вершина расхождения двух путей, перенос выравнивает стоимости
+    /// отрезков (segment_shift) и не больше наименьшего потока на длинном отрезке
+    fn shift<C: ArcCosts>(&mut self, arcs: &Arcs, costs: &C, x: &mut [f64], cost: &mut [f64], mark: &mut [usize], stamp: &mut usize) {
+        let labels = self.labels(arcs, cost);
+        for k in (1..self.order.len()).rev() {
+            let v = self.order[k];
+            if labels.used_pred[v].is_none() || labels.used_max[v] - labels.min[v] <= SHIFT_EPS * f64::max(labels.used_max[v].abs(), 1f64) {
+                continue;
+            }
+            // вершины кратчайшего пути помечаются, длинный путь идет назад до первой помеченной вершины
+            *stamp += 1;
+            let mut u = v;
+            mark[u] = *stamp;
+            while let Some(a) = labels.min_pred[u] {
+                u = arcs.from[a];
+                mark[u] = *stamp;
+            }
+            let mut long = vec![];
+            let mut u = v;
+            while let Some(a) = labels.used_pred[u] {
+                long.push(a);
+                u = arcs.from[a];
+                if mark[u] == *stamp {
+                    break;
+                }
+            }
+            if mark[u] != *stamp {
+                continue;
+            }
+            let divergence = u;
+            let mut short = vec![];
+            let mut u = v;
+            while u != divergence {
+                let a = labels.min_pred[u].unwrap();
+                short.push(a);
+                u = arcs.from[a];
+            }
+
+            let available = long.iter().map(|&a| self.flows[a]).fold(f64::INFINITY, f64::min);
+            let mut delta = segment_shift(costs, x, &long, &short, available);
+            if available - delta <= self.min_flow {
+                delta = available;
+            }
+            if delta <= 0f64 {
+                continue;
+            }
+            for &a in &long {
+                self.flows[a] = if self.flows[a] - delta <= 0f64 { 0f64 } else { self.flows[a] - delta };
+                x[a] -= delta;
+            }
+            for &a in &short {
+                self.flows[a] += delta;
+                x[a] += delta;
+            }
+            for &a in long.iter().chain(&short) {
+                cost[a] = costs.cost(x, a);
+            }
+        }
+    }
+}
+
+/// Величина переноса потока с длинного сегмента на короткий, при которой их стоимости равны: ньютоновские шаги
+/// внутри отрезка [0, available], выходящий за текущие границы шаг заменяется делением пополам. Одного шага Ньютона
+/// мало - при малых потоках производная BPR почти нулевая, и шаг перебрасывает весь поток туда и обратно
+fn segment_shift<C: ArcCosts>(costs: &C, x: &mut [f64], long: &[usize], short: &[usize], available: f64) -> f64 {
+    let saved: Vec<f64> = long.iter().chain(short).map(|&a| x[a]).collect();
+    let difference = |delta: f64, x: &mut [f64]| {
+        for (k, &a) in long.iter().chain(short).enumerate() {
+            x[a] = saved[k];
+        }
+        for &a in long {
+            x[a] -= delta;
+        }
+        for &a in short {
+            x[a] += delta;
+        }
+        let long_cost = long.iter().fold(0f64, |sum, &a| sum + costs.cost(x, a));
+        let short_cost = short.iter().fold(0f64, |sum, &a| sum + costs.cost(x, a));
+        let curvature = long.iter().chain(short).fold(0f64, |sum, &a| sum + costs.derivative(x, a));
+        (long_cost - short_cost, long_cost + short_cost, curvature)
+    };
+
+    // ньютоновские шаги к выпуклой функции могут подходить к корню сверху, не сдвигая lo, поэтому
+    // результат - проверенная точка с наименьшей по модулю разностью стоимостей
+    let (mut lo, mut hi) = (0f64, available);
+    let (mut delta, mut best, mut best_difference) = (0f64, 0f64, f64::INFINITY);
+    for _ in 0..SEGMENT_ITERATIONS {
+        let (g, scale, curvature) = difference(delta, x);
+        if g.abs() < best_difference {
+            best = delta;
+            best_difference = g.abs();
+        }
+        if g.abs() <= SHIFT_EPS * f64::max(scale, 1f64) {
+            break;
+        }
+        if g > 0f64 {
+            lo = delta;
+        } else {
+            hi = delta;
+        }
+        if hi - lo <= SHIFT_EPS * available {
+            break;
+        }
+        let newton = if curvature > 0f64 { delta + g / curvature } else { hi };
+        delta = if newton > lo && newton < hi { newton } else if newton >= available { available } else { (lo + hi) / 2f64 };
+    }
+    for (k, &a) in long.iter().chain(short).enumerate() {
+        x[a] = saved[k];
+    }
+    best
+}
+
+/// Распределение по кустам (Algorithm B, Dial 2006): для каждого источника хранится ациклический куст,
+/// поток переносится между самым длинным и кратчайшим путями куста к каждой вершине. Оптимум - по costs:
+/// для BprCost равновесие пользователей, для LinkCost минимум суммарной стоимости
+pub fn assign_bush_based<C: ArcCosts>(
+    graph_adj: &EdgeWeightedDigraph,
+    commodities: &[Demand],
+    costs: &C,
+    settings: &BushSettings
+) -> BushAssignment {
+    let start = Instant::now();
+    let arcs = Arcs::new(graph_adj, settings.first_thru_node);
+    let m = arcs.from.len();
+    let n = arcs.out_arcs.len();
+
+    // demand по источникам, корреспонденции без пути не распределяются
+    let mut targets: BTreeMap<usize, Vec<(usize, f64)>> = BTreeMap::new();
+    let mut unrouted = vec![];
+    let mut x = vec![0f64; m];
+    let free_cost: Vec<f64> = (0..m).map(|a| costs.cost(&x, a)).collect();
+    let mut reachable: BTreeMap<usize, Vec<f64>> = BTreeMap::new();
+    for (index, commodity) in commodities.iter().enumerate() {
+        let source = graph_adj.get_node_index(&commodity.get_source());
+        let target = graph_adj.get_node_index(&commodity.get_target());
+        match (source, target) {
+            (Some(s), Some(t)) if s != t => {
+                let (s, t) = (s as usize, t as usize);
+                let dist = reachable.entry(s).or_insert_with(|| arcs.shortest_tree(&free_cost, s).0);
+                if dist[t].is_finite() {
+                    targets.entry(s).or_default().push((t, commodity.get_demand_vale()));
+                } else {
+                    unrouted.push(index as i32);
+                }
+            }
+            _ => unrouted.push(index as i32)
+        }
+    }
+
+    let mut cost = free_cost;
+    let mut bushes = vec![];
+    for (origin, targets) in targets {
+        bushes.push(Bush::new(&arcs, &cost, origin, targets, &mut x));
+        for (a, c) in cost.iter_mut().enumerate() {
+            *c = costs.cost(&x, a);
+        }
+    }
+
+    let mut mark = vec![0usize; n];
+    let mut stamp = 0usize;
+    let mut history = vec![];
+    let mut iterations = 0;
+    loop {
+        let relative_gap = relative_gap(&arcs, &bushes, &x, &cost);
+        let objective = costs.objective(&x);
+        history.push((iterations, objective, relative_gap, start.elapsed()));
+        if relative_gap <= settings.tolerance || iterations >= settings.max_iterations {
+            return BushAssignment {
+                edge_flows: graph_adj.edges().into_iter()
+                    .filter(|e| graph_adj.is_edge_enabled(e))
+                    .map(|e| {
+                        let flow = x[e.get_id() as usize];
+                        (e, flow)
+                    })
+                    .collect(),
+                unrouted,
+                objective,
+                relative_gap,
+                iterations,
+                bush_arcs: bushes.iter().map(|b| b.in_bush.iter().filter(|&&a| a).count()).sum(),
+                history
+            };
+        }
+        for bush in bushes.iter_mut() {
+            bush.update(&arcs, &cost);
+            for _ in 0..settings.inner_iterations {
+                bush.shift(&arcs, costs, &mut x, &mut cost, &mut mark, &mut stamp);
+            }
+        }
+        // стоимости пересчитываются целиком, чтобы погрешность приращений не накапливалась
+        for (a, c) in cost.iter_mut().enumerate() {
+            *c = costs.cost(&x, a);
+        }
+        iterations += 1;
+    }
+}
+
+/// (TSTT - SPTT) / TSTT, TSTT = sum x_a * c_a, SPTT - стоимость всех demand по кратчайшим путям сети
+fn relative_gap(arcs: &Arcs, bushes: &[Bush], x: &[f64], cost: &[f64]) -> f64 {
+    // у выключенных дуг стоимость бесконечна, а поток нулевой: 0 * inf дало бы NaN
+    let total = (0..x.len()).filter(|&a| arcs.enabled[a]).fold(0f64, |sum, a| sum + x[a] * cost[a]);
+    let mut shortest = 0f64;
+    for bush in bushes {
+        let (dist, _) = arcs.shortest_tree(cost, bush.origin);
+        shortest += bush.targets.iter().fold(0f64, |sum, &(t, demand)| sum + demand * dist[t]);
+    }
+    if total > 0f64 { (total - shortest) / total } else { 0f64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tntp::read_tntp;
+
+    #[test]
+    fn braess_network_reaches_known_equilibrium() {
+        let network = read_tntp("data/braess_net.tntp", "data/braess_trips.tntp");
+        let settings = BushSettings::new().with_first_thru_node(network.get_first_thru_node());
+        let assignment = assign_bush_based(network.get_graph(), network.get_commodities(), network.get_costs(), &settings);
+        assert!(assignment.unrouted.is_empty());
+        assert!(assignment.relative_gap < 1e-9, "{}", assignment.relative_gap);
+        assert!(assignment.iterations < settings.max_iterations);
+        assert!((assignment.objective - 455.75).abs() < 1e-9);
+        // равновесие из data/braess_net.tntp: дуги 1-2, 1-3, 2-3, 2-4, 3-4
+        let expected = [3.5, 2.5, 1f64, 2.5, 3.5];
+        for (e, flow) in &assignment.edge_flows {
+            assert!((flow - expected[e.get_id() as usize]).abs() < 1e-6, "дуга {}: {}", e.get_id(), flow);
+        }
+    }
+}
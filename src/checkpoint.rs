@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::structure_xml::Demand;
+use crate::utils_graph::CommodityFlows;
+
+/// версия формата файла состояния, при изменении формата увеличивается
+pub const CHECKPOINT_VERSION: u32 = 2;
+
+/// пути commodity вместе с их ключами из path_key
+pub type KeyedPaths = Vec<(String, Vec<DirectedEdge>)>;
+
+/// Состояние метода проекции после завершенной итерации. Вещественные числа хранятся битами f64,
+/// поэтому продолжение с сохраненного состояния дает тот же результат, что и расчет без остановки
+#[derive(Debug, Clone, Default)]
+pub struct ProjectionCheckpoint {
+    fingerprint: (i32, i32, usize, u64), // вершины, дуги, commodity и биты суммарного demand задачи
+    iterations: i32,
+    initial_delay: f64,
+    delay: f64,
+    lower_bound: f64, // нижняя оценка оптимальной задержки: продолженный расчет останавливается по тому же зазору
+    best: (i32, f64), // итерация с наименьшей задержкой и сама задержка
+    unrouted: Vec<i32>,
+    ends: BTreeMap<i32, (i32, i32)>,                 // индекс commodity -> source и target
+    current_paths: BTreeMap<i32, Vec<DirectedEdge>>, // текущий кратчайший путь commodity
+    active_paths: BTreeMap<i32, KeyedPaths>,         // активные пути commodity с их ключами
+    path_flows: BTreeMap<i32, Vec<(String, f64)>>,   // поток на каждом пути по ключу пути
+    edge_flows: Vec<(DirectedEdge, CommodityFlows)>, // потоки (x, y) commodity на дугах
+    edge_costs: Vec<DirectedEdge>                    // дуги графа с текущими стоимостями
+}
+
+impl ProjectionCheckpoint {
+
+    /// пустое состояние для задачи; остальные поля заполняет метод проекции через with_*
+    pub fn new(graph_adj: &EdgeWeightedDigraph, commodities: &[Demand]) -> ProjectionCheckpoint {
+        ProjectionCheckpoint {
+            fingerprint: fingerprint(graph_adj, commodities),
+            edge_costs: graph_adj.edges(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_progress(mut self, iterations: i32, initial_delay: f64, delay: f64, lower_bound: f64) -> ProjectionCheckpoint {
+        self.iterations = iterations;
+        self.initial_delay = initial_delay;
+        self.delay = delay;
+        self.lower_bound = lower_bound;
+        self
+    }
+
+    pub fn with_best(mut self, iteration: i32, delay: f64) -> ProjectionCheckpoint {
+        self.best = (iteration, delay);
+        self
+    }
+
+    pub fn with_commodities(
+        mut self,
+        unrouted: Vec<i32>,
+        ends: BTreeMap<i32, (i32, i32)>,
+        current_paths: BTreeMap<i32, Vec<DirectedEdge>>,
+        active_paths: BTreeMap<i32, KeyedPaths>,
+        path_flows: BTreeMap<i32, Vec<(String, f64)>>
+    ) -> ProjectionCheckpoint {
+        self.unrouted = unrouted;
+        self.ends = ends;
+        self.current_paths = current_paths;
+        self.active_paths = active_paths;
+        self.path_flows = path_flows;
+        self
+    }
+
+    pub fn with_edge_flows(mut self, edge_flows: Vec<(DirectedEdge, CommodityFlows)>) -> ProjectionCheckpoint {
+        self.edge_flows = edge_flows;
+        self
+    }
+
+    /// проверка, что состояние сохранено для той же задачи
+    pub fn matches(&self, graph_adj: &EdgeWeightedDigraph, commodities: &[Demand]) -> bool {
+        self.fingerprint == fingerprint(graph_adj, commodities)
+    }
+
+    pub fn get_iterations(&self) -> i32 {
+        self.iterations
+    }
+
+    pub fn get_initial_delay(&self) -> f64 {
+        self.initial_delay
+    }
+
+    pub fn get_delay(&self) -> f64 {
+        self.delay
+    }
+
+    pub fn get_lower_bound(&self) -> f64 {
+        self.lower_bound
+    }
+
+    pub fn get_best(&self) -> (i32, f64) {
+        self.best
+    }
+
+    pub fn get_unrouted(&self) -> &[i32] {
+        &self.unrouted
+    }
+
+    pub fn get_ends(&self) -> &BTreeMap<i32, (i32, i32)> {
+        &self.ends
+    }
+
+    pub fn get_current_paths(&self) -> &BTreeMap<i32, Vec<DirectedEdge>> {
+        &self.current_paths
+    }
+
+    pub fn get_active_paths(&self) -> &BTreeMap<i32, KeyedPaths> {
+        &self.active_paths
+    }
+
+    pub fn get_path_flows(&self) -> &BTreeMap<i32, Vec<(String, f64)>> {
+        &self.path_flows
+    }
+
+    pub fn get_edge_flows(&self) -> &[(DirectedEdge, CommodityFlows)] {
+        &self.edge_flows
+    }
+
+    /// возвращает графу стоимости дуг на момент сохранения
+    pub fn restore_edge_costs(&self, graph_adj: &mut EdgeWeightedDigraph) {
+        for e in &self.edge_costs {
+            graph_adj.update_edge_by_id(e.get_id(), e.get_cost());
+        }
+    }
+
+    /// Запись в файл: первая строка "projection-checkpoint <версия>", далее по строке на элемент состояния.
+    /// Файл сначала пишется рядом под временным именем и затем переименовывается, чтобы сбой во время записи
+    /// не испортил предыдущее состояние
+    pub fn write(&self, path: &str) {
+        let (v_count, e_count, commodities, demand) = self.fingerprint;
+        let mut data = format!("projection-checkpoint {}\n", CHECKPOINT_VERSION);
+        data.push_str(&format!("problem {} {} {} {:016x}\n", v_count, e_count, commodities, demand));
+        data.push_str(&format!("state {} {} {} {}\n", self.iterations, bits(self.initial_delay), bits(self.delay), bits(self.lower_bound)));
+        data.push_str(&format!("best {} {}\n", self.best.0, bits(self.best.1)));
+        for index in &self.unrouted {
+            data.push_str(&format!("unrouted {}\n", index));
+        }
+        for (index, (s, t)) in &self.ends {
+            data.push_str(&format!("ends {} {} {}\n", index, s, t));
+        }
+        for (index, path) in &self.current_paths {
+            data.push_str(&format!("current {}{}\n", index, edges_to_str(path)));
+        }
+        for (index, paths) in &self.active_paths {
+            for (key, path) in paths {
+                data.push_str(&format!("active {} {}{}\n", index, key, edges_to_str(path)));
+            }
+        }
+        for (index, flows) in &self.path_flows {
+            for (key, flow) in flows {
+                data.push_str(&format!("flow {} {} {}\n", index, key, bits(*flow)));
+            }
+        }
+        for (edge, commodities) in &self.edge_flows {
+            data.push_str(&format!("edge {}", edge_to_str(edge)));
+            for (commodity, (x, y)) in commodities {
+                data.push_str(&format!(" {}:{}:{}", commodity, bits(*x), bits(*y)));
+            }
+            data.push('\n');
+        }
+        data.push_str(&format!("costs{}\n", edges_to_str(&self.edge_costs)));
+
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, data).expect("Не удалось записать файл состояния");
+        fs::rename(&tmp, path).expect("Не удалось заменить файл состояния");
+    }
+
+    /// чтение состояния в формате write, файл другой версии не читается
+    pub fn read(path: &str) -> ProjectionCheckpoint {
+        let data = fs::read_to_string(path).expect("Не удалось прочитать файл состояния");
+        let mut lines = data.lines();
+        let header: Vec<&str> = lines.next().unwrap_or("").split_whitespace().collect();
+        if header.len() != 2 || header[0] != "projection-checkpoint" {
+            panic!("Файл {} не является файлом состояния метода проекции", path);
+        }
+        let version = header[1].parse::<u32>().expect("Неверная версия файла состояния");
+        if version != CHECKPOINT_VERSION {
+            panic!("Версия файла состояния {} не поддерживается, ожидается {}", version, CHECKPOINT_VERSION);
+        }
+
+        let mut checkpoint = ProjectionCheckpoint::default();
+        for line in lines.filter(|l| !l.trim().is_empty()) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let index = || fields[1].parse::<i32>().expect("Неверный индекс commodity в файле состояния");
+            match fields[0] {
+                "problem" if fields.len() == 5 => {
+                    checkpoint.fingerprint = (
+                        fields[1].parse().expect("Неверное число вершин в файле состояния"),
+                        fields[2].parse().expect("Неверное число дуг в файле состояния"),
+                        fields[3].parse().expect("Неверное число commodity в файле состояния"),
+                        u64::from_str_radix(fields[4], 16).expect("Неверный demand в файле состояния")
+                    );
+                }
+                "state" if fields.len() == 5 => {
+                    checkpoint.iterations = fields[1].parse().expect("Неверное число итераций в файле состояния");
+                    checkpoint.initial_delay = from_bits(fields[2]);
+                    checkpoint.delay = from_bits(fields[3]);
+                    checkpoint.lower_bound = from_bits(fields[4]);
+                }
+                "best" if fields.len() == 3 => {
+                    checkpoint.best = (fields[1].parse().expect("Неверная итерация в файле состояния"), from_bits(fields[2]));
+                }
+                "unrouted" if fields.len() == 2 => checkpoint.unrouted.push(index()),
+                "ends" if fields.len() == 4 => {
+                    let s = fields[2].parse().expect("Неверный source в файле состояния");
+                    let t = fields[3].parse().expect("Неверный target в файле состояния");
+                    checkpoint.ends.insert(index(), (s, t));
+                }
+                "current" if fields.len() >= 2 => {
+                    checkpoint.current_paths.insert(index(), fields[2..].iter().map(|f| edge_from_str(f)).collect());
+                }
+                "active" if fields.len() >= 3 => {
+                    let path = fields[3..].iter().map(|f| edge_from_str(f)).collect();
+                    checkpoint.active_paths.entry(index()).or_default().push((fields[2].to_string(), path));
+                }
+                "flow" if fields.len() == 4 => {
+                    checkpoint.path_flows.entry(index()).or_default().push((fields[2].to_string(), from_bits(fields[3])));
+                }
+                "edge" if fields.len() >= 2 => {
+                    let commodities = fields[2..].iter().map(|f| {
+                        let parts: Vec<&str> = f.split(':').collect();
+                        if parts.len() != 3 {
+                            panic!("Неверный поток commodity в файле состояния: {}", f);
+                        }
+                        (parts[0].parse::<i32>().expect("Неверный индекс commodity в файле состояния"), (from_bits(parts[1]), from_bits(parts[2])))
+                    }).collect();
+                    checkpoint.edge_flows.push((edge_from_str(fields[1]), commodities));
+                }
+                "costs" => checkpoint.edge_costs = fields[1..].iter().map(|f| edge_from_str(f)).collect(),
+                _ => panic!("Неверная строка в файле состояния: {}", line)
+            }
+        }
+        checkpoint
+    }
+}
+
+fn fingerprint(graph_adj: &EdgeWeightedDigraph, commodities: &[Demand]) -> (i32, i32, usize, u64) {
+    let demand = commodities.iter().fold(0f64, |sum, c| sum + c.get_demand_vale());
+    (graph_adj.get_v_count(), graph_adj.get_e_count(), commodities.len(), demand.to_bits())
+}
+
+fn bits(value: f64) -> String {
+    format!("{:016x}", value.to_bits())
+}
+
+fn from_bits(field: &str) -> f64 {
+    f64::from_bits(u64::from_str_radix(field, 16).expect("Неверное число в файле состояния"))
+}
+
+/// дуга в виде id:from:to:cost:capacity
+fn edge_to_str(e: &DirectedEdge) -> String {
+    format!("{}:{}:{}:{}:{}", e.get_id(), e.from(), e.to(), bits(e.get_cost()), bits(e.get_capacity()))
+}
+
+fn edges_to_str(edges: &[DirectedEdge]) -> String {
+    edges.iter().map(|e| format!(" {}", edge_to_str(e))).collect()
+}
+
+fn edge_from_str(field: &str) -> DirectedEdge {
+    let parts: Vec<&str> = field.split(':').collect();
+    if parts.len() != 5 {
+        panic!("Неверная дуга в файле состояния: {}", field);
+    }
+    let number = |k: usize| parts[k].parse::<i32>().expect("Неверная дуга в файле состояния");
+    DirectedEdge::new(number(0), number(1), number(2), from_bits(parts[3]), from_bits(parts[4]))
+}
@@ -0,0 +1,259 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::constrained_path::PathConstraint;
+use crate::dijkstra_find_path::TieBreak;
+use crate::flow_solution::{CommodityFlow, FlowSolution};
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::max_flow::dinic;
+use crate::structure_xml::Demand;
+use crate::utils_graph::path_key;
+
+/// Результат задачи максимального одновременного потока
+#[derive(Debug, Clone)]
+pub struct ConcurrentFlowResult {
+    lambda: f64,
+    upper_bound: f64,
+    solution: FlowSolution,
+    dual_lengths: Vec<(DirectedEdge, f64)>,
+    phases: i32,
+    unrouted: Vec<i32>
+}
+
+impl ConcurrentFlowResult {
+
+    /// во сколько раз можно увеличить всю матрицу нагрузки, найденная маршрутизация это обеспечивает
+    pub fn get_lambda(&self) -> f64 {
+        self.lambda
+    }
+
+    /// верхняя оценка оптимального lambda по двойственным длинам: D(l) / alpha(l)
+    pub fn get_upper_bound(&self) -> f64 {
+        self.upper_bound
+    }
+
+    /// маршрутизация исходной матрицы нагрузки, увеличенной в lambda раз
+    pub fn get_solution(&self) -> &FlowSolution {
+        &self.solution
+    }
+
+    /// длины дуг, на которых достигается верхняя оценка
+    pub fn get_dual_lengths(&self) -> &[(DirectedEdge, f64)] {
+        &self.dual_lengths
+    }
+
+    pub fn get_phases(&self) -> i32 {
+        self.phases
+    }
+
+    /// commodity без пути от источника к стоку, для них lambda не определена
+    pub fn get_unrouted(&self) -> &[i32] {
+        &self.unrouted
+    }
+}
+
+/// Максимальный одновременный многопродуктовый поток (Garg-Könemann, вариант Fleischer) с точностью epsilon.
+/// Дуги линка с общей пропускной способностью (UNDIRECTED) используют одну длину и одну емкость
+pub fn max_concurrent_flow(graph_adj: &EdgeWeightedDigraph, commodities: &[Demand], epsilon: f64) -> ConcurrentFlowResult {
+    let edges: Vec<DirectedEdge> = graph_adj.edges().into_iter()
+        .filter(|e| graph_adj.is_edge_enabled(e) && e.get_capacity() > 0f64)
+        .collect();
+
+    // ресурс - то, что несет емкость: дуга или линк UNDIRECTED
+    let mut resource_of: HashMap<i32, usize> = HashMap::new();
+    let mut capacity = vec![];
+    for e in &edges {
+        let twin = graph_adj.get_shared_capacity_twin(e).and_then(|t| resource_of.get(&t.get_id()).copied());
+        match twin {
+            Some(r) => {
+                resource_of.insert(e.get_id(), r);
+            }
+            None => {
+                resource_of.insert(e.get_id(), capacity.len());
+                capacity.push(e.get_capacity());
+            }
+        }
+    }
+
+    // граф, в котором стоимость дуги - ее двойственная длина, дуги без емкости не используются
+    let mut work = graph_adj.clone();
+    for e in graph_adj.edges() {
+        if e.get_capacity() <= 0f64 {
+            work.set_edge_enabled_by_id(e.get_id(), false);
+        }
+    }
+
+    // commodity с ограничениями на пути идут только по допустимым путям, максимальный поток без ограничений
+    // при этом остается верхней оценкой и годится для масштабирования нагрузки
+    let constraints: Vec<PathConstraint> = commodities.iter().map(|c| PathConstraint::for_demand(graph_adj, c)).collect();
+    let mut ends = vec![];
+    let mut unrouted = vec![];
+    for (index, commodity) in commodities.iter().enumerate() {
+        let s = graph_adj.get_node_index(&commodity.get_source());
+        let t = graph_adj.get_node_index(&commodity.get_target());
+        let pair = match (s, t) {
+            (Some(s), Some(t)) if s != t && commodity.get_demand_vale() > 0f64
+                && constraints[index].shortest_path(&mut work, s, t, TieBreak::LowestEdgeIds).is_some() => {
+                let z = dinic(&work, s, t).get_value();
+                if z > 0f64 { Some((s, t, z)) } else { None }
+            }
+            _ => None
+        };
+        if pair.is_none() {
+            unrouted.push(index as i32);
+        }
+        ends.push(pair);
+    }
+    let routed: Vec<usize> = (0..commodities.len()).filter(|&j| ends[j].is_some()).collect();
+    if routed.is_empty() {
+        return ConcurrentFlowResult {
+            lambda: 0f64,
+            upper_bound: 0f64,
+            solution: FlowSolution::default(),
+            dual_lengths: vec![],
+            phases: 0,
+            unrouted
+        };
+    }
+
+    // масштабируем нагрузку так, чтобы оптимальное lambda лежало в [1, k]: beta = min z_j / d_j, lambda* <= beta
+    let k = routed.len() as f64;
+    let beta = routed.iter()
+        .map(|&j| ends[j].unwrap().2 / commodities[j].get_demand_vale())
+        .fold(f64::INFINITY, f64::min);
+    let mut demand: Vec<f64> = commodities.iter().map(|c| c.get_demand_vale() * beta / k).collect();
+
+    let m = capacity.len() as f64;
+    let delta = (m / (1f64 - epsilon)).powf(-1f64 / epsilon);
+    let mut length: Vec<f64> = capacity.iter().map(|c| delta / c).collect();
+    let mut d_l: f64 = length.iter().zip(&capacity).map(|(l, c)| l * c).sum();
+    for e in &edges {
+        work.update_edge_by_id(e.get_id(), length[resource_of[&e.get_id()]]);
+    }
+
+    // если фаз слишком много, нагрузка удваивается (lambda* больше ожидаемого)
+    let phase_limit = (2f64 / epsilon * ((1f64 + epsilon) / delta).ln() / (1f64 + epsilon).ln()).ceil() as i32;
+    let mut phases = 0;
+    let mut phases_since_scaling = 0;
+    let mut path_flows: Vec<BTreeMap<String, (Vec<DirectedEdge>, f64)>> = vec![BTreeMap::new(); commodities.len()];
+    let mut upper_bound = f64::INFINITY;
+    let mut dual_lengths = vec![];
+
+    while d_l < 1f64 {
+        for &j in &routed {
+            let (s, t, _) = ends[j].unwrap();
+            let mut remaining = demand[j];
+            while d_l < 1f64 && remaining > 0f64 {
+                let path = constraints[j].shortest_path(&mut work, s, t, TieBreak::LowestEdgeIds).expect("Путь не найден");
+                let bottleneck = path.iter().map(|e| capacity[resource_of[&e.get_id()]]).fold(f64::INFINITY, f64::min);
+                let u = f64::min(remaining, bottleneck);
+                remaining -= u;
+                let entry = path_flows[j].entry(path_key(&path)).or_insert((path.clone(), 0f64));
+                entry.1 += u;
+                // l_e = l_e * (1 + epsilon * u / c_e)
+                for e in &path {
+                    let r = resource_of[&e.get_id()];
+                    let increase = length[r] * epsilon * u / capacity[r];
+                    length[r] += increase;
+                    d_l += increase * capacity[r];
+                    work.update_edge_by_id(e.get_id(), length[r]);
+                    if let Some(twin) = graph_adj.get_shared_capacity_twin(e) {
+                        work.update_edge_by_id(twin.get_id(), length[r]);
+                    }
+                }
+            }
+        }
+        phases += 1;
+        phases_since_scaling += 1;
+
+        // двойственная оценка: lambda* <= D(l) / sum_j d_j dist_l(s_j, t_j)
+        let mut alpha = 0f64;
+        for &j in &routed {
+            let (s, t, _) = ends[j].unwrap();
+            let path = constraints[j].shortest_path(&mut work, s, t, TieBreak::LowestEdgeIds).expect("Путь не найден");
+            alpha += commodities[j].get_demand_vale() * work.path_cost(&path);
+        }
+        if alpha > 0f64 && d_l / alpha < upper_bound {
+            upper_bound = d_l / alpha;
+            dual_lengths = edges.iter().map(|e| (*e, length[resource_of[&e.get_id()]])).collect();
+        }
+
+        if d_l < 1f64 && phases_since_scaling >= phase_limit {
+            demand.iter_mut().for_each(|d| *d *= 2f64);
+            phases_since_scaling = 0;
+        }
+    }
+
+    // поток, набранный алгоритмом, масштабируем до допустимого: максимальная загрузка ресурса равна 1
+    let mut load = vec![0f64; capacity.len()];
+    for flows in &path_flows {
+        for (path, flow) in flows.values() {
+            for e in path {
+                load[resource_of[&e.get_id()]] += flow;
+            }
+        }
+    }
+    let mu = load.iter().zip(&capacity).map(|(l, c)| l / c).fold(0f64, f64::max);
+    let lambda = routed.iter()
+        .map(|&j| path_flows[j].values().map(|(_, f)| f).sum::<f64>() / mu / commodities[j].get_demand_vale())
+        .fold(f64::INFINITY, f64::min);
+
+    // маршрутизация lambda * d_j для каждого commodity
+    let solution = FlowSolution::new(commodities.iter().enumerate().map(|(j, c)| {
+        let total: f64 = path_flows[j].values().map(|(_, f)| f).sum();
+        let scale = if total > 0f64 { lambda * c.get_demand_vale() / total } else { 0f64 };
+        CommodityFlow::new(
+            c.get_source(),
+            c.get_target(),
+            c.get_demand_vale() * lambda,
+            path_flows[j].values().map(|(path, f)| FlowSolution::path_flow(graph_adj, path, f * scale)).collect()
+        )
+    }).collect());
+
+    ConcurrentFlowResult {
+        lambda,
+        upper_bound,
+        solution,
+        dual_lengths,
+        phases,
+        unrouted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(arcs: &[(i32, i32, f64, f64)]) -> EdgeWeightedDigraph {
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(vec!["a".to_string(), "b".to_string()], arcs);
+        graph
+    }
+
+    fn demand(value: f64) -> Vec<Demand> {
+        vec![Demand::new("d".to_string(), "a".to_string(), "b".to_string(), value)]
+    }
+
+    fn assert_near_optimal(result: &ConcurrentFlowResult, optimum: f64, epsilon: f64) {
+        assert!(result.get_lambda() <= optimum * (1f64 + 1e-9), "lambda {}", result.get_lambda());
+        assert!(result.get_lambda() >= (1f64 - epsilon).powi(3) * optimum, "lambda {}", result.get_lambda());
+        assert!(result.get_lambda() <= result.get_upper_bound() * (1f64 + 1e-9));
+    }
+
+    #[test]
+    fn single_link_gives_capacity_over_demand() {
+        let result = max_concurrent_flow(&graph(&[(0, 1, 1f64, 10f64)]), &demand(4f64), 0.05);
+        assert_near_optimal(&result, 2.5, 0.05);
+        let flow: f64 = result.get_solution().get_commodities()[0].get_paths().iter().map(|p| p.get_flow()).sum();
+        assert!((flow - 4f64 * result.get_lambda()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parallel_links_carry_separate_flows() {
+        let result = max_concurrent_flow(&graph(&[(0, 1, 1f64, 10f64), (0, 1, 1f64, 10f64)]), &demand(4f64), 0.05);
+        // обе дуги несут поток, загрузка каждой не больше ее емкости
+        assert_near_optimal(&result, 5f64, 0.05);
+        let paths = result.get_solution().get_commodities()[0].get_paths();
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|p| p.get_flow() <= 10f64 * (1f64 + 1e-9)));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::dijkstra_find_path::{DijkstraSP, TieBreak};
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::structure_xml::Demand;
+
+/// Кратчайший путь от s до t не более чем из max_hops дуг (алгоритм Беллмана-Форда по слоям числа дуг).
+/// Путь возвращается в обратном порядке, как в DijkstraSP::path_to
+pub fn hop_constrained_path(graph_adj: &EdgeWeightedDigraph, s: i32, t: i32, max_hops: usize) -> Option<Vec<DirectedEdge>> {
+    let n = graph_adj.get_v_count() as usize;
+    let mut dist = vec![f64::INFINITY; n];
+    dist[s as usize] = 0f64;
+    // layers[k][w] - последняя дуга пути в w из k + 1 дуг, если он короче путей из меньшего числа дуг
+    let mut layers: Vec<Vec<Option<DirectedEdge>>> = vec![];
+    for _ in 0..max_hops {
+        let mut next = dist.clone();
+        let mut parent = vec![None; n];
+        let mut changed = false;
+        for v in (0..n).filter(|&v| dist[v].is_finite()) {
+            for e in graph_adj.edge_list(v) {
+                let w = e.to() as usize;
+                if dist[v] + e.get_cost() < next[w] {
+                    next[w] = dist[v] + e.get_cost();
+                    parent[w] = Some(e);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+        layers.push(parent);
+        dist = next;
+    }
+    if !dist[t as usize].is_finite() {
+        return None;
+    }
+    let mut path = vec![];
+    let mut v = t as usize;
+    for parent in layers.iter().rev() {
+        if let Some(e) = parent[v] {
+            path.push(e);
+            v = e.from() as usize;
+        }
+    }
+    Some(path)
+}
+
+/// Ограничения на пути одного commodity: максимальное число линков и список допустимых путей
+#[derive(Debug, Clone, Default)]
+pub struct PathConstraint {
+    max_hops: Option<usize>,
+    admissible: Vec<Vec<DirectedEdge>>
+}
+
+impl PathConstraint {
+
+    /// ограничения demand в терминах дуг графа; допустимый путь, который нельзя пройти по работающим дугам, отбрасывается
+    pub fn for_demand(graph_adj: &EdgeWeightedDigraph, demand: &Demand) -> PathConstraint {
+        let source = graph_adj.get_node_index(&demand.get_source());
+        let target = graph_adj.get_node_index(&demand.get_target());
+        let admissible = match (source, target) {
+            (Some(s), Some(t)) => demand.get_admissible_paths().iter()
+                .filter_map(|links| links_to_path(graph_adj, s, t, links))
+                .collect(),
+            _ => vec![]
+        };
+        PathConstraint {
+            max_hops: demand.get_max_path_length(),
+            admissible
+        }
+    }
+
+    /// допускают ли ограничения путь, ребра в обратном порядке
+    pub fn allows(&self, path: &[DirectedEdge]) -> bool {
+        self.max_hops.is_none_or(|h| path.len() <= h)
+            && (self.admissible.is_empty() || self.admissible.iter().any(|p| p.iter().map(|e| e.get_id()).eq(path.iter().map(|e| e.get_id()))))
+    }
+
+    /// кратчайший путь при текущих стоимостях дуг с учетом ограничений, путь в обратном порядке
+    pub fn shortest_path(&self, graph_adj: &mut EdgeWeightedDigraph, s: i32, t: i32, tie_break: TieBreak) -> Option<Vec<DirectedEdge>> {
+        if !self.admissible.is_empty() {
+            return self.admissible.iter()
+                .filter(|path| self.max_hops.is_none_or(|h| path.len() <= h))
+                .map(|path| (graph_adj.path_cost(path), path))
+                .filter(|(cost, _)| cost.is_finite())
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, path)| path.clone());
+        }
+        match self.max_hops {
+            Some(max_hops) => hop_constrained_path(graph_adj, s, t, max_hops),
+            None => DijkstraSP::dijkstra_with_tie_break(graph_adj, s, tie_break).path_to(t as usize)
+        }
+    }
+}
+
+/// путь из id линков от s к t в дуги графа (в обратном порядке), None - по линкам нельзя пройти от s к t
+fn links_to_path(graph_adj: &EdgeWeightedDigraph, s: i32, t: i32, links: &[String]) -> Option<Vec<DirectedEdge>> {
+    let mut path = vec![];
+    let mut v = s;
+    for link in links {
+        let e = graph_adj.get_link_edges(link).into_iter()
+            .find(|e| e.from() == v && graph_adj.is_edge_enabled(e) && graph_adj.is_node_enabled(e.to()))?;
+        v = e.to();
+        path.push(e);
+    }
+    if v != t || path.is_empty() {
+        return None;
+    }
+    path.reverse();
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a-b-c-d по дугам стоимости 1, обход a-c за 5 и прямая дуга a-d за 10
+    fn graph() -> EdgeWeightedDigraph {
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        let labels = ["a", "b", "c", "d"].iter().map(|l| l.to_string()).collect();
+        graph.graph_from_arcs(labels, &[(0, 1, 1f64, 10f64), (1, 2, 1f64, 10f64), (2, 3, 1f64, 10f64), (0, 2, 5f64, 10f64), (0, 3, 10f64, 10f64)]);
+        graph
+    }
+
+    fn ids(path: &[DirectedEdge]) -> Vec<i32> {
+        path.iter().map(|e| e.get_id()).collect()
+    }
+
+    #[test]
+    fn hop_limit_changes_shortest_path() {
+        let mut graph = graph();
+        let shortest = DijkstraSP::dijkstra_with_tie_break(&mut graph, 0, TieBreak::KeepCurrent).path_to(3).unwrap();
+        assert_eq!(ids(&shortest), vec![2, 1, 0]);
+        // без ограничения или с запасом по числу дуг путь тот же
+        assert_eq!(ids(&hop_constrained_path(&graph, 0, 3, 3).unwrap()), vec![2, 1, 0]);
+        // кратчайший путь нарушает ограничение, берется более дорогой путь из меньшего числа дуг
+        let two_hops = hop_constrained_path(&graph, 0, 3, 2).unwrap();
+        assert_eq!(ids(&two_hops), vec![2, 3]);
+        assert_eq!(graph.path_cost(&two_hops), 6f64);
+        assert_eq!(ids(&hop_constrained_path(&graph, 0, 3, 1).unwrap()), vec![4]);
+        assert!(hop_constrained_path(&graph, 1, 0, 3).is_none());
+    }
+
+    #[test]
+    fn allows_checks_hops_and_admissible_paths() {
+        let mut graph = graph();
+        let direct = hop_constrained_path(&graph, 0, 3, 1).unwrap();
+        let around = hop_constrained_path(&graph, 0, 3, 2).unwrap();
+        let long = hop_constrained_path(&graph, 0, 3, 3).unwrap();
+
+        assert!(PathConstraint::default().allows(&long));
+        let hops = PathConstraint { max_hops: Some(2), admissible: vec![] };
+        assert!(hops.allows(&direct) && hops.allows(&around));
+        assert!(!hops.allows(&long));
+
+        let demand = Demand::new("d".to_string(), "a".to_string(), "d".to_string(), 1f64)
+            .with_fixed_path(vec!["a_c".to_string(), "c_d".to_string()]);
+        let fixed = PathConstraint::for_demand(&graph, &demand);
+        assert_eq!(fixed.admissible.len(), 1);
+        assert!(fixed.allows(&around));
+        assert!(!fixed.allows(&direct) && !fixed.allows(&long));
+        assert_eq!(ids(&fixed.shortest_path(&mut graph, 0, 3, TieBreak::KeepCurrent).unwrap()), ids(&around));
+    }
+}
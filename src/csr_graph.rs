@@ -0,0 +1,109 @@
+use crate::bag::Bag;
+use crate::graph::DirectedEdge;
+
+/// Списки смежности в формате CSR (compressed sparse row):
+/// исходящие дуги вершины v лежат подряд в диапазоне offsets[v]..offsets[v + 1],
+/// стоимость и пропускная способность хранятся отдельными столбцами
+#[derive(Debug, Clone)]
+pub struct CsrAdjacency {
+    offsets: Vec<usize>,
+    ids: Vec<i32>,
+    from: Vec<i32>,
+    to: Vec<i32>,
+    costs: Vec<f64>,
+    capacities: Vec<f64>
+}
+
+impl CsrAdjacency {
+
+    /// строит CSR из списков смежности, порядок дуг внутри вершины совпадает с порядком обхода Bag
+    pub fn from_bags(adj: &[Bag<DirectedEdge>]) -> CsrAdjacency {
+        let e_count = adj.iter().map(|b| b.size() as usize).sum();
+        let mut csr = CsrAdjacency {
+            offsets: Vec::with_capacity(adj.len() + 1),
+            ids: Vec::with_capacity(e_count),
+            from: Vec::with_capacity(e_count),
+            to: Vec::with_capacity(e_count),
+            costs: Vec::with_capacity(e_count),
+            capacities: Vec::with_capacity(e_count)
+        };
+        csr.offsets.push(0);
+        for bag in adj {
+            for e in bag.iter() {
+                csr.ids.push(e.get_id());
+                csr.from.push(e.from());
+                csr.to.push(e.to());
+                csr.costs.push(e.get_cost());
+                csr.capacities.push(e.get_capacity());
+            }
+            csr.offsets.push(csr.ids.len());
+        }
+        csr
+    }
+
+    pub fn edge_list(&self, v: usize) -> CsrIter<'_> {
+        CsrIter {
+            csr: self,
+            k: self.offsets[v],
+            end: self.offsets[v + 1]
+        }
+    }
+
+    pub fn get_edge(&self, k: usize) -> DirectedEdge {
+        DirectedEdge::new(self.ids[k], self.from[k], self.to[k], self.costs[k], self.capacities[k])
+    }
+
+    /// позиция дуги с данным id в строке вершины v, параллельные дуги v->w различаются только id
+    pub fn position_of_id(&self, v: usize, id: i32) -> Option<usize> {
+        (self.offsets[v]..self.offsets[v + 1]).find(|&k| self.ids[k] == id)
+    }
+
+    pub fn get_costs(&self) -> &[f64] {
+        &self.costs
+    }
+
+    pub fn get_capacities(&self) -> &[f64] {
+        &self.capacities
+    }
+
+    pub fn set_cost(&mut self, k: usize, cost: f64) {
+        self.costs[k] = cost;
+    }
+
+    pub fn set_capacity(&mut self, k: usize, capacity: f64) {
+        self.capacities[k] = capacity;
+    }
+
+    /// обратное преобразование в списки смежности, порядок обхода дуг сохраняется
+    pub fn to_bags(&self) -> Vec<Bag<DirectedEdge>> {
+        let v_count = self.offsets.len() - 1;
+        let mut adj = Vec::with_capacity(v_count);
+        for v in 0..v_count {
+            let mut bag = Bag::get_empty_bag();
+            // Bag добавляет элементы в начало списка
+            for k in (self.offsets[v]..self.offsets[v + 1]).rev() {
+                bag.add(self.get_edge(k));
+            }
+            adj.push(bag);
+        }
+        adj
+    }
+}
+
+pub struct CsrIter<'a> {
+    csr: &'a CsrAdjacency,
+    k: usize,
+    end: usize
+}
+
+impl<'a> Iterator for CsrIter<'a> {
+    type Item = DirectedEdge;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.k >= self.end {
+            return None;
+        }
+        let e = self.csr.get_edge(self.k);
+        self.k += 1;
+        Some(e)
+    }
+}
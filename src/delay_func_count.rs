@@ -1,6 +1,6 @@
 pub fn count_delay_value(x: f64, c: f64, p: f64) -> f64 {
     if x > p * c {
-        let k = count_first_derivative(x,c,p);
+        let k = count_first_derivative(p * c, c, p);
         let m = count_second_derivative(p * c, c, p);
         return p * c / (c - p * c) + k * (x - p*c) + m / 2f64 * (x - p*c).powi(2);
     }
@@ -29,4 +29,86 @@ pub fn count_second_derivative(x: f64, c: f64, p: f64) -> f64 {
         return (2f64 * c) / (c - p * c).powi(3);
     }
     return (2f64 * c) / (c - x).powi(3)
-}
\ No newline at end of file
+}
+
+/// границы участков функции Fortz-Thorup по загрузке линка x / c и наклоны на участках
+const FORTZ_THORUP_BOUNDS: [f64; 6] = [0f64, 1f64 / 3f64, 2f64 / 3f64, 0.9, 1f64, 1.1];
+const FORTZ_THORUP_SLOPES: [f64; 6] = [1f64, 3f64, 10f64, 70f64, 500f64, 5000f64];
+
+fn fortz_thorup_segment(x: f64, c: f64) -> usize {
+    let u = x / c;
+    (1..FORTZ_THORUP_BOUNDS.len()).take_while(|&i| u >= FORTZ_THORUP_BOUNDS[i]).last().unwrap_or(0)
+}
+
+/// выпуклая кусочно-линейная функция затрат Fortz-Thorup: phi(0) = 0, наклон растет от 1 до 5000 с загрузкой линка
+pub fn count_fortz_thorup_value(x: f64, c: f64) -> f64 {
+    let mut value = 0f64;
+    for i in 0..FORTZ_THORUP_BOUNDS.len() {
+        let begin = FORTZ_THORUP_BOUNDS[i] * c;
+        let end = FORTZ_THORUP_BOUNDS.get(i + 1).map_or(f64::INFINITY, |b| b * c);
+        if x <= begin {
+            break;
+        }
+        value += FORTZ_THORUP_SLOPES[i] * (f64::min(x, end) - begin);
+    }
+    value
+}
+
+/*
+в точках излома производной нет, возвращаем правую производную - она является субградиентом
+ */
+pub fn count_fortz_thorup_derivative(x: f64, c: f64) -> f64 {
+    FORTZ_THORUP_SLOPES[fortz_thorup_segment(x, c)]
+}
+
+/*
+вторая производная кусочно-линейной функции равна 0 везде, кроме точек излома,
+поэтому для шага метода проекции берем кривизну сглаженной функции, у которой производная
+линейно растет по участку от его наклона до наклона следующего участка.
+На последнем участке наклон считаем растущим так же, как на предыдущем
+ */
+pub fn count_fortz_thorup_second_derivative(x: f64, c: f64) -> f64 {
+    let last = FORTZ_THORUP_BOUNDS.len() - 1;
+    let i = usize::min(fortz_thorup_segment(x, c), last - 1);
+    (FORTZ_THORUP_SLOPES[i + 1] - FORTZ_THORUP_SLOPES[i]) / ((FORTZ_THORUP_BOUNDS[i + 1] - FORTZ_THORUP_BOUNDS[i]) * c)
+}
+
+/// Функция затрат на линке, по ней метод проекции считает стоимость дуг и шаг
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CostModel {
+    /// задержка Клейнрока x / (c - x), после p * c - квадратичное продолжение
+    Kleinrock { p: f64 },
+    /// сглаженная максимальная загрузка линков: сумма exp(beta * (x / c - 1)).
+    /// Логарифм суммы (log-sum-exp) отличается от max x / c не более чем на ln(m) / beta,
+    /// а минимумы суммы и ее логарифма совпадают, поэтому задача остается сепарабельной по линкам
+    MinMaxUtilization { beta: f64 },
+    /// кусочно-линейная функция Fortz-Thorup, стандартная целевая функция traffic engineering для IGP
+    FortzThorup,
+}
+
+impl CostModel {
+
+    pub fn value(&self, x: f64, c: f64) -> f64 {
+        match *self {
+            CostModel::Kleinrock { p } => count_delay_value(x, c, p),
+            CostModel::MinMaxUtilization { beta } => (beta * (x / c - 1f64)).exp(),
+            CostModel::FortzThorup => count_fortz_thorup_value(x, c),
+        }
+    }
+
+    pub fn first_derivative(&self, x: f64, c: f64) -> f64 {
+        match *self {
+            CostModel::Kleinrock { p } => count_first_derivative(x, c, p),
+            CostModel::MinMaxUtilization { beta } => beta / c * (beta * (x / c - 1f64)).exp(),
+            CostModel::FortzThorup => count_fortz_thorup_derivative(x, c),
+        }
+    }
+
+    pub fn second_derivative(&self, x: f64, c: f64) -> f64 {
+        match *self {
+            CostModel::Kleinrock { p } => count_second_derivative(x, c, p),
+            CostModel::MinMaxUtilization { beta } => (beta / c).powi(2) * (beta * (x / c - 1f64)).exp(),
+            CostModel::FortzThorup => count_fortz_thorup_second_derivative(x, c),
+        }
+    }
+}
@@ -1,47 +1,123 @@
-use std::collections::HashMap;
 use crate::index_min_pq::IndexMinPQ;
 use crate::graph::EdgeWeightedDigraph;
 use crate::graph::DirectedEdge;
 
+/// относительная точность, с которой две длины пути считаются равными
+const TIE_EPS: f64 = 1e-12;
+
+/// Правило выбора между путями одинаковой длины.
+/// Без него результат зависел от порядка ребер во входном файле и от порядка обхода Bag
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieBreak {
+    /// путь с наименьшим числом ребер, при равенстве - ребро-предшественник с наименьшим id
+    FewestHops,
+    /// ребро-предшественник с наименьшим id
+    LowestEdgeIds,
+    /// текущий путь commodity остается, если новый путь не короче его (внутри Дейкстры - правило LowestEdgeIds)
+    KeepCurrent,
+}
+
+impl TieBreak {
+
+    /// возвращает true, если вместо найденного пути candidate нужно оставить текущий путь current
+    pub fn prefer_current(&self, graph: &EdgeWeightedDigraph, current: &[DirectedEdge], candidate: &[DirectedEdge]) -> bool {
+        if *self != TieBreak::KeepCurrent {
+            return false;
+        }
+        let current_cost = graph.path_cost(current);
+        let candidate_cost = graph.path_cost(candidate);
+        current_cost <= candidate_cost || is_tie(current_cost, candidate_cost)
+    }
+}
+
+fn is_tie(a: f64, b: f64) -> bool {
+    a.is_finite() && b.is_finite() && (a - b).abs() <= TIE_EPS * f64::max(a.abs(), b.abs())
+}
+
 pub struct DijkstraSP {
-    edge_to: HashMap<i32, DirectedEdge>,
+    edge_to: Vec<Option<DirectedEdge>>,
     dist_to: Vec<f64>,
-    pq: IndexMinPQ
+    hops: Vec<i32>,
+    done: Vec<bool>,
+    tie_break: TieBreak,
+    pq: IndexMinPQ<f64>
 }
 
 impl DijkstraSP {
 
     pub fn dijkstra(graph: &mut EdgeWeightedDigraph, s:i32) -> DijkstraSP {
+        Self::dijkstra_with_tie_break(graph, s, TieBreak::FewestHops)
+    }
+
+    pub fn dijkstra_with_tie_break(graph: &mut EdgeWeightedDigraph, s:i32, tie_break: TieBreak) -> DijkstraSP {
+        let pq = IndexMinPQ::get_index_from_size(graph.get_v_count() as usize);
+        Self::dijkstra_with_queue(graph, s, tie_break, pq)
+    }
+
+    /// Дейкстра на d-арной куче, arity >= 2
+    pub fn dijkstra_with_arity(graph: &mut EdgeWeightedDigraph, s:i32, arity: usize) -> DijkstraSP {
+        let pq = IndexMinPQ::with_arity(graph.get_v_count() as usize, arity).unwrap_or_else(|e| panic!("{}", e));
+        Self::dijkstra_with_queue(graph, s, TieBreak::FewestHops, pq)
+    }
+
+    fn dijkstra_with_queue(graph: &mut EdgeWeightedDigraph, s:i32, tie_break: TieBreak, pq: IndexMinPQ<f64>) -> DijkstraSP {
+        let v_count = graph.get_v_count() as usize;
         let mut dij = DijkstraSP {
-            edge_to: HashMap::new(),
-            dist_to: vec![f64::INFINITY; graph.get_v_count() as usize],
-            pq: IndexMinPQ::get_index_from_size(graph.get_v_count())
+            edge_to: vec![None; v_count],
+            dist_to: vec![f64::INFINITY; v_count],
+            hops: vec![0; v_count],
+            done: vec![false; v_count],
+            tie_break,
+            pq
         };
         dij.dist_to[s as usize] = 0.0;
-        dij.pq.insert(s as usize, 0.0);
+        dij.pq.insert(s as usize, 0.0).expect("Источник вне графа");
 
-        while !dij.pq.is_empty() {
-            let v = dij.pq.del_min();
-            Self::relax(&mut dij, &graph, v)
+        while let Ok(v) = dij.pq.del_min() {
+            dij.done[v] = true;
+            Self::relax(&mut dij, graph, v)
         }
         dij
     }
 
     fn relax(dij: &mut DijkstraSP, graph: &EdgeWeightedDigraph, v: usize){
-        for gr in graph.edge_list(v).iter() {
+        for gr in graph.edge_list(v) {
             let w = gr.to() as usize;
-            if dij.dist_to[w] > dij.dist_to[v] + gr.get_cost() {
-                dij.dist_to[w] = dij.dist_to[v] + gr.get_cost();
-                dij.edge_to.insert(w as i32, *gr);
+            let dist = dij.dist_to[v] + gr.get_cost();
+            if is_tie(dist, dij.dist_to[w]) {
+                // вершина уже извлечена из очереди - ее дерево путей не меняем, иначе возможен цикл по ребрам нулевой длины
+                if !dij.done[w] && dij.prefer(&gr, v, w) {
+                    dij.edge_to[w] = Some(gr);
+                    dij.hops[w] = dij.hops[v] + 1;
+                }
+                continue;
+            }
+            if dij.dist_to[w] > dist {
+                dij.dist_to[w] = dist;
+                dij.edge_to[w] = Some(gr);
+                dij.hops[w] = dij.hops[v] + 1;
                 if dij.pq.contains(w) {
-                    dij.pq.change(w, dij.dist_to[w]);
+                    dij.pq.decrease_key(w, dist).expect("Ключ в очереди не уменьшился");
                     continue;
                 }
-                dij.pq.insert(w, dij.dist_to[w])
+                dij.pq.insert(w, dist).expect("Вершина вне графа")
             }
         }
     }
 
+    /// true, если ребро edge из v лучше текущего ребра-предшественника вершины w при равной длине пути
+    fn prefer(&self, edge: &DirectedEdge, v: usize, w: usize) -> bool {
+        let current = match self.edge_to[w] {
+            Some(e) => e,
+            None => return false // w - источник
+        };
+        let hops = self.hops[v] + 1;
+        match self.tie_break {
+            TieBreak::FewestHops if hops != self.hops[w] => hops < self.hops[w],
+            _ => edge.get_id() < current.get_id()
+        }
+    }
+
     pub fn dist_to(&self, v: usize) -> f64 {
         self.dist_to[v]
     }
@@ -50,24 +126,16 @@ impl DijkstraSP {
         self.dist_to[v] >= 0.0 && self.dist_to[v] < f64::INFINITY // предусмотреть что self.dist_to[v] равен 0
     }
 
+    /// ребра пути до вершины v в обратном порядке: первым идет ребро, входящее в v
     pub fn path_to(&self, v: usize) -> Option<Vec<DirectedEdge>> {
         if !self.has_path_to(v) {
             return None
         }
         let mut result: Vec<DirectedEdge> = vec![];
-        let mut i = v as i32;
-        /*let mut e = self.edge_to.get(&i);
-        while e.is_none() {
-            result.push(*e.unwrap());
-            i = dgraph.from();
-        }*/
-        for (_, dgraph) in &self.edge_to {
-            let e = self.edge_to.get(&i);
-            if e.is_none() {
-                break;
-            }
-            result.push(*e.unwrap());
-            i = e.unwrap().from();
+        let mut e = self.edge_to[v];
+        while let Some(edge) = e {
+            result.push(edge);
+            e = self.edge_to[edge.from() as usize];
         }
         Some(result)
     }
@@ -93,3 +161,42 @@ impl DijkstraAllPairsSP {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// три пути длины 2 из a в d: a -> b -> d (дуги 0, 1), a -> c -> d (дуги 2, 3) и прямая дуга a -> d (дуга 4)
+    fn graph() -> EdgeWeightedDigraph {
+        let labels = vec!["a".to_string(), "b".to_string(), "c".to_string(), "d".to_string()];
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(labels, &[(0, 1, 1f64, 1f64), (1, 3, 1f64, 1f64), (0, 2, 1f64, 1f64), (2, 3, 1f64, 1f64), (0, 3, 2f64, 1f64)]);
+        graph
+    }
+
+    fn path_ids(graph: &mut EdgeWeightedDigraph, tie_break: TieBreak) -> Vec<i32> {
+        let sp = DijkstraSP::dijkstra_with_tie_break(graph, 0, tie_break);
+        assert_eq!(sp.dist_to(3), 2f64);
+        sp.path_to(3).unwrap().iter().map(|e| e.get_id()).collect()
+    }
+
+    #[test]
+    fn tie_break_picks_path_among_equal_costs() {
+        let mut graph = graph();
+        assert_eq!(path_ids(&mut graph, TieBreak::FewestHops), vec![4]);
+        assert_eq!(path_ids(&mut graph, TieBreak::LowestEdgeIds), vec![1, 0]);
+        // внутри Дейкстры KeepCurrent выбирает как LowestEdgeIds
+        assert_eq!(path_ids(&mut graph, TieBreak::KeepCurrent), vec![1, 0]);
+
+        // текущий путь a -> c -> d не длиннее найденного, его оставляет только KeepCurrent
+        let edges = graph.edges();
+        let edge = |id: i32| *edges.iter().find(|e| e.get_id() == id).unwrap();
+        let (current, candidate) = (vec![edge(3), edge(2)], vec![edge(1), edge(0)]);
+        assert!(TieBreak::KeepCurrent.prefer_current(&graph, &current, &candidate));
+        assert!(!TieBreak::FewestHops.prefer_current(&graph, &current, &candidate));
+        assert!(!TieBreak::LowestEdgeIds.prefer_current(&graph, &current, &candidate));
+        // более длинный текущий путь заменяется и при KeepCurrent
+        graph.update_edge_by_id(3, 1.5);
+        assert!(!TieBreak::KeepCurrent.prefer_current(&graph, &current, &candidate));
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::delay_func_count::CostModel;
+use crate::dijkstra_find_path::DijkstraSP;
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::link_utilization::{link_utilization, LinkUtilization};
+use crate::structure_xml::Demand;
+
+/// наибольший вес линка OSPF/IS-IS: метрика хранится в 16 битах, вес 0 не допускается
+pub const MAX_LINK_WEIGHT: i32 = 65535;
+
+/// Граф кратчайших путей к одному получателю: в отличие от DijkstraSP.edge_to,
+/// у вершины хранятся все исходящие дуги, лежащие на каком-либо кратчайшем пути к target
+#[derive(Debug, Clone)]
+pub struct ShortestPathDag {
+    dist: Vec<f64>,                    // длина кратчайшего пути от вершины до target
+    next_hops: Vec<Vec<DirectedEdge>>
+}
+
+impl ShortestPathDag {
+
+    /// distances[v][t] - длины кратчайших путей между всеми парами вершин при весах weight
+    fn build(graph_adj: &EdgeWeightedDigraph, distances: &[Vec<f64>], weights: &BTreeMap<i32, i32>, target: i32) -> ShortestPathDag {
+        let t = target as usize;
+        let dist: Vec<f64> = distances.iter().map(|row| row[t]).collect();
+        let next_hops = (0..dist.len())
+            .map(|v| {
+                if v == t || !dist[v].is_finite() {
+                    return vec![];
+                }
+                // веса целые, поэтому равенство длин путей проверяется точно
+                graph_adj.edge_list(v)
+                    .filter(|e| edge_weight(weights, e) + dist[e.to() as usize] == dist[v])
+                    .collect()
+            })
+            .collect();
+        ShortestPathDag {
+            dist,
+            next_hops
+        }
+    }
+
+    /// поток к target по графу: demand[v] - сколько отправляет вершина v, в каждой вершине поток делится
+    /// поровну между next hops; вершины обходятся от самых дальних к target, тогда весь входящий поток вершины уже известен
+    fn split_evenly(&self, demand: &[f64], flows: &mut BTreeMap<i32, f64>) {
+        let mut order: Vec<usize> = (0..self.dist.len()).filter(|&v| !self.next_hops[v].is_empty()).collect();
+        order.sort_by(|&a, &b| self.dist[b].total_cmp(&self.dist[a]));
+        let mut node_flow = demand.to_vec();
+        for v in order {
+            if node_flow[v] <= 0f64 {
+                continue;
+            }
+            let share = node_flow[v] / self.next_hops[v].len() as f64;
+            for e in &self.next_hops[v] {
+                *flows.entry(e.get_id()).or_insert(0f64) += share;
+                node_flow[e.to() as usize] += share;
+            }
+        }
+    }
+}
+
+/// Распределение потоков протоколом маршрутизации по состоянию каналов (OSPF/IS-IS) с ECMP:
+/// поток идет только по кратчайшим путям при заданных весах и в каждой вершине делится поровну
+/// между всеми следующими дугами с одинаковой длиной пути до получателя
+#[derive(Debug, Clone)]
+pub struct EcmpRouting {
+    edge_flows: Vec<(DirectedEdge, f64)>,
+    distances: Vec<Vec<f64>>,
+    unrouted: Vec<i32>
+}
+
+impl EcmpRouting {
+
+    /// поток на каждой работающей дуге графа
+    pub fn get_edge_flows(&self) -> &[(DirectedEdge, f64)] {
+        &self.edge_flows
+    }
+
+    /// длина кратчайшего пути от s до t при заданных весах, f64::INFINITY если пути нет
+    pub fn get_distance(&self, s: i32, t: i32) -> f64 {
+        self.distances[s as usize][t as usize]
+    }
+
+    /// индексы commodity, для которых в графе нет пути от источника к стоку
+    pub fn get_unrouted(&self) -> &[i32] {
+        &self.unrouted
+    }
+}
+
+/// маршрутизация ECMP; weights - вес каждой дуги по ее id, дуги без веса получают вес 1
+pub fn route_ecmp(graph_adj: &EdgeWeightedDigraph, commodities: &[Demand], weights: &BTreeMap<i32, i32>) -> EcmpRouting {
+    let n = graph_adj.get_v_count() as usize;
+
+    // длины кратчайших путей между всеми парами вершин
+    let mut weighted = graph_adj.clone();
+    for e in graph_adj.edges() {
+        weighted.update_edge_by_id(e.get_id(), edge_weight(weights, &e));
+    }
+    let mut distances = vec![vec![f64::INFINITY; n]; n];
+    for (s, row) in distances.iter_mut().enumerate() {
+        if !graph_adj.is_node_enabled(s as i32) {
+            continue;
+        }
+        let sp = DijkstraSP::dijkstra(&mut weighted, s as i32);
+        for (t, dist) in row.iter_mut().enumerate() {
+            if sp.has_path_to(t) {
+                *dist = sp.dist_to(t);
+            }
+        }
+    }
+
+    // нагрузка к каждому получателю от каждого отправителя
+    let mut demand_to: BTreeMap<i32, Vec<f64>> = BTreeMap::new();
+    let mut unrouted = vec![];
+    for (index, commodity) in commodities.iter().enumerate() {
+        let source = graph_adj.get_node_index(&commodity.get_source());
+        let target = graph_adj.get_node_index(&commodity.get_target());
+        match (source, target) {
+            (Some(s), Some(t)) if s != t && distances[s as usize][t as usize].is_finite() => {
+                demand_to.entry(t).or_insert_with(|| vec![0f64; n])[s as usize] += commodity.get_demand_vale();
+            }
+            _ => unrouted.push(index as i32)
+        }
+    }
+
+    let mut flows: BTreeMap<i32, f64> = BTreeMap::new();
+    for (&t, demand) in &demand_to {
+        let dag = ShortestPathDag::build(graph_adj, &distances, weights, t);
+        dag.split_evenly(demand, &mut flows);
+    }
+
+    let edge_flows = graph_adj.edges().into_iter()
+        .filter(|e| graph_adj.is_edge_enabled(e))
+        .map(|e| (e, flows.get(&e.get_id()).copied().unwrap_or(0f64)))
+        .collect();
+    EcmpRouting {
+        edge_flows,
+        distances,
+        unrouted
+    }
+}
+
+/// Оценка заданных весов: маршрутизация ECMP, загрузка линков и задержка Клейнрока
+#[derive(Debug, Clone)]
+pub struct EcmpEvaluation {
+    routing: EcmpRouting,
+    utilization: LinkUtilization,
+    delay: f64
+}
+
+impl EcmpEvaluation {
+
+    pub fn get_routing(&self) -> &EcmpRouting {
+        &self.routing
+    }
+
+    pub fn get_utilization(&self) -> &LinkUtilization {
+        &self.utilization
+    }
+
+    /// задержка Клейнрока по всем линкам, после p * c - квадратичное продолжение из delay_func_count
+    pub fn get_delay(&self) -> f64 {
+        self.delay
+    }
+}
+
+pub fn evaluate_weights(graph_adj: &EdgeWeightedDigraph, commodities: &[Demand], weights: &BTreeMap<i32, i32>, p: f64) -> EcmpEvaluation {
+    let routing = route_ecmp(graph_adj, commodities, weights);
+    let utilization = link_utilization(graph_adj, routing.get_edge_flows());
+    let delay = utilization.cost(CostModel::Kleinrock { p });
+    EcmpEvaluation {
+        routing,
+        utilization,
+        delay
+    }
+}
+
+/// веса InvCap: обратно пропорциональны пропускной способности, самая емкая дуга получает вес 1
+pub fn inv_cap_weights(graph_adj: &EdgeWeightedDigraph, max_weight: i32) -> BTreeMap<i32, i32> {
+    let edges: Vec<DirectedEdge> = graph_adj.edges().into_iter().filter(|e| graph_adj.is_edge_enabled(e)).collect();
+    let max_capacity = edges.iter().map(|e| e.get_capacity()).fold(0f64, f64::max);
+    edges.iter().map(|e| {
+        let weight = if e.get_capacity() > 0f64 { (max_capacity / e.get_capacity()).round() } else { max_weight as f64 };
+        (e.get_id(), f64::min(f64::max(weight, 1f64), max_weight as f64) as i32)
+    }).collect()
+}
+
+/// Чтение весов из файла. Строка "<линк> <вес>" задает вес обеих дуг линка,
+/// строка "<линк> <откуда> <куда> <вес>" - вес одного направления; пустые строки и строки с # пропускаются.
+/// Дуги, которых нет в файле, получают вес 1. Вес вне 1..=MAX_LINK_WEIGHT считается ошибкой
+pub fn read_weights(graph_adj: &EdgeWeightedDigraph, path: &str) -> BTreeMap<i32, i32> {
+    let data = fs::read_to_string(path).expect("Не удалось прочитать файл весов");
+    let mut weights = BTreeMap::new();
+    for line in data.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let weight = match fields.last().unwrap().parse::<i32>() {
+            Ok(weight) if (1..=MAX_LINK_WEIGHT).contains(&weight) => weight,
+            _ => panic!("Неверный вес линка {}: {}, ожидается целое от 1 до {}", fields[0], fields.last().unwrap(), MAX_LINK_WEIGHT)
+        };
+        let edges = graph_adj.get_link_edges(fields[0]);
+        if edges.is_empty() {
+            panic!("Линк {} не найден", fields[0]);
+        }
+        for e in edges {
+            let matches = match fields.len() {
+                2 => true,
+                4 => graph_adj.get_node_label(e.from()) == fields[1] && graph_adj.get_node_label(e.to()) == fields[2],
+                _ => panic!("Неверная строка в файле весов: {}", line)
+            };
+            if matches {
+                weights.insert(e.get_id(), weight);
+            }
+        }
+    }
+    weights
+}
+
+/// запись весов в формате read_weights, по строке на каждую дугу
+pub fn write_weights(graph_adj: &EdgeWeightedDigraph, weights: &BTreeMap<i32, i32>, path: &str) {
+    let mut data = String::new();
+    for e in graph_adj.edges() {
+        if let Some(weight) = weights.get(&e.get_id()) {
+            data.push_str(&format!("{} {} {} {}\n", graph_adj.get_edge_label(&e),
+                                   graph_adj.get_node_label(e.from()), graph_adj.get_node_label(e.to()), weight));
+        }
+    }
+    fs::write(path, data).expect("Не удалось записать файл весов");
+}
+
+fn edge_weight(weights: &BTreeMap<i32, i32>, e: &DirectedEdge) -> f64 {
+    weights.get(&e.get_id()).copied().unwrap_or(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> EdgeWeightedDigraph {
+        let labels = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(labels, &[(0, 1, 1f64, 10f64), (1, 2, 1f64, 10f64), (0, 2, 1f64, 10f64)]);
+        graph
+    }
+
+    fn read_text(name: &str, text: &str) -> BTreeMap<i32, i32> {
+        let path = std::env::temp_dir().join(format!("ecmp_weights_{}_{}.txt", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let weights = std::panic::catch_unwind(|| read_weights(&triangle(), path.to_str().unwrap()));
+        fs::remove_file(&path).unwrap();
+        weights.unwrap_or_else(|e| std::panic::resume_unwind(e))
+    }
+
+    #[test]
+    fn reads_weights_in_range() {
+        let weights = read_text("valid", "# веса\na_b 1\nb_c b c 65535\n");
+        assert_eq!(weights, BTreeMap::from([(0, 1), (1, MAX_LINK_WEIGHT)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Неверный вес линка a_c: 0")]
+    fn rejects_zero_weight() {
+        read_text("zero", "a_b 5\na_c 0\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "Неверный вес линка b_c: -3")]
+    fn rejects_negative_weight() {
+        read_text("negative", "b_c -3\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "Неверный вес линка a_b: 65536")]
+    fn rejects_weight_above_max() {
+        read_text("above", "a_b a b 65536\n");
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::graph::EdgeWeightedDigraph;
+use crate::link_utilization::{link_utilization, LinkUtilization};
+use crate::projection_solver::{solve_projection, solve_projection_warm, ProjectionSettings, WarmStart};
+use crate::structure_xml::Demand;
+
+/// Отказавший элемент сети
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailedElement {
+    Link(String),
+    Node(String),
+}
+
+impl fmt::Display for FailedElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailedElement::Link(id) => write!(f, "link {}", id),
+            FailedElement::Node(id) => write!(f, "node {}", id),
+        }
+    }
+}
+
+/// Состояние сети после отказа одного элемента
+#[derive(Debug, Clone)]
+pub struct FailureScenario {
+    element: FailedElement,
+    objective: f64,
+    max_utilization: f64,
+    disconnected: Vec<i32>,            // индексы commodity, которые нельзя провести после отказа
+    disconnected_volume: f64,
+    most_affected: Vec<(String, f64)>, // линк (направление) и рост его загрузки относительно сети без отказов
+    iterations: i32
+}
+
+/// Результат анализа отказов: сценарии упорядочены от самого тяжелого
+#[derive(Debug, Clone)]
+pub struct FailureAnalysis {
+    objective: f64,  // значение целевой функции сети без отказов
+    max_utilization: f64,
+    scenarios: Vec<FailureScenario>
+}
+
+impl fmt::Display for FailureAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "no failures: objective: {}, max utilization: {:.4}", self.objective, self.max_utilization)?;
+        for scenario in &self.scenarios {
+            let affected = scenario.most_affected.iter()
+                .map(|(link, delta)| format!("{} (+{:.4})", link, delta))
+                .collect::<Vec<String>>()
+                .join(", ");
+            writeln!(f, "failure {}: objective: {}, max utilization: {:.4}, disconnected demands: {} (volume {:.2}), iterations: {}, most affected: {}",
+                     scenario.element, scenario.objective, scenario.max_utilization, scenario.disconnected.len(),
+                     scenario.disconnected_volume, scenario.iterations, affected)?;
+        }
+        Ok(())
+    }
+}
+
+/// сколько линков с наибольшим ростом загрузки выводится для сценария
+const MOST_AFFECTED_COUNT: usize = 3;
+
+/// Отказы по одному: каждый линк (и каждая вершина, если with_nodes) отключается, потоки пересчитываются
+/// методом проекции с теплого старта из решения для сети без отказов.
+/// Сценарии сортируются по тяжести: объем потерянной нагрузки, затем максимальная загрузка, затем целевая функция
+pub fn analyze_failures(
+    graph_adj: &EdgeWeightedDigraph,
+    commodities: &[Demand],
+    settings: &ProjectionSettings,
+    with_nodes: bool
+) -> FailureAnalysis {
+    let cost_model = settings.get_cost_model();
+    let intact = solve_projection(&mut graph_adj.clone(), commodities, settings);
+    let intact_utilization = link_utilization(graph_adj, intact.get_edge_flows());
+    let intact_unrouted = intact.get_unrouted().to_vec();
+    let warm_start = WarmStart::from_result(&intact);
+
+    let mut elements: Vec<FailedElement> = graph_adj.get_link_ids().iter().map(|id| FailedElement::Link(id.clone())).collect();
+    if with_nodes {
+        elements.extend((0..graph_adj.get_v_count()).map(|v| FailedElement::Node(graph_adj.get_node_label(v))));
+    }
+
+    let mut scenarios = vec![];
+    for element in elements {
+        let mut graph = graph_adj.clone();
+        match &element {
+            FailedElement::Link(id) => {
+                for e in graph_adj.get_link_edges(id) {
+                    graph.set_edge_enabled_by_id(e.get_id(), false);
+                }
+            }
+            FailedElement::Node(id) => graph.disable_node(graph_adj.get_node_index(id).unwrap()),
+        }
+        let result = solve_projection_warm(&mut graph, commodities, settings, &warm_start);
+        let utilization = link_utilization(&graph, result.get_edge_flows());
+        let disconnected: Vec<i32> = result.get_unrouted().iter().copied().filter(|j| !intact_unrouted.contains(j)).collect();
+        scenarios.push(FailureScenario {
+            element,
+            objective: utilization.cost(cost_model),
+            max_utilization: utilization.max_utilization(),
+            disconnected_volume: disconnected.iter().fold(0f64, |sum, &j| sum + commodities[j as usize].get_demand_vale()),
+            disconnected,
+            most_affected: most_affected(&intact_utilization, &utilization),
+            iterations: result.get_iterations()
+        });
+    }
+    scenarios.sort_by(|a, b| {
+        b.disconnected_volume.total_cmp(&a.disconnected_volume)
+            .then(b.max_utilization.total_cmp(&a.max_utilization))
+            .then(b.objective.total_cmp(&a.objective))
+    });
+
+    FailureAnalysis {
+        objective: intact_utilization.cost(cost_model),
+        max_utilization: intact_utilization.max_utilization(),
+        scenarios
+    }
+}
+
+/// линки с наибольшим ростом загрузки после отказа
+fn most_affected(intact: &LinkUtilization, failed: &LinkUtilization) -> Vec<(String, f64)> {
+    let before: BTreeMap<(&str, &str, &str), f64> = intact.get_links().iter()
+        .map(|l| ((l.get_link_id(), l.get_from(), l.get_to()), l.get_utilization()))
+        .collect();
+    let mut deltas: Vec<(String, f64)> = failed.get_links().iter()
+        .map(|l| {
+            let old = before.get(&(l.get_link_id(), l.get_from(), l.get_to())).copied().unwrap_or(0f64);
+            (format!("{} ({} -> {})", l.get_link_id(), l.get_from(), l.get_to()), l.get_utilization() - old)
+        })
+        .filter(|(_, delta)| *delta > 0f64)
+        .collect();
+    deltas.sort_by(|a, b| b.1.total_cmp(&a.1));
+    deltas.truncate(MOST_AFFECTED_COUNT);
+    deltas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dijkstra_find_path::TieBreak;
+
+    #[test]
+    fn failure_of_parallel_link_disables_only_that_link() {
+        let labels = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(labels, &[(0, 1, 0.01, 10f64), (1, 2, 1f64, 100f64)]);
+        let parallel = graph.add_edge(0, 1, 0.01, 20f64);
+        let parallel_link = graph.get_link_id(&graph.find_edge_by_id(parallel).unwrap()).unwrap().to_string();
+        let commodities = vec![Demand::new("d".to_string(), "a".to_string(), "c".to_string(), 8f64)];
+        let settings = ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent);
+
+        // без отказов поток делится между параллельными линками, а не сливается на одну дугу
+        let intact = solve_projection(&mut graph.clone(), &commodities, &settings);
+        let flow_on = |id: i32| intact.get_edge_flows().iter().find(|(e, _)| e.get_id() == id).map_or(0f64, |(_, f)| *f);
+        assert!(flow_on(0) > 0f64 && flow_on(parallel) > 0f64);
+        assert!((flow_on(0) + flow_on(parallel) - 8f64).abs() < 1e-9);
+
+        let analysis = analyze_failures(&graph, &commodities, &settings, false);
+        let scenario = |link: &str| analysis.scenarios.iter()
+            .find(|s| s.element == FailedElement::Link(link.to_string()))
+            .unwrap();
+        // после отказа линка вся нагрузка идет по второму из параллельных линков
+        assert!((scenario(&parallel_link).max_utilization - 0.8).abs() < 1e-9);
+        assert!((scenario("a_b").max_utilization - 0.4).abs() < 1e-9);
+        assert!(analysis.scenarios.iter().filter(|s| s.element != FailedElement::Link("b_c".to_string()))
+            .all(|s| s.disconnected.is_empty()));
+    }
+}
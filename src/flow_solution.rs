@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::structure_xml::Demand;
+
+/// Поток на одном пути, путь записан исходными id вершин и линков SNDlib
+#[derive(Debug, Clone)]
+pub struct PathFlow {
+    nodes: Vec<String>,
+    links: Vec<String>,
+    flow: f64
+}
+
+impl PathFlow {
+
+    pub fn new(nodes: Vec<String>, links: Vec<String>, flow: f64) -> PathFlow {
+        PathFlow {
+            nodes,
+            links,
+            flow
+        }
+    }
+
+    pub fn get_flow(&self) -> f64 {
+        self.flow
+    }
+
+    /// путь в дуги графа (в обратном порядке, как в DijkstraSP::path_to) по id вершин и линков;
+    /// None - вершины или линка пути нет в графе или они отключены
+    pub fn to_edges(&self, graph: &EdgeWeightedDigraph) -> Option<Vec<DirectedEdge>> {
+        if self.nodes.len() != self.links.len() + 1 {
+            return None;
+        }
+        let mut path = vec![];
+        for (k, link) in self.links.iter().enumerate() {
+            let v = graph.get_node_index(&self.nodes[k])?;
+            let w = graph.get_node_index(&self.nodes[k + 1])?;
+            if !graph.is_node_enabled(v) {
+                return None;
+            }
+            let e = graph.edge_list(v as usize).find(|e| e.to() == w && &graph.get_edge_label(e) == link)?;
+            path.push(e);
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Распределение потока одного commodity по путям
+#[derive(Debug, Clone)]
+pub struct CommodityFlow {
+    source: String,
+    target: String,
+    demand: f64,
+    paths: Vec<PathFlow>
+}
+
+impl CommodityFlow {
+
+    pub fn new(source: String, target: String, demand: f64, paths: Vec<PathFlow>) -> CommodityFlow {
+        CommodityFlow {
+            source,
+            target,
+            demand,
+            paths
+        }
+    }
+
+    pub fn get_source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn get_target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn get_paths(&self) -> &[PathFlow] {
+        &self.paths
+    }
+}
+
+/// Решение задачи распределения потоков в терминах исходной сети SNDlib
+#[derive(Debug, Clone, Default)]
+pub struct FlowSolution {
+    commodities: Vec<CommodityFlow>
+}
+
+impl FlowSolution {
+
+    pub fn new(commodities: Vec<CommodityFlow>) -> FlowSolution {
+        FlowSolution {
+            commodities
+        }
+    }
+
+    /// собирает решение из результата метода проекции:
+    /// result_x - индекс commodity -> ключ пути -> поток, active_paths - индекс commodity -> ключ пути -> ребра пути
+    pub fn from_paths(
+        graph: &EdgeWeightedDigraph,
+        commodities: &[Demand],
+        result_x: &BTreeMap<i32, BTreeMap<String, f64>>,
+        active_paths: &HashMap<i32, BTreeMap<String, Vec<DirectedEdge>>>
+    ) -> FlowSolution {
+        let mut solution = FlowSolution::default();
+        for (index, commodity) in commodities.iter().enumerate() {
+            let r_index = index as i32;
+            let mut paths = vec![];
+            if let (Some(flows), Some(edges)) = (result_x.get(&r_index), active_paths.get(&r_index)) {
+                for (key, flow) in flows {
+                    if *flow <= 0f64 {
+                        continue;
+                    }
+                    if let Some(path) = edges.get(key) {
+                        paths.push(Self::path_flow(graph, path, *flow));
+                    }
+                }
+            }
+            solution.commodities.push(CommodityFlow::new(
+                commodity.get_source(),
+                commodity.get_target(),
+                commodity.get_demand_vale(),
+                paths
+            ));
+        }
+        solution
+    }
+
+    /// путь из ребер в обратном порядке (как в DijkstraSP::path_to) в PathFlow
+    pub fn path_flow(graph: &EdgeWeightedDigraph, path: &[DirectedEdge], flow: f64) -> PathFlow {
+        PathFlow::new(
+            graph.path_to_labels(path),
+            path.iter().rev().map(|e| graph.get_edge_label(e)).collect(),
+            flow
+        )
+    }
+
+    pub fn get_commodities(&self) -> &[CommodityFlow] {
+        &self.commodities
+    }
+
+    /// Запись решения в текстовый файл: строка "commodity <source> <target> <demand>" и за ней строки
+    /// "path <поток> <вершина> <линк> <вершина> ... <вершина>" его путей; строки с # - комментарии
+    pub fn write(&self, path: &str) {
+        let mut data = String::from("# commodity <source> <target> <demand>\n# path <flow> <node> <link> <node> ... <node>\n");
+        for commodity in &self.commodities {
+            data.push_str(&format!("commodity {} {} {}\n", commodity.source, commodity.target, commodity.demand));
+            for path_flow in &commodity.paths {
+                data.push_str(&format!("path {} {}", path_flow.flow, path_flow.nodes[0]));
+                for (link, node) in path_flow.links.iter().zip(&path_flow.nodes[1..]) {
+                    data.push_str(&format!(" {} {}", link, node));
+                }
+                data.push('\n');
+            }
+        }
+        fs::write(path, data).expect("Не удалось записать файл решения");
+    }
+
+    /// чтение решения в формате write
+    pub fn read(path: &str) -> FlowSolution {
+        let data = fs::read_to_string(path).expect("Не удалось прочитать файл решения");
+        let mut solution = FlowSolution::default();
+        for line in data.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[0] {
+                "commodity" if fields.len() == 4 => solution.commodities.push(CommodityFlow::new(
+                    fields[1].to_string(),
+                    fields[2].to_string(),
+                    fields[3].parse::<f64>().expect("Неверный demand в файле решения"),
+                    vec![]
+                )),
+                "path" if fields.len() >= 3 && fields.len() % 2 == 1 => {
+                    let flow = fields[1].parse::<f64>().expect("Неверный поток в файле решения");
+                    let nodes = fields[2..].iter().step_by(2).map(|n| n.to_string()).collect();
+                    let links = fields[3..].iter().step_by(2).map(|l| l.to_string()).collect();
+                    solution.commodities.last_mut()
+                        .expect("Путь в файле решения до первого commodity")
+                        .paths.push(PathFlow::new(nodes, links, flow));
+                }
+                _ => panic!("Неверная строка в файле решения: {}", line)
+            }
+        }
+        solution
+    }
+}
+
+impl fmt::Display for FlowSolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for commodity in &self.commodities {
+            write!(f, "source - {}, target - {} -> paths: ", commodity.source, commodity.target)?;
+            for path in &commodity.paths {
+                write!(f, "{} - flow: {} ", path.nodes.join("-"), path.flow)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_read_round_trip() {
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(vec!["a".to_string(), "b".to_string(), "c".to_string()],
+                              &[(0, 1, 1f64, 10f64), (0, 2, 1f64, 10f64), (2, 1, 1f64, 10f64)]);
+        let via_c: Vec<DirectedEdge> = [2, 1].iter().map(|&id| graph.find_edge_by_id(id).unwrap()).collect();
+        let direct = vec![graph.find_edge_by_id(0).unwrap()];
+        let solution = FlowSolution::new(vec![
+            CommodityFlow::new("a".to_string(), "b".to_string(), 4.25, vec![
+                FlowSolution::path_flow(&graph, &direct, 1f64 / 3f64),
+                FlowSolution::path_flow(&graph, &via_c, 4.25 - 1f64 / 3f64)
+            ]),
+            CommodityFlow::new("c".to_string(), "b".to_string(), 1f64, vec![])
+        ]);
+        assert_eq!(solution.get_commodities()[0].get_paths()[1].nodes, ["a", "c", "b"]);
+        assert_eq!(solution.get_commodities()[0].get_paths()[1].links, ["a_c", "c_b"]);
+
+        let path = std::env::temp_dir().join(format!("flow_solution_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        solution.write(path);
+        let read = FlowSolution::read(path);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(read.to_string(), solution.to_string());
+        for (a, b) in read.get_commodities().iter().zip(solution.get_commodities()) {
+            assert_eq!((a.get_source(), a.get_target()), (b.get_source(), b.get_target()));
+            assert_eq!(a.demand.to_bits(), b.demand.to_bits());
+            assert_eq!(a.get_paths().len(), b.get_paths().len());
+            for (p, q) in a.get_paths().iter().zip(b.get_paths()) {
+                assert_eq!(p.get_flow().to_bits(), q.get_flow().to_bits());
+                assert_eq!(p.links, q.links);
+                assert_eq!(p.to_edges(&graph), q.to_edges(&graph));
+            }
+        }
+        assert_eq!(read.get_commodities()[0].get_paths()[1].to_edges(&graph), Some(via_c));
+    }
+}
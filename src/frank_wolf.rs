@@ -1,91 +1,940 @@
-use ndarray::{arr1, Array, array, Array1, Array2, Axis};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-/// рачет функции скалярной |Ax - y|^2
-/// a - матрица
-/// x - вектор, стобец с координатам
-/// y - вектор
-pub fn count_norma(a: &Array2<f64>, x: &Vec<f64>, y: &Vec<f64>) -> f64 {
-    let x_vec = arr1(x);
-    let y_vec = arr1(y);
-    let binding = (a.dot(&x_vec) - y_vec);
-    let b = binding.view();
-    b.dot(&b).powi(2)
+use ndarray::Array1;
+
+use crate::delay_func_count::CostModel;
+use crate::dijkstra_find_path::DijkstraSP;
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::structure_xml::Demand;
+
+/// число делений отрезка пополам при точном выборе шага
+const LINE_SEARCH_ITERATIONS: i32 = 60;
+
+/// ограничение веса предыдущей сопряженной точки 1 - delta, чтобы вершина оракула всегда входила в направление
+const CONJUGATE_DELTA: f64 = 0.01;
+
+/// Гладкая выпуклая функция, которую минимизирует метод Франка-Вульфа
+pub trait Objective {
+    fn value(&self, x: &Array1<f64>) -> f64;
+    fn gradient(&self, x: &Array1<f64>) -> Array1<f64>;
+
+    /// произведение гессиана в x на вектор v, по умолчанию - разностью градиентов
+    fn hessian_product(&self, x: &Array1<f64>, v: &Array1<f64>) -> Array1<f64> {
+        let norm = v.dot(v).sqrt();
+        if norm == 0f64 {
+            return Array1::zeros(v.len());
+        }
+        let h = f64::EPSILON.sqrt() * f64::max(1f64, x.dot(x).sqrt()) / norm;
+        (self.gradient(&(x + &(v * h))) - self.gradient(x)) / h
+    }
 }
 
-/// расчет градиента по формуле 2 * (A^T(Ax - y))
-/// a - матрица
-/// x - вектор, стобец с координатам
-/// y - вектор
-/// функция возвращат вектор
-pub fn count_gradient(a: &Array2<f64>, x: &Vec<f64>, y: &Vec<f64>) -> Vec<f64> {
-    let x_vec = arr1(x);
-    let y_vec = arr1(y);
-    let binding = &(a.dot(&x_vec) - y_vec);
-    let mut a_m = a.view();
-    let a_transport = a_m.reversed_axes();
-    let b = a_transport.dot(binding) * 2.0;
-    b.to_vec()
+/// Оракул линейной минимизации: вершина допустимого множества, на которой <gradient, s> минимально
+pub trait LinearMinimizationOracle {
+    fn minimize(&mut self, gradient: &Array1<f64>) -> Array1<f64>;
 }
 
-/// реализация оракула для реализаци алгоритма
-/// a - матрица
-/// x - вектор, стобец с координатам
-/// y - вектор
-/// функция возвращат вектор
-pub fn create_oracle(size: f64, req_param: f64, a: &Array2<f64>, x: &Vec<f64>, y: &Vec<f64>) -> Vec<f64> {
+/// Многогранник потоков на дугах, при которых все demand доставлены; x[id дуги] - поток на дуге.
+/// Вершина - распределение "все или ничего": каждый demand целиком идет по кратчайшему пути при стоимостях дуг,
+/// равных градиенту
+#[derive(Debug, Clone)]
+pub struct FlowPolytope {
+    graph: EdgeWeightedDigraph,
+    demand_from: BTreeMap<i32, Vec<(i32, f64)>>, // source -> target и demand
+    unrouted: Vec<i32>,
+    size: usize
+}
 
-    let mut s = vec![0.0, size];
-    let grad = count_gradient(a, x, y);
-    if grad.iter().all(|&x| x == 0.0) {
-        return s;
+impl FlowPolytope {
+
+    pub fn new(graph_adj: &EdgeWeightedDigraph, commodities: &[Demand]) -> FlowPolytope {
+        let mut graph = graph_adj.clone();
+        let mut demand_from: BTreeMap<i32, Vec<(i32, f64)>> = BTreeMap::new();
+        let mut unrouted = vec![];
+        for (index, commodity) in commodities.iter().enumerate() {
+            let source = graph_adj.get_node_index(&commodity.get_source());
+            let target = graph_adj.get_node_index(&commodity.get_target());
+            match (source, target) {
+                (Some(s), Some(t)) if s != t && DijkstraSP::dijkstra(&mut graph, s).has_path_to(t as usize) => {
+                    demand_from.entry(s).or_default().push((t, commodity.get_demand_vale()));
+                }
+                _ => unrouted.push(index as i32)
+            }
+        }
+        FlowPolytope {
+            graph,
+            demand_from,
+            unrouted,
+            size: edge_vector_len(graph_adj)
+        }
+    }
+
+    /// индексы commodity, для которых в графе нет пути от источника к стоку
+    pub fn get_unrouted(&self) -> &[i32] {
+        &self.unrouted
     }
-    let i = grad.iter().map(|x| x.abs()).reduce(f64::max).unwrap() as usize;
-    s[i] = - grad[i].signum() * req_param;
-    return s
 }
 
-///реалзация алгоритма Франк-Вульф
-pub fn frank_wolfe(a: &Array2<f64>, y: &Vec<f64>, k: i32) -> Vec<f64> {
+impl LinearMinimizationOracle for FlowPolytope {
+    fn minimize(&mut self, gradient: &Array1<f64>) -> Array1<f64> {
+        for e in self.graph.edges() {
+            self.graph.update_edge_by_id(e.get_id(), gradient[e.get_id() as usize]);
+        }
+        let mut s = Array1::zeros(self.size);
+        for (&source, targets) in &self.demand_from {
+            let sp = DijkstraSP::dijkstra(&mut self.graph, source);
+            for &(t, demand) in targets {
+                for e in sp.path_to(t as usize).unwrap_or_default() {
+                    s[e.get_id() as usize] += demand;
+                }
+            }
+        }
+        s
+    }
+}
 
-    /// количество итераций
-    const COUNT_ITERATION: usize = 2000;
+/// Сумма функций затрат по линкам; x[id дуги] - поток на дуге, у линков UNDIRECTED обе дуги делят емкость
+#[derive(Debug, Clone)]
+pub struct LinkCost {
+    links: Vec<(Vec<usize>, f64)>, // дуги линка и его емкость
+    link_of: Vec<Option<usize>>,   // id дуги -> номер линка
+    cost_model: CostModel
+}
 
-    /// параметр регуляции
-    const req_param: f64 = 164f64;
+impl LinkCost {
 
-    /// допустимая точность
-    const epsilon: f64 = 0.001;
+    pub fn new(graph_adj: &EdgeWeightedDigraph, cost_model: CostModel) -> LinkCost {
+        let mut links: Vec<(Vec<usize>, f64)> = vec![];
+        let mut seen = vec![false; edge_vector_len(graph_adj)];
+        for e in graph_adj.edges() {
+            if !graph_adj.is_edge_enabled(&e) || seen[e.get_id() as usize] {
+                continue;
+            }
+            let mut arcs = vec![e.get_id() as usize];
+            if let Some(twin) = graph_adj.get_shared_capacity_twin(&e) {
+                arcs.push(twin.get_id() as usize);
+            }
+            for &a in &arcs {
+                seen[a] = true;
+            }
+            links.push((arcs, e.get_capacity()));
+        }
+        let mut link_of = vec![None; seen.len()];
+        for (l, (arcs, _)) in links.iter().enumerate() {
+            for &a in arcs {
+                link_of[a] = Some(l);
+            }
+        }
+        LinkCost {
+            links,
+            link_of,
+            cost_model
+        }
+    }
 
+    fn load(x: &Array1<f64>, arcs: &[usize]) -> f64 {
+        arcs.iter().fold(0f64, |sum, &a| sum + x[a])
+    }
 
-    let size = a.len_of(Axis(0));
+    fn arc_load(&self, x: &[f64], arc: usize) -> Option<(f64, f64)> {
+        let (arcs, c) = &self.links[self.link_of[arc]?];
+        Some((arcs.iter().fold(0f64, |sum, &a| sum + x[a]), *c))
+    }
 
-    let mut x = Array2::<f64>::zeros((size, COUNT_ITERATION));
-    let mut s = Array2::<f64>::zeros((size, COUNT_ITERATION));
+    /// предельная стоимость дуги - производная функции затрат ее линка, x[id дуги] - поток на дуге
+    pub fn arc_cost(&self, x: &[f64], arc: usize) -> f64 {
+        self.arc_load(x, arc).map_or(f64::INFINITY, |(load, c)| self.cost_model.first_derivative(load, c))
+    }
+
+    /// вторая производная функции затрат линка дуги
+    pub fn arc_derivative(&self, x: &[f64], arc: usize) -> f64 {
+        self.arc_load(x, arc).map_or(0f64, |(load, c)| self.cost_model.second_derivative(load, c))
+    }
+}
 
-    /// определение шага спуска
-    let mut arr_step = [f64::NAN; COUNT_ITERATION];
+impl Objective for LinkCost {
+    fn value(&self, x: &Array1<f64>) -> f64 {
+        self.links.iter().fold(0f64, |sum, (arcs, c)| sum + self.cost_model.value(Self::load(x, arcs), *c))
+    }
 
-    /// массив записи результата
-    let mut result = [f64::NAN; COUNT_ITERATION];
+    fn gradient(&self, x: &Array1<f64>) -> Array1<f64> {
+        let mut g = Array1::zeros(x.len());
+        for (arcs, c) in &self.links {
+            let derivative = self.cost_model.first_derivative(Self::load(x, arcs), *c);
+            for &a in arcs {
+                g[a] = derivative;
+            }
+        }
+        g
+    }
+
+    /// функция сепарабельна по линкам, гессиан блочно-диагональный: у дуг одного линка общая вторая производная
+    fn hessian_product(&self, x: &Array1<f64>, v: &Array1<f64>) -> Array1<f64> {
+        let mut hv = Array1::zeros(x.len());
+        for (arcs, c) in &self.links {
+            let product = self.cost_model.second_derivative(Self::load(x, arcs), *c) * Self::load(v, arcs);
+            for &a in arcs {
+                hv[a] = product;
+            }
+        }
+        hv
+    }
+}
+
+/// Правило выбора шага метода Франка-Вульфа
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepRule {
+    /// 2 / (k + 2), не требует вычислений функции
+    Standard,
+    /// точная минимизация по отрезку делением пополам по производной
+    LineSearch,
+    /// min(gap / (L |d|^2), 1), L - константа Липшица градиента
+    ShortStep { lipschitz: f64 },
+}
 
-    /// массив разности f(x(n)) - f(x(n-1))
-    let mut arr_difference = [f64::NAN; COUNT_ITERATION];
+impl FromStr for StepRule {
+    type Err = String;
 
-    for i in 1..COUNT_ITERATION {
-        arr_step[i] = f64::from(2) / (f64::from(2) + f64::from(k));
-        let g = count_gradient(a, &x.row(i - 1).to_vec(), y);
-        s.push_row(Array1::from(create_oracle(size as f64, req_param, a, &g, y)).view()).expect("TODO: panic message");
-        let x1 = Array::from(x.row(i - 1).to_vec()) * (f64::from(1) - arr_difference[i]);
-        let x2 = Array::from(s.row(i).to_vec()) * arr_step[i];
-        x.push_row((x1 + x2).view()).expect("TODO: panic message");
-        result[i] = count_norma(a, &x.row(i - 1).to_vec(), y);
-        if i > 1 {
-            arr_difference[i - 1] = result[i] - result[i - 1];
-            if epsilon >= arr_difference[i - 1].abs() {
-                break
+    /// standard, line-search или short:<L>
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "standard" => Ok(StepRule::Standard),
+            "line-search" => Ok(StepRule::LineSearch),
+            other => match other.strip_prefix("short:").map(|l| l.parse::<f64>()) {
+                Some(Ok(lipschitz)) if lipschitz > 0f64 => Ok(StepRule::ShortStep { lipschitz }),
+                _ => Err(format!("Неизвестное правило шага: {}", other))
             }
         }
     }
-    result.to_vec()
 }
 
+/// Вариант метода Франка-Вульфа
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrankWolfeVariant {
+    /// только шаги к вершине оракула
+    Classic,
+    /// шаг к вершине оракула или от худшей вершины активного множества, если так убывание быстрее
+    AwayStep,
+    /// перенос веса с худшей вершины активного множества на вершину оракула
+    Pairwise,
+    /// conjugate FW (Mitradjieva-Lindberg): направление сопряжено с предыдущим относительно гессиана
+    Conjugate,
+    /// bi-conjugate FW: направление сопряжено с двумя предыдущими
+    BiConjugate,
+}
+
+impl FromStr for FrankWolfeVariant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "classic" => Ok(FrankWolfeVariant::Classic),
+            "away-step" => Ok(FrankWolfeVariant::AwayStep),
+            "pairwise" => Ok(FrankWolfeVariant::Pairwise),
+            "conjugate" => Ok(FrankWolfeVariant::Conjugate),
+            "bi-conjugate" => Ok(FrankWolfeVariant::BiConjugate),
+            other => Err(format!("Неизвестный вариант метода Франка-Вульфа: {}", other))
+        }
+    }
+}
+
+/// Параметры метода Франка-Вульфа
+#[derive(Debug, Clone, Copy)]
+pub struct FrankWolfeSettings {
+    step: StepRule,
+    variant: FrankWolfeVariant,
+    max_iterations: i32,
+    tolerance: f64 // относительный зазор двойственности, при котором расчет останавливается
+}
+
+impl FrankWolfeSettings {
+
+    pub fn new(step: StepRule) -> FrankWolfeSettings {
+        FrankWolfeSettings {
+            step,
+            variant: FrankWolfeVariant::Classic,
+            max_iterations: 1000,
+            tolerance: 1e-4
+        }
+    }
+
+    pub fn with_variant(mut self, variant: FrankWolfeVariant) -> FrankWolfeSettings {
+        self.variant = variant;
+        self
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: i32) -> FrankWolfeSettings {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f64) -> FrankWolfeSettings {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+/// Состояние на одной итерации: значение функции, зазор двойственности <grad f(x), x - s> и выбранный шаг
+#[derive(Debug, Clone, Copy)]
+pub struct FrankWolfeIteration {
+    iteration: i32,
+    value: f64,
+    gap: f64,
+    relative_gap: f64,
+    step: f64,
+    elapsed: Duration
+}
+
+/// Результат метода Франка-Вульфа. Зазор двойственности - сертификат точности: f(x) - f* <= gap,
+/// поэтому f(x) - gap - нижняя оценка оптимума
+#[derive(Debug, Clone)]
+pub struct FrankWolfeResult {
+    x: Array1<f64>,
+    value: f64,
+    gap: f64,
+    lower_bound: f64,     // лучшая нижняя оценка оптимума за все итерации
+    iterations: i32,
+    variant: FrankWolfeVariant,
+    away_steps: i32,      // шаги от away-вершины, бывают только в варианте away-step
+    pairwise_steps: i32,  // шаги переноса веса, бывают только в варианте pairwise
+    drop_steps: i32,      // шаги, после которых вершина ушла из активного множества
+    active_vertices: usize, // размер активного множества в итоговой точке, для вариантов без активного множества 0
+    history: Vec<FrankWolfeIteration>
+}
+
+impl FrankWolfeResult {
+
+    pub fn get_x(&self) -> &Array1<f64> {
+        &self.x
+    }
+
+    /// запись истории сходимости: строка "iteration value gap relative_gap step seconds" на итерацию
+    pub fn write_history(&self, path: &str) {
+        let mut data = String::from("# iteration value gap relative_gap step seconds\n");
+        for h in &self.history {
+            data.push_str(&format!("{} {} {} {} {} {}\n", h.iteration, h.value, h.gap, h.relative_gap, h.step, h.elapsed.as_secs_f64()));
+        }
+        fs::write(path, data).expect("Не удалось записать историю сходимости");
+    }
+}
+
+impl fmt::Display for FrankWolfeResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relative_gap = self.history.last().map_or(f64::NAN, |h| h.relative_gap);
+        writeln!(f, "value: {}, gap: {:e}, relative gap: {:e}, lower bound: {}, iterations: {}",
+                 self.value, self.gap, relative_gap, self.lower_bound, self.iterations)?;
+        match self.variant {
+            FrankWolfeVariant::AwayStep => writeln!(f, "away steps: {}, drop steps: {}, active vertices: {}",
+                                                    self.away_steps, self.drop_steps, self.active_vertices),
+            FrankWolfeVariant::Pairwise => writeln!(f, "pairwise steps: {}, drop steps: {}, active vertices: {}",
+                                                    self.pairwise_steps, self.drop_steps, self.active_vertices),
+            _ => Ok(())
+        }
+    }
+}
+
+/// Метод Франка-Вульфа (условного градиента): x_{k+1} = x_k + step * d_k, направление d_k строится по вершине s_k,
+/// которую оракул дает при градиенте в x_k. Для away-step и pairwise x0 должна быть вершиной допустимого множества
+/// (например, решением оракула), для классического варианта - любой допустимой точкой.
+/// Расчет останавливается, когда относительный зазор gap / |f(x)| не больше tolerance, или после max_iterations итераций
+pub fn frank_wolfe<F: Objective, O: LinearMinimizationOracle>(
+    objective: &F,
+    oracle: &mut O,
+    x0: Array1<f64>,
+    settings: &FrankWolfeSettings
+) -> FrankWolfeResult {
+    let start = Instant::now();
+    let mut active = ActiveSet::new(x0.clone());
+    let mut x = x0;
+    let mut lower_bound = f64::NEG_INFINITY;
+    let mut history = vec![];
+    let mut iterations = 0;
+    let mut away_steps = 0;
+    let mut pairwise_steps = 0;
+    let mut drop_steps = 0;
+    let mut conjugate = ConjugateState::default();
+    loop {
+        let value = objective.value(&x);
+        let g = objective.gradient(&x);
+        let s = oracle.minimize(&g);
+        let gap = g.dot(&(&x - &s));
+        lower_bound = f64::max(lower_bound, value - gap);
+        let relative_gap = gap / f64::max(value.abs(), f64::MIN_POSITIVE);
+        let done = relative_gap <= settings.tolerance || iterations >= settings.max_iterations;
+        let mut step = 0f64;
+        if !done {
+            // направление, наибольший допустимый шаг по нему и изменение активного множества
+            let (d, max_step, kind) = match settings.variant {
+                FrankWolfeVariant::Classic => (&s - &x, 1f64, None),
+                FrankWolfeVariant::Conjugate | FrankWolfeVariant::BiConjugate => {
+                    let point = conjugate.point(objective, &x, &s, &g, settings.variant == FrankWolfeVariant::BiConjugate);
+                    (point - &x, 1f64, None)
+                }
+                FrankWolfeVariant::AwayStep => {
+                    let (a, weight) = active.away_vertex(&g);
+                    let away_gap = g.dot(&(active.vertex(a) - &x));
+                    if gap >= away_gap || weight >= 1f64 {
+                        (&s - &x, 1f64, Some(StepKind::Toward))
+                    } else {
+                        (&x - active.vertex(a), weight / (1f64 - weight), Some(StepKind::Away(a)))
+                    }
+                }
+                FrankWolfeVariant::Pairwise => {
+                    let (a, weight) = active.away_vertex(&g);
+                    (&s - active.vertex(a), weight, Some(StepKind::Pairwise(a)))
+                }
+            };
+            step = match settings.step {
+                StepRule::Standard => f64::min(2f64 / (iterations as f64 + 2f64), max_step),
+                StepRule::LineSearch => line_search(objective, &x, &d, max_step),
+                StepRule::ShortStep { lipschitz } => short_step(-g.dot(&d), &d, lipschitz, max_step)
+            };
+            match kind {
+                None => {}
+                Some(StepKind::Toward) => active.toward(s, step),
+                Some(StepKind::Away(a)) => {
+                    away_steps += 1;
+                    if step >= max_step {
+                        drop_steps += 1;
+                    }
+                    active.away(a, step, max_step);
+                }
+                Some(StepKind::Pairwise(a)) => {
+                    pairwise_steps += 1;
+                    if step >= max_step {
+                        drop_steps += 1;
+                    }
+                    active.pairwise(a, s, step, max_step);
+                }
+            }
+            if matches!(settings.variant, FrankWolfeVariant::Conjugate | FrankWolfeVariant::BiConjugate) {
+                conjugate.push(&x + &d, step);
+            }
+            x = x + d * step;
+        }
+        history.push(FrankWolfeIteration {
+            iteration: iterations,
+            value,
+            gap,
+            relative_gap,
+            step,
+            elapsed: start.elapsed()
+        });
+        if done {
+            return FrankWolfeResult {
+                x,
+                value,
+                gap,
+                lower_bound,
+                iterations,
+                variant: settings.variant,
+                away_steps,
+                pairwise_steps,
+                drop_steps,
+                active_vertices: if matches!(settings.variant, FrankWolfeVariant::AwayStep | FrankWolfeVariant::Pairwise) { active.len() } else { 0 },
+                history
+            };
+        }
+        iterations += 1;
+    }
+}
+
+/// Сопряженные точки двух предыдущих итераций: направление итерации - от x к сопряженной точке,
+/// выпуклой комбинации вершины оракула и предыдущих сопряженных точек, поэтому точка допустима
+#[derive(Debug, Clone, Default)]
+struct ConjugateState {
+    previous: Option<Array1<f64>>,
+    before_previous: Option<Array1<f64>>,
+    previous_step: f64
+}
+
+impl ConjugateState {
+
+    /// Сопряженная точка по формулам Mitradjieva-Lindberg. Если коэффициенты не определены или направление
+    /// не является направлением спуска, берется вершина оракула s, то есть обычный шаг Франка-Вульфа
+    fn point<F: Objective>(&self, objective: &F, x: &Array1<f64>, s: &Array1<f64>, g: &Array1<f64>, bi: bool) -> Array1<f64> {
+        let ratio = |numerator: f64, denominator: f64| if denominator.abs() > f64::MIN_POSITIVE { numerator / denominator } else { 0f64 };
+        let d_fw = s - x;
+        let h_fw = objective.hessian_product(x, &d_fw);
+        let tau = self.previous_step;
+        let point = match (&self.previous, &self.before_previous) {
+            (Some(p1), Some(p2)) if bi && tau < 1f64 - CONJUGATE_DELTA => {
+                let d_bar = p1 - x;
+                let d_bi = p1 * tau - x + p2 * (1f64 - tau);
+                let mu = f64::max(ratio(-d_bi.dot(&h_fw), d_bi.dot(&objective.hessian_product(x, &(p2 - p1)))), 0f64);
+                let nu = f64::max(ratio(-d_bar.dot(&h_fw), d_bar.dot(&objective.hessian_product(x, &d_bar))) + mu * tau / (1f64 - tau), 0f64);
+                let beta = 1f64 / (1f64 + mu + nu);
+                s * beta + p1 * (nu * beta) + p2 * (mu * beta)
+            }
+            (Some(p1), _) => {
+                let d_bar = p1 - x;
+                let alpha = ratio(d_bar.dot(&h_fw), d_bar.dot(&objective.hessian_product(x, &(s - p1))));
+                let alpha = if alpha > 0f64 { f64::min(alpha, 1f64 - CONJUGATE_DELTA) } else { 0f64 };
+                p1 * alpha + s * (1f64 - alpha)
+            }
+            _ => s.clone()
+        };
+        if g.dot(&(&point - x)) < 0f64 { point } else { s.clone() }
+    }
+
+    fn push(&mut self, point: Array1<f64>, step: f64) {
+        self.before_previous = self.previous.replace(point);
+        self.previous_step = step;
+    }
+}
+
+/// шаг к вершине оракула, шаг от away-вершины или перенос веса с away-вершины на вершину оракула
+#[derive(Debug, Clone, Copy)]
+enum StepKind {
+    Toward,
+    Away(usize),
+    Pairwise(usize),
+}
+
+/// Активное множество: x = sum weights[i] * vertices[i], веса положительны и в сумме дают 1.
+/// Для многогранника потоков вершины - распределения "все или ничего"
+#[derive(Debug, Clone)]
+struct ActiveSet {
+    vertices: Vec<Array1<f64>>,
+    weights: Vec<f64>,
+    by_support: HashMap<u64, Vec<usize>> // хеш ненулевых координат вершины -> номера вершин с таким хешем
+}
+
+impl ActiveSet {
+
+    fn new(x0: Array1<f64>) -> ActiveSet {
+        let mut active = ActiveSet {
+            vertices: vec![],
+            weights: vec![],
+            by_support: HashMap::new()
+        };
+        active.push(x0, 1f64);
+        active
+    }
+
+    fn len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    fn vertex(&self, i: usize) -> &Array1<f64> {
+        &self.vertices[i]
+    }
+
+    /// хеш пар (координата, биты значения) по ненулевым координатам; у вершин многогранника потоков их немного
+    fn support_hash(v: &Array1<f64>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for (i, x) in v.iter().enumerate().filter(|(_, x)| **x != 0f64) {
+            (i, x.to_bits()).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// away-вершина - вершина активного множества с наибольшим <g, v>, и ее вес
+    fn away_vertex(&self, g: &Array1<f64>) -> (usize, f64) {
+        let a = argmin(self.vertices.iter().map(|v| -g.dot(v))).unwrap();
+        (a, self.weights[a])
+    }
+
+    fn push(&mut self, s: Array1<f64>, weight: f64) {
+        self.by_support.entry(Self::support_hash(&s)).or_default().push(self.vertices.len());
+        self.vertices.push(s);
+        self.weights.push(weight);
+    }
+
+    /// вес добавляется существующей такой же вершине или вершина добавляется в множество
+    fn add(&mut self, s: Array1<f64>, weight: f64) {
+        let same = self.by_support.get(&Self::support_hash(&s))
+            .and_then(|bucket| bucket.iter().copied().find(|&i| self.vertices[i] == s));
+        match same {
+            Some(i) => self.weights[i] += weight,
+            None => self.push(s, weight)
+        }
+    }
+
+    /// на место удаленной вершины переносится последняя, ее номер в by_support обновляется
+    fn remove(&mut self, i: usize) {
+        let last = self.vertices.len() - 1;
+        let key = Self::support_hash(&self.vertices[i]);
+        let bucket = self.by_support.get_mut(&key).unwrap();
+        bucket.retain(|&j| j != i);
+        if bucket.is_empty() {
+            self.by_support.remove(&key);
+        }
+        if i != last {
+            let moved = self.by_support.get_mut(&Self::support_hash(&self.vertices[last])).unwrap();
+            for j in moved.iter_mut().filter(|j| **j == last) {
+                *j = i;
+            }
+        }
+        self.vertices.swap_remove(i);
+        self.weights.swap_remove(i);
+    }
+
+    fn toward(&mut self, s: Array1<f64>, step: f64) {
+        if step >= 1f64 {
+            *self = ActiveSet::new(s);
+            return;
+        }
+        for w in self.weights.iter_mut() {
+            *w *= 1f64 - step;
+        }
+        self.add(s, step);
+    }
+
+    /// при наибольшем шаге вес away-вершины становится нулевым и она удаляется (drop step)
+    fn away(&mut self, a: usize, step: f64, max_step: f64) {
+        if step >= max_step {
+            self.remove(a);
+        }
+        for w in self.weights.iter_mut() {
+            *w *= 1f64 + step;
+        }
+        if step < max_step {
+            self.weights[a] -= step;
+        }
+    }
+
+    fn pairwise(&mut self, a: usize, s: Array1<f64>, step: f64, max_step: f64) {
+        self.weights[a] -= step;
+        self.add(s, step);
+        if step >= max_step {
+            self.remove(a);
+        }
+    }
+}
+
+/// шаг из [0, max_step], минимизирующий f(x + step * d); производная по шагу <grad f(x + step * d), d>
+/// не убывает, поэтому ее ноль ищется делением пополам
+pub fn line_search<F: Objective>(objective: &F, x: &Array1<f64>, d: &Array1<f64>, max_step: f64) -> f64 {
+    let derivative = |step: f64| objective.gradient(&(x + &(d * step))).dot(d);
+    if derivative(max_step) <= 0f64 {
+        return max_step;
+    }
+    if derivative(0f64) >= 0f64 {
+        return 0f64;
+    }
+    let (mut low, mut high) = (0f64, max_step);
+    for _ in 0..LINE_SEARCH_ITERATIONS {
+        let middle = (low + high) / 2f64;
+        if derivative(middle) < 0f64 {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    (low + high) / 2f64
+}
+
+/// шаг, минимизирующий квадратичную оценку сверху f(x) - step * gap + step^2 * L |d|^2 / 2
+pub fn short_step(gap: f64, d: &Array1<f64>, lipschitz: f64, max_step: f64) -> f64 {
+    let curvature = lipschitz * d.dot(d);
+    if curvature <= 0f64 {
+        return max_step;
+    }
+    f64::clamp(gap / curvature, 0f64, max_step)
+}
+
+/// Распределение потоков по дугам (link-based assignment)
+#[derive(Debug, Clone)]
+pub struct LinkAssignment {
+    edge_flows: Vec<(DirectedEdge, f64)>,
+    unrouted: Vec<i32>,
+    convergence: FrankWolfeResult
+}
+
+impl LinkAssignment {
+
+    /// поток на каждой работающей дуге графа
+    pub fn get_edge_flows(&self) -> &[(DirectedEdge, f64)] {
+        &self.edge_flows
+    }
+
+    pub fn get_unrouted(&self) -> &[i32] {
+        &self.unrouted
+    }
+
+    pub fn get_convergence(&self) -> &FrankWolfeResult {
+        &self.convergence
+    }
+}
+
+/// Распределение потоков методом Франка-Вульфа в пространстве потоков на дугах (Flow Deviation):
+/// минимизация суммы функций затрат линков по многограннику потоков, начальная точка - распределение
+/// "все или ничего" по предельным стоимостям при нулевой загрузке
+pub fn assign_link_based(
+    graph_adj: &EdgeWeightedDigraph,
+    commodities: &[Demand],
+    cost_model: CostModel,
+    settings: &FrankWolfeSettings
+) -> LinkAssignment {
+    assign_link_based_with(graph_adj, commodities, &LinkCost::new(graph_adj, cost_model), settings)
+}
+
+/// то же для произвольной функции потоков на дугах, например суммы интегралов BPR для равновесия пользователей
+pub fn assign_link_based_with<F: Objective>(
+    graph_adj: &EdgeWeightedDigraph,
+    commodities: &[Demand],
+    objective: &F,
+    settings: &FrankWolfeSettings
+) -> LinkAssignment {
+    let mut oracle = FlowPolytope::new(graph_adj, commodities);
+    let x0 = oracle.minimize(&objective.gradient(&Array1::zeros(edge_vector_len(graph_adj))));
+    let convergence = frank_wolfe(objective, &mut oracle, x0, settings);
+    LinkAssignment {
+        edge_flows: edge_flows(graph_adj, convergence.get_x()),
+        unrouted: oracle.get_unrouted().to_vec(),
+        convergence
+    }
+}
+
+/// длина вектора потоков, индексируемого id дуги
+pub fn edge_vector_len(graph_adj: &EdgeWeightedDigraph) -> usize {
+    graph_adj.edges().iter().map(|e| e.get_id() as usize + 1).max().unwrap_or(0)
+}
+
+/// потоки на работающих дугах по вектору, индексируемому id дуги
+pub fn edge_flows(graph_adj: &EdgeWeightedDigraph, x: &Array1<f64>) -> Vec<(DirectedEdge, f64)> {
+    graph_adj.edges().into_iter()
+        .filter(|e| graph_adj.is_edge_enabled(e))
+        .map(|e| {
+            let flow = x[e.get_id() as usize];
+            (e, flow)
+        })
+        .collect()
+}
+
+fn argmin<I: Iterator<Item = f64>>(values: I) -> Option<usize> {
+    values.enumerate().min_by(|(_, a), (_, b)| a.total_cmp(b)).map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{array, Array2};
+
+    // простые множества и функция для проверки движка без сети
+
+    /// |Ax - y|^2
+    #[derive(Debug, Clone)]
+    struct LeastSquares {
+        a: Array2<f64>,
+        y: Array1<f64>
+    }
+
+    impl LeastSquares {
+
+        fn new(a: Array2<f64>, y: Array1<f64>) -> LeastSquares {
+            LeastSquares {
+                a,
+                y
+            }
+        }
+    }
+
+    impl Objective for LeastSquares {
+        fn value(&self, x: &Array1<f64>) -> f64 {
+            let r = self.a.dot(x) - &self.y;
+            r.dot(&r)
+        }
+
+        /// 2 * A^T(Ax - y)
+        fn gradient(&self, x: &Array1<f64>) -> Array1<f64> {
+            self.a.t().dot(&(self.a.dot(x) - &self.y)) * 2f64
+        }
+
+        fn hessian_product(&self, _x: &Array1<f64>, v: &Array1<f64>) -> Array1<f64> {
+            self.a.t().dot(&self.a.dot(v)) * 2f64
+        }
+    }
+
+    /// шар |x|_1 <= radius, вершины - +-radius * e_i
+    #[derive(Debug, Clone, Copy)]
+    struct L1Ball {
+        radius: f64
+    }
+
+    impl L1Ball {
+
+        fn new(radius: f64) -> L1Ball {
+            L1Ball {
+                radius
+            }
+        }
+    }
+
+    impl LinearMinimizationOracle for L1Ball {
+        fn minimize(&mut self, gradient: &Array1<f64>) -> Array1<f64> {
+            let mut s = Array1::zeros(gradient.len());
+            let i = argmin(gradient.iter().map(|g| -g.abs()));
+            if let Some(i) = i.filter(|&i| gradient[i] != 0f64) {
+                s[i] = -gradient[i].signum() * self.radius;
+            }
+            s
+        }
+    }
+
+    /// симплекс x >= 0, sum x = radius, вершины - radius * e_i
+    #[derive(Debug, Clone, Copy)]
+    struct Simplex {
+        radius: f64
+    }
+
+    impl Simplex {
+
+        fn new(radius: f64) -> Simplex {
+            Simplex {
+                radius
+            }
+        }
+    }
+
+    impl LinearMinimizationOracle for Simplex {
+        fn minimize(&mut self, gradient: &Array1<f64>) -> Array1<f64> {
+            let mut s = Array1::zeros(gradient.len());
+            if let Some(i) = argmin(gradient.iter().copied()) {
+                s[i] = self.radius;
+            }
+            s
+        }
+    }
+
+    /// параллелепипед lower <= x <= upper
+    #[derive(Debug, Clone)]
+    struct BoxSet {
+        lower: Array1<f64>,
+        upper: Array1<f64>
+    }
+
+    impl BoxSet {
+
+        fn new(lower: Array1<f64>, upper: Array1<f64>) -> BoxSet {
+            BoxSet {
+                lower,
+                upper
+            }
+        }
+    }
+
+    impl LinearMinimizationOracle for BoxSet {
+        fn minimize(&mut self, gradient: &Array1<f64>) -> Array1<f64> {
+            Array1::from_iter((0..gradient.len()).map(|i| if gradient[i] > 0f64 { self.lower[i] } else { self.upper[i] }))
+        }
+    }
+
+    /// |Ax - y|^2 на шаре |x|_1 <= radius (LASSO в форме с ограничением), начало - в нуле
+    fn least_squares_l1(a: &Array2<f64>, y: &Array1<f64>, radius: f64, settings: &FrankWolfeSettings) -> FrankWolfeResult {
+        let objective = LeastSquares::new(a.clone(), y.clone());
+        frank_wolfe(&objective, &mut L1Ball::new(radius), Array1::zeros(a.ncols()), settings)
+    }
+
+    #[test]
+    fn oracles_return_minimizing_vertex() {
+        assert_eq!(Simplex::new(2f64).minimize(&array![3f64, -1f64, 2f64]), array![0f64, 2f64, 0f64]);
+        assert_eq!(L1Ball::new(2f64).minimize(&array![0.5f64, -3f64, 2f64]), array![0f64, 2f64, 0f64]);
+        assert_eq!(L1Ball::new(2f64).minimize(&array![0.5f64, 1f64, 3f64]), array![0f64, 0f64, -2f64]);
+        assert_eq!(L1Ball::new(2f64).minimize(&array![0f64, 0f64]), array![0f64, 0f64]);
+        let mut cube = BoxSet::new(array![0f64, -1f64, -2f64], array![5f64, 4f64, 3f64]);
+        assert_eq!(cube.minimize(&array![1f64, -1f64, 0f64]), array![0f64, 4f64, 3f64]);
+    }
+
+    #[test]
+    fn least_squares_derivatives() {
+        let objective = LeastSquares::new(array![[2f64, 0f64], [1f64, 1f64]], array![2f64, 3f64]);
+        let x = array![1f64, -1f64];
+        // Ax - y = (0, -3)
+        assert_eq!(objective.value(&x), 9f64);
+        assert_eq!(objective.gradient(&x), array![-6f64, -6f64]);
+        // 2 A^T A v, A^T A = [[5, 1], [1, 1]]
+        assert_eq!(objective.hessian_product(&x, &array![1f64, 2f64]), array![14f64, 6f64]);
+    }
+
+    #[test]
+    fn step_rules() {
+        // f(t) = (2t - 1)^2 на отрезке x + t * d
+        let objective = LeastSquares::new(array![[1f64, 0f64], [0f64, 1f64]], array![1f64, 0f64]);
+        let (x, d) = (array![0f64, 0f64], array![2f64, 0f64]);
+        assert!((line_search(&objective, &x, &d, 1f64) - 0.5).abs() < 1e-12);
+        assert_eq!(line_search(&objective, &x, &d, 0.3), 0.3);
+        assert_eq!(line_search(&objective, &x, &-&d, 1f64), 0f64);
+        assert_eq!(short_step(2f64, &array![1f64, 1f64], 4f64, 1f64), 0.25);
+        assert_eq!(short_step(2f64, &array![1f64, 1f64], 1f64, 0.5), 0.5);
+        assert_eq!("short:2".parse::<StepRule>(), Ok(StepRule::ShortStep { lipschitz: 2f64 }));
+        assert!("short:-1".parse::<StepRule>().is_err());
+    }
+
+    #[test]
+    fn lasso_on_two_by_two_system() {
+        // min (2 x1 - 4)^2 + (x2 - 1)^2 при |x1| + |x2| <= 2: решение (1.8, 0.2), значение 0.8
+        let a = array![[2f64, 0f64], [0f64, 1f64]];
+        let y = array![4f64, 1f64];
+        for variant in [FrankWolfeVariant::AwayStep, FrankWolfeVariant::Pairwise, FrankWolfeVariant::Classic] {
+            let settings = FrankWolfeSettings::new(StepRule::LineSearch).with_variant(variant).with_tolerance(1e-10);
+            let result = least_squares_l1(&a, &y, 2f64, &settings);
+            let x = result.get_x();
+            assert!((x[0] - 1.8).abs() < 1e-3 && (x[1] - 0.2).abs() < 1e-3, "{:?}: {}", variant, x);
+            assert!(result.lower_bound <= 0.8 + 1e-12 && result.value >= 0.8 - 1e-12);
+            assert!(result.value - 0.8 < 1e-5, "{:?}: {}", variant, result.value);
+        }
+    }
+
+    #[test]
+    fn standard_step_on_simplex() {
+        // проекция (1, 1, -1) на симплекс: (0.5, 0.5, 0)
+        let objective = LeastSquares::new(Array2::eye(3), array![1f64, 1f64, -1f64]);
+        let settings = FrankWolfeSettings::new(StepRule::Standard).with_max_iterations(2000).with_tolerance(0f64);
+        let result = frank_wolfe(&objective, &mut Simplex::new(1f64), array![0f64, 0f64, 1f64], &settings);
+        let x = result.get_x();
+        assert!((x.sum() - 1f64).abs() < 1e-12 && x.iter().all(|&v| v >= 0f64));
+        assert!((x[0] - 0.5).abs() < 1e-2 && (x[1] - 0.5).abs() < 1e-2 && x[2] < 1e-2, "{}", x);
+        assert!(result.lower_bound <= 3f64 && result.value - 3f64 < 1e-3);
+    }
+
+    #[test]
+    fn flow_polytope_routes_by_arc_gradient() {
+        let labels = vec!["a".to_string(), "b".to_string()];
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(labels, &[(0, 1, 1f64, 10f64), (0, 1, 1f64, 10f64)]);
+        let commodities = vec![Demand::new("d".to_string(), "a".to_string(), "b".to_string(), 3f64)];
+        let mut polytope = FlowPolytope::new(&graph, &commodities);
+        // параллельные дуги различаются только id, каждая получает свою стоимость
+        assert_eq!(polytope.minimize(&array![5f64, 1f64]), array![0f64, 3f64]);
+        assert_eq!(polytope.minimize(&array![1f64, 5f64]), array![3f64, 0f64]);
+    }
+
+    #[test]
+    fn active_set_merges_equal_vertices_after_removal() {
+        let mut active = ActiveSet::new(array![1f64, 0f64, 0f64]);
+        active.add(array![0f64, 1f64, 0f64], 0.5);
+        active.add(array![0f64, 0f64, 1f64], 0.25);
+        active.add(array![0f64, 1f64, 0f64], 0.25);
+        assert_eq!(active.len(), 3);
+        // последняя вершина переезжает на место удаленной и по-прежнему находится по хешу
+        active.remove(0);
+        active.add(array![0f64, 0f64, 1f64], 0.5);
+        active.add(array![-0f64, 1f64, 0f64], 0.25);
+        assert_eq!(active.len(), 2);
+        let weight = |v: Array1<f64>| active.weights[active.vertices.iter().position(|x| *x == v).unwrap()];
+        assert_eq!(weight(array![0f64, 1f64, 0f64]), 1f64);
+        assert_eq!(weight(array![0f64, 0f64, 1f64]), 0.75);
+    }
+
+    #[test]
+    fn pairwise_steps_are_counted() {
+        let a = array![[2f64, 0f64], [0f64, 1f64]];
+        let y = array![4f64, 1f64];
+        let settings = FrankWolfeSettings::new(StepRule::Standard).with_max_iterations(20).with_tolerance(0f64);
+        let pairwise = least_squares_l1(&a, &y, 2f64, &settings.with_variant(FrankWolfeVariant::Pairwise));
+        assert_eq!(pairwise.pairwise_steps, 20);
+        assert_eq!(pairwise.away_steps, 0);
+        assert!(pairwise.to_string().contains("pairwise steps: 20"));
+        let away = least_squares_l1(&a, &y, 2f64, &settings.with_variant(FrankWolfeVariant::AwayStep));
+        assert_eq!(away.pairwise_steps, 0);
+        assert!(away.to_string().contains(&format!("away steps: {}", away.away_steps)));
+    }
+}
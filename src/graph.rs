@@ -2,12 +2,14 @@ use std::cell::{Ref, RefCell, RefMut};
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use ndarray::s;
-use crate::bag::Bag;
-use crate::structure_xml::Network;
+use crate::bag::{self, Bag};
+use crate::csr_graph::{CsrAdjacency, CsrIter};
+use crate::structure_xml::{LinkModel, Network};
 use crate::utils_graph::EdgeCapacityProduct;
 
 #[derive(Debug, Clone, Copy)]
 pub struct DirectedEdge {
+    id: i32, // порядковый номер дуги в графе
     v: i32, //from
     w: i32, //to
     capacity: f64,
@@ -16,7 +18,7 @@ pub struct DirectedEdge {
 
 impl PartialEq for DirectedEdge {
     fn eq(&self, other: &Self) -> bool {
-        self.v == other.v && self.w == self.w
+        self.v == other.v && self.w == other.w
     }
 }
 
@@ -31,14 +33,29 @@ impl Hash for DirectedEdge {
 
 impl DirectedEdge {
 
+    pub fn new(id: i32, from: i32, to: i32, cost: f64, capacity: f64) -> DirectedEdge {
+        DirectedEdge {
+            id,
+            v: from,
+            w: to,
+            capacity,
+            cost
+        }
+    }
+
     pub fn get_empty_edge(from: i32, to: i32) -> DirectedEdge {
         DirectedEdge {
+            id: -1,
             v: from,
             w: to,
             capacity: 0f64,
             cost: 0f64
         }
     }
+    pub fn get_id(&self) -> i32 {
+        self.id
+    }
+
     pub fn from(&self) -> i32 {
         return self.v
     }
@@ -60,14 +77,59 @@ impl DirectedEdge {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EdgeWeightedDigraph {
     v_count: i32,     // количество вершин
     e_count: i32, // количество ребер
     adj: Option<Vec<Bag<DirectedEdge>>>,// списки смежности
+    csr: Option<CsrAdjacency>, // списки смежности в формате CSR, после вызова compact
+    edge_enabled: Vec<bool>, // признак работающей дуги, индекс - id дуги
+    edge_tail: Vec<i32>, // id дуги -> вершина, из которой она выходит, -1 для удаленных дуг
+    node_enabled: Vec<bool>, // признак работающей вершины
+    enabled_v_count: i32, // количество работающих вершин
+    enabled_e_count: i32, // количество работающих дуг: дуга включена и обе ее вершины в работе
+    node_labels: Vec<String>, // исходные id вершин SNDlib, индекс - номер вершины
+    node_index: HashMap<String, i32>, // id вершины SNDlib -> номер вершины
+    link_ids: Vec<String>, // исходные id линков SNDlib
+    edge_link: HashMap<i32, usize>, // id дуги -> номер линка, из которого она построена
+    link_model: LinkModel, // модель линков SNDlib
     matrix: Option<Vec<Vec<f64>>>
 }
 
+enum EdgeIter<'a> {
+    Bag(bag::Iter<'a, DirectedEdge>),
+    Csr(CsrIter<'a>),
+    Empty,
+}
+
+/// Итератор по исходящим дугам вершины, не зависит от способа хранения графа.
+/// Отключенные дуги и дуги в отключенные вершины пропускаются
+pub struct EdgeList<'a> {
+    iter: EdgeIter<'a>,
+    // признаки работающих дуг и вершин, None - обход без фильтрации
+    enabled: Option<(&'a [bool], &'a [bool])>,
+}
+
+impl<'a> Iterator for EdgeList<'a> {
+    type Item = DirectedEdge;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let e = match &mut self.iter {
+                EdgeIter::Bag(iter) => iter.next().copied(),
+                EdgeIter::Csr(iter) => iter.next(),
+                EdgeIter::Empty => None,
+            }?;
+            let (edge_enabled, node_enabled) = match self.enabled {
+                Some(enabled) => enabled,
+                None => return Some(e)
+            };
+            if edge_enabled.get(e.id as usize).copied().unwrap_or(true) && node_enabled[e.w as usize] {
+                return Some(e);
+            }
+        }
+    }
+}
+
 impl EdgeWeightedDigraph {
 
     pub fn default_graph() -> EdgeWeightedDigraph {
@@ -75,6 +137,17 @@ impl EdgeWeightedDigraph {
             v_count: 0,
             e_count: 0,
             adj: None,
+            csr: None,
+            edge_enabled: vec![],
+            edge_tail: vec![],
+            node_enabled: vec![],
+            enabled_v_count: 0,
+            enabled_e_count: 0,
+            node_labels: vec![],
+            node_index: HashMap::new(),
+            link_ids: vec![],
+            edge_link: HashMap::new(),
+            link_model: LinkModel::Directed,
             matrix: None
         };
         graph
@@ -82,17 +155,18 @@ impl EdgeWeightedDigraph {
 
     pub fn graph_from_array_str(&mut self, mut array : Vec<String>) -> &mut Self{
         self.v_count = array[0].parse::<i32>().unwrap();
-        self.e_count = array[1].parse::<i32>().unwrap();
+        self.e_count = 0;
         let mut adj = vec![];
         for _ in 0..self.v_count {
             adj.push(Bag::get_empty_bag());
         }
-        for e in 2..array.len() {
-            let arr : Vec<String>= array[e].split_whitespace().map(|s| s.to_string()).collect();
+        for (e, line) in array.iter().enumerate().skip(2) {
+            let arr : Vec<String>= line.split_whitespace().map(|s| s.to_string()).collect();
             let ver = arr[0].parse::<i32>().unwrap();
             let edg = arr[1].parse::<i32>().unwrap();
             let cost = arr[2].parse::<f64>().unwrap();
             let e = DirectedEdge {
+                id: (e - 2) as i32,
                 v: ver,
                 w: edg,
                 cost,
@@ -102,38 +176,97 @@ impl EdgeWeightedDigraph {
             self.e_count += 1;
         }
         self.adj = Some(adj);
+        self.reset_enabled();
         self
     }
 
-    pub fn graph_from_struct_xml(&mut self, network: &Network, map_index: &HashMap<String, i32>) -> &mut Self{
+    /// граф из структуры SNDlib, номера вершин идут в порядке их описания в файле,
+    /// исходные id вершин и линков сохраняются в графе.
+    /// Для модели DIRECTED линк дает одну дугу, для BIDIRECTED и UNDIRECTED - пару встречных дуг
+    pub fn graph_from_struct_xml(&mut self, network: &Network, link_model: LinkModel) -> &mut Self{
         let network_struct = network.get_network_structure();
+        self.link_model = link_model;
         self.v_count = network_struct.get_node_count() as i32;
-        self.e_count = network_struct.get_link_count() as i32;
+        self.e_count = 0;
+        self.node_labels = network_struct.get_nodes().get_node_vec().iter().map(|n| n.get_id()).collect();
+        self.node_index = self.node_labels.iter().enumerate().map(|(i, id)| (id.clone(), i as i32)).collect();
+        self.link_ids = vec![];
+        self.edge_link = HashMap::new();
+        let map_index = &self.node_index;
         let mut adj = vec![];
         for _ in 0..self.v_count {
             adj.push(Bag::get_empty_bag());
         }
         let links = network_struct.get_links().get_vec_link();
+        let mut id = 0;
         for link in links {
             let from = *map_index.get(&link.get_source()).unwrap();
             let to = *map_index.get(&link.get_target()).unwrap();
             let mut e = DirectedEdge{
+                id,
                 v: from,
                 w: to,
                 cost: link.get_cost(),
                 capacity: link.get_capacity()
             };
             adj[e.from() as usize].add(e);
-            let mut _e = DirectedEdge {
-                v: to,
-                w: from,
-                cost: link.get_cost(),
-                capacity: link.get_capacity()
+            self.edge_link.insert(id, self.link_ids.len());
+            id += 1;
+            self.e_count += 1;
+            if link_model != LinkModel::Directed {
+                let mut _e = DirectedEdge {
+                    id,
+                    v: to,
+                    w: from,
+                    cost: link.get_cost(),
+                    capacity: link.get_capacity()
+                };
+                adj[_e.from() as usize].add(_e);
+                self.edge_link.insert(id, self.link_ids.len());
+                id += 1;
+                self.e_count += 1;
+            }
+            self.link_ids.push(link.get_id());
+        }
+        self.adj = Some(adj);
+        self.reset_enabled();
+        self
+    }
+
+    /// граф из списка дуг (откуда, куда, стоимость, пропускная способность) с исходными id вершин, модель DIRECTED;
+    /// id дуги - ее номер в списке, метка дуги - пара id вершин
+    pub fn graph_from_arcs(&mut self, node_labels: Vec<String>, arcs: &[(i32, i32, f64, f64)]) -> &mut Self {
+        self.link_model = LinkModel::Directed;
+        self.v_count = node_labels.len() as i32;
+        self.e_count = 0;
+        self.node_index = node_labels.iter().enumerate().map(|(i, id)| (id.clone(), i as i32)).collect();
+        self.link_ids = vec![];
+        self.edge_link = HashMap::new();
+        let mut adj = vec![];
+        for _ in 0..self.v_count {
+            adj.push(Bag::get_empty_bag());
+        }
+        for (id, &(from, to, cost, capacity)) in arcs.iter().enumerate() {
+            let e = DirectedEdge {
+                id: id as i32,
+                v: from,
+                w: to,
+                cost,
+                capacity
             };
-            adj[_e.from() as usize].add(_e);
+            adj[e.from() as usize].add(e);
+            // параллельные дуги получают разные id линков, как в add_edge
+            let mut link = format!("{}_{}", node_labels[from as usize], node_labels[to as usize]);
+            if self.link_ids.contains(&link) {
+                link = link + "_" + &id.to_string();
+            }
+            self.edge_link.insert(e.id, self.link_ids.len());
+            self.link_ids.push(link);
             self.e_count += 1;
         }
+        self.node_labels = node_labels;
         self.adj = Some(adj);
+        self.reset_enabled();
         self
     }
 
@@ -160,26 +293,296 @@ impl EdgeWeightedDigraph {
         self.e_count
     }
 
-    pub fn edge_list(&self, v:usize) -> &Bag<DirectedEdge> {
-        let bag = self.adj.as_ref().unwrap();
-        &bag[v]
+    /// количество работающих вершин
+    pub fn get_enabled_v_count(&self) -> i32 {
+        self.enabled_v_count
+    }
+
+    /// количество работающих дуг: дуга не отключена и обе ее вершины в работе
+    pub fn get_enabled_e_count(&self) -> i32 {
+        self.enabled_e_count
+    }
+
+    fn reset_enabled(&mut self) {
+        let edges = self.edges();
+        let max_id = edges.iter().map(|e| e.id + 1).max().unwrap_or(0);
+        self.edge_enabled = vec![true; max_id as usize];
+        self.edge_tail = vec![-1; max_id as usize];
+        for e in &edges {
+            self.edge_tail[e.id as usize] = e.v;
+        }
+        self.node_enabled = vec![true; self.v_count as usize];
+        self.enabled_v_count = self.v_count;
+        self.enabled_e_count = self.e_count;
+    }
+
+    /// переводит списки смежности, построенные любым загрузчиком, в CSR
+    pub fn compact(&mut self) -> &mut Self {
+        if let Some(adj) = self.adj.take() {
+            self.csr = Some(CsrAdjacency::from_bags(&adj));
+        }
+        self
+    }
+
+    pub fn edge_list(&self, v:usize) -> EdgeList<'_> {
+        let mut list = self.all_edges(v);
+        if !self.node_enabled[v] {
+            list.iter = EdgeIter::Empty;
+        }
+        list.enabled = Some((&self.edge_enabled, &self.node_enabled));
+        list
+    }
+
+    /// все исходящие дуги вершины, включая отключенные
+    fn all_edges(&self, v: usize) -> EdgeList<'_> {
+        let iter = match &self.csr {
+            Some(csr) => EdgeIter::Csr(csr.edge_list(v)),
+            None => EdgeIter::Bag(self.adj.as_ref().unwrap()[v].iter())
+        };
+        EdgeList {
+            iter,
+            enabled: None,
+        }
+    }
+
+    /// все дуги графа, включая отключенные
+    pub fn edges(&self) -> Vec<DirectedEdge> {
+        (0..self.v_count as usize).flat_map(|v| self.all_edges(v)).collect()
+    }
+
+    /// дуга с данным id, включая отключенные
+    pub fn find_edge_by_id(&self, id: i32) -> Option<DirectedEdge> {
+        let v = *self.edge_tail.get(usize::try_from(id).ok()?)?;
+        if v < 0 {
+            return None;
+        }
+        self.all_edges(v as usize).find(|e| e.id == id)
+    }
+
+    /// добавляет дугу v->w и возвращает ее id. Вершины с номерами от v_count добавляются в граф,
+    /// дуга получает собственный линк с меткой из id вершин
+    pub fn add_edge(&mut self, v: i32, w: i32, cost: f64, capacity: f64) -> i32 {
+        let nodes = i32::max(v, w) + 1;
+        if nodes > self.v_count {
+            for u in self.v_count..nodes {
+                if !self.node_labels.is_empty() {
+                    self.node_labels.push(u.to_string());
+                    self.node_index.insert(u.to_string(), u);
+                }
+                self.node_enabled.push(true);
+            }
+            self.enabled_v_count += nodes - self.v_count;
+            self.v_count = nodes;
+            self.modify_bags(|adj| adj.resize_with(nodes as usize, Bag::get_empty_bag));
+        }
+
+        let id = self.edge_enabled.len() as i32;
+        let e = DirectedEdge::new(id, v, w, cost, capacity);
+        self.modify_bags(|adj| adj[v as usize].add(e));
+        self.edge_enabled.push(true);
+        self.edge_tail.push(v);
+        let mut link = self.get_node_label(v) + "_" + &self.get_node_label(w);
+        if self.link_ids.contains(&link) {
+            link = link + "_" + &id.to_string();
+        }
+        self.edge_link.insert(id, self.link_ids.len());
+        self.link_ids.push(link);
+        self.e_count += 1;
+        if self.is_edge_enabled(&e) {
+            self.enabled_e_count += 1;
+        }
+        id
+    }
+
+    /// удаляет дугу с данным id
+    pub fn remove_edge_by_id(&mut self, id: i32) -> Option<DirectedEdge> {
+        let e = self.find_edge_by_id(id)?;
+        if self.is_edge_enabled(&e) {
+            self.enabled_e_count -= 1;
+        }
+        self.modify_bags(|adj| adj[e.v as usize].remove_first(|x| x.id == id));
+        self.edge_enabled[id as usize] = false;
+        self.edge_tail[id as usize] = -1;
+        self.edge_link.remove(&id);
+        self.e_count -= 1;
+        Some(e)
+    }
+
+    /// отключает или включает дугу с данным id, возвращает false, если дуги нет
+    pub fn set_edge_enabled_by_id(&mut self, id: i32, enabled: bool) -> bool {
+        let e = match self.find_edge_by_id(id) {
+            Some(e) => e,
+            None => return false
+        };
+        let was_enabled = self.is_edge_enabled(&e);
+        self.edge_enabled[id as usize] = enabled;
+        self.enabled_e_count += self.is_edge_enabled(&e) as i32 - was_enabled as i32;
+        true
+    }
+
+    pub fn is_edge_enabled(&self, edge: &DirectedEdge) -> bool {
+        self.edge_enabled.get(edge.id as usize).copied().unwrap_or(true)
+            && self.node_enabled[edge.v as usize]
+            && self.node_enabled[edge.w as usize]
+    }
+
+    /// выводит вершину из работы вместе со всеми входящими и исходящими дугами
+    pub fn disable_node(&mut self, v: i32) {
+        if !self.node_enabled[v as usize] {
+            return;
+        }
+        self.enabled_e_count -= self.enabled_incident_count(v);
+        self.node_enabled[v as usize] = false;
+        self.enabled_v_count -= 1;
+    }
+
+    /// количество работающих дуг, входящих в вершину v или выходящих из нее
+    fn enabled_incident_count(&self, v: i32) -> i32 {
+        self.edges().iter()
+            .filter(|e| (e.v == v || e.w == v) && self.is_edge_enabled(e))
+            .count() as i32
+    }
+
+    pub fn is_node_enabled(&self, v: i32) -> bool {
+        self.node_enabled[v as usize]
+    }
+
+    /// пропускная способность дуги с данным id, возвращает false, если дуги нет
+    pub fn set_capacity_by_id(&mut self, id: i32, capacity: f64) -> bool {
+        self.modify_edge(id, |_, c| *c = capacity)
     }
 
-    pub fn update_edge(&mut self, v:i32, w:i32, cost:f64) {
-        let bag = self.adj.as_deref_mut().unwrap();
-        let edge = &mut bag[v as usize].iter_mut()
-            .find(|n| find(v, w, **n));
-        match edge {
-            Some(ref mut e) => e.cost = cost,
-            None => {}
+    /// изменение состава дуг; граф в формате CSR перестраивается целиком
+    fn modify_bags<R, F: FnOnce(&mut Vec<Bag<DirectedEdge>>) -> R>(&mut self, f: F) -> R {
+        match self.csr.take() {
+            Some(csr) => {
+                let mut adj = csr.to_bags();
+                let result = f(&mut adj);
+                self.csr = Some(CsrAdjacency::from_bags(&adj));
+                result
+            }
+            None => f(self.adj.as_mut().unwrap())
         }
     }
 
+    /// стоимость дуги с данным id, возвращает false, если дуги нет
+    pub fn update_edge_by_id(&mut self, id: i32, cost: f64) -> bool {
+        self.modify_edge(id, |c, _| *c = cost)
+    }
+
+    /// меняет стоимость и пропускную способность дуги с данным id в списках смежности или в CSR
+    fn modify_edge<F: FnOnce(&mut f64, &mut f64)>(&mut self, id: i32, f: F) -> bool {
+        let v = match self.find_edge_by_id(id) {
+            Some(e) => e.v as usize,
+            None => return false
+        };
+        if let Some(csr) = self.csr.as_mut() {
+            let k = csr.position_of_id(v, id).unwrap();
+            let (mut cost, mut capacity) = (csr.get_costs()[k], csr.get_capacities()[k]);
+            f(&mut cost, &mut capacity);
+            csr.set_cost(k, cost);
+            csr.set_capacity(k, capacity);
+            return true;
+        }
+        let e = self.adj.as_deref_mut().unwrap()[v].iter_mut().find(|e| e.id == id).unwrap();
+        f(&mut e.cost, &mut e.capacity);
+        true
+    }
+
+    /// стоимость пути по текущим весам графа (веса в самих ребрах пути могут быть устаревшими).
+    /// Дуга пути ищется по id, для дуг без id графа - первая работающая дуга с теми же концами
+    pub fn path_cost(&self, path: &[DirectedEdge]) -> f64 {
+        path.iter()
+            .map(|p| match self.find_edge_by_id(p.id).filter(|e| find(p.v, p.w, *e)) {
+                Some(e) => if self.is_edge_enabled(&e) { e.cost } else { f64::INFINITY },
+                None => self.edge_list(p.from() as usize)
+                    .find(|n| find(p.from(), p.to(), *n))
+                    .map_or(f64::INFINITY, |e| e.cost)
+            })
+            .sum()
+    }
+
+    /// доступ к списку смежности на изменение, только до вызова compact
     pub fn edge_list_mut(&mut self, v: usize) -> &mut Bag<DirectedEdge> {
-        let bag = self.adj.as_deref_mut().unwrap();
+        let bag = self.adj.as_deref_mut().expect("Граф хранится в формате CSR");
         &mut bag[v]
     }
 
+    /// исходный id вершины, для графов без меток - ее номер
+    pub fn get_node_label(&self, v: i32) -> String {
+        match self.node_labels.get(v as usize) {
+            Some(label) => label.clone(),
+            None => v.to_string()
+        }
+    }
+
+    /// номер вершины по исходному id, для графов без меток id - это номер вершины
+    pub fn get_node_index(&self, label: &str) -> Option<i32> {
+        if self.node_labels.is_empty() {
+            return label.parse::<i32>().ok().filter(|&v| v >= 0 && v < self.v_count);
+        }
+        self.node_index.get(label).copied()
+    }
+
+    /// id линка SNDlib, из которого построена дуга
+    pub fn get_link_id(&self, edge: &DirectedEdge) -> Option<&str> {
+        self.edge_link.get(&edge.id).map(|&l| self.link_ids[l].as_str())
+    }
+
+    /// метка дуги: id линка, для дуг без линка - пара номеров вершин
+    pub fn get_edge_label(&self, edge: &DirectedEdge) -> String {
+        match self.get_link_id(edge) {
+            Some(link) => link.to_string(),
+            None => edge.v.to_string() + "_" + &edge.w.to_string()
+        }
+    }
+
+    pub fn get_link_ids(&self) -> &[String] {
+        &self.link_ids
+    }
+
+    /// дуги, построенные из линка: для двунаправленного линка - пара встречных дуг
+    pub fn get_link_edges(&self, link_id: &str) -> Vec<DirectedEdge> {
+        let link = match self.link_ids.iter().position(|l| l == link_id) {
+            Some(l) => l,
+            None => return vec![]
+        };
+        let mut edges: Vec<DirectedEdge> = self.edges().into_iter()
+            .filter(|e| self.edge_link.get(&e.id) == Some(&link))
+            .collect();
+        edges.sort_by_key(|e| e.id);
+        edges
+    }
+
+    /// встречная дуга, построенная из того же линка
+    pub fn get_twin_edge(&self, edge: &DirectedEdge) -> Option<DirectedEdge> {
+        let link = self.edge_link.get(&edge.id)?;
+        self.all_edges(edge.w as usize)
+            .find(|e| e.w == edge.v && self.edge_link.get(&e.id) == Some(link))
+    }
+
+    pub fn get_link_model(&self) -> LinkModel {
+        self.link_model
+    }
+
+    /// для модели UNDIRECTED встречная дуга, с которой дуга делит пропускную способность
+    pub fn get_shared_capacity_twin(&self, edge: &DirectedEdge) -> Option<DirectedEdge> {
+        if self.link_model != LinkModel::Undirected {
+            return None;
+        }
+        self.get_twin_edge(edge)
+    }
+
+    /// путь в виде исходных id вершин; ребра пути идут в обратном порядке, как в DijkstraSP::path_to
+    pub fn path_to_labels(&self, path: &[DirectedEdge]) -> Vec<String> {
+        if path.is_empty() {
+            return vec![];
+        }
+        let mut labels = vec![self.get_node_label(path[path.len() - 1].v)];
+        labels.extend(path.iter().rev().map(|e| self.get_node_label(e.w)));
+        labels
+    }
+
     pub fn get_matrix_connectivity(&self) -> &Vec<Vec<f64>> {
          &self.matrix.as_ref().unwrap()
     }
@@ -190,4 +593,85 @@ fn find(v: i32, w:i32, e: DirectedEdge) -> bool {
         return true
     }
     return false
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// две параллельные дуги 0->1 и дуга 1->2
+    fn parallel() -> EdgeWeightedDigraph {
+        let labels = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(labels, &[(0, 1, 1f64, 10f64), (0, 1, 2f64, 20f64), (1, 2, 1f64, 10f64)]);
+        graph
+    }
+
+    #[test]
+    fn id_setters_address_parallel_arcs() {
+        for compact in [false, true] {
+            let mut graph = parallel();
+            if compact {
+                graph.compact();
+            }
+            assert!(graph.update_edge_by_id(1, 5f64));
+            assert!(graph.set_capacity_by_id(1, 50f64));
+            assert!(graph.set_edge_enabled_by_id(0, false));
+            let first = graph.find_edge_by_id(0).unwrap();
+            let second = graph.find_edge_by_id(1).unwrap();
+            assert_eq!((first.get_cost(), first.get_capacity()), (1f64, 10f64));
+            assert_eq!((second.get_cost(), second.get_capacity()), (5f64, 50f64));
+            assert!(!graph.is_edge_enabled(&first));
+            assert!(graph.is_edge_enabled(&second));
+            assert_eq!(graph.path_cost(&[second]), 5f64);
+            assert_eq!(graph.path_cost(&[first]), f64::INFINITY);
+            assert!(!graph.set_capacity_by_id(7, 1f64));
+        }
+    }
+
+    #[test]
+    fn parallel_arcs_get_separate_links() {
+        let graph = parallel();
+        let first = graph.find_edge_by_id(0).unwrap();
+        let second = graph.find_edge_by_id(1).unwrap();
+        assert_eq!(graph.get_link_id(&first), Some("a_b"));
+        assert_eq!(graph.get_link_id(&second), Some("a_b_1"));
+        assert_eq!(graph.get_link_edges("a_b").iter().map(|e| e.get_id()).collect::<Vec<i32>>(), vec![0]);
+        assert_eq!(graph.get_link_edges("a_b_1").iter().map(|e| e.get_id()).collect::<Vec<i32>>(), vec![1]);
+    }
+
+    #[test]
+    fn enabled_counts_follow_edges_and_nodes() {
+        let mut graph = parallel();
+        assert_eq!((graph.get_enabled_v_count(), graph.get_enabled_e_count()), (3, 3));
+        graph.set_edge_enabled_by_id(0, false);
+        graph.set_edge_enabled_by_id(0, false);
+        assert_eq!(graph.get_enabled_e_count(), 2);
+        graph.remove_edge_by_id(2);
+        assert_eq!((graph.get_e_count(), graph.get_enabled_e_count()), (2, 1));
+        assert!(graph.find_edge_by_id(2).is_none());
+        graph.set_edge_enabled_by_id(0, true);
+        assert_eq!(graph.get_enabled_e_count(), 2);
+        graph.disable_node(1);
+        graph.disable_node(1);
+        assert_eq!((graph.get_enabled_v_count(), graph.get_enabled_e_count()), (2, 0));
+        // дуга отключенной вершины не включается вместе с дугой
+        graph.set_edge_enabled_by_id(0, false);
+        graph.set_edge_enabled_by_id(0, true);
+        assert_eq!(graph.get_enabled_e_count(), 0);
+    }
+
+    #[test]
+    fn add_edge_grows_nodes_and_registers_link() {
+        let mut graph = parallel();
+        let id = graph.add_edge(2, 4, 1f64, 10f64);
+        assert_eq!((graph.get_v_count(), graph.get_e_count()), (5, 4));
+        assert_eq!((graph.get_enabled_v_count(), graph.get_enabled_e_count()), (5, 4));
+        let e = graph.find_edge_by_id(id).unwrap();
+        assert_eq!(graph.get_link_id(&e), Some("c_4"));
+        assert_eq!(graph.get_node_index("4"), Some(4));
+        let twin = graph.add_edge(0, 1, 1f64, 10f64);
+        let twin = graph.find_edge_by_id(twin).unwrap();
+        assert_eq!(graph.get_link_id(&twin), Some("a_b_4"));
+        assert_eq!(graph.edge_list(3).count(), 0);
+    }
+}
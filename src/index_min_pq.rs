@@ -1,116 +1,294 @@
+use std::cmp::Ordering;
+use std::fmt;
 
-pub struct IndexMinPQ {
-    max_n: i32,
-    n: i32,
-    pq: Vec<i32>,
-    qp: Vec<i32>,
-    keys: Vec<f64>
+/// Ошибки операций индексированной очереди с приоритетом
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexMinPQError {
+    /// индекс вне диапазона 0..max_n
+    IndexOutOfRange(usize),
+    /// индекс уже есть в очереди
+    AlreadyContains(usize),
+    /// индекса нет в очереди
+    NotContains(usize),
+    /// очередь пуста
+    Empty,
+    /// decrease_key вызван с ключом, который не меньше текущего
+    KeyNotDecreased(usize),
+    /// арность кучи меньше 2
+    InvalidArity(usize),
 }
 
-impl IndexMinPQ {
+impl fmt::Display for IndexMinPQError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexMinPQError::IndexOutOfRange(i) => write!(f, "индекс {} вне диапазона очереди", i),
+            IndexMinPQError::AlreadyContains(i) => write!(f, "индекс {} уже есть в очереди", i),
+            IndexMinPQError::NotContains(i) => write!(f, "индекса {} нет в очереди", i),
+            IndexMinPQError::Empty => write!(f, "очередь пуста"),
+            IndexMinPQError::KeyNotDecreased(i) => write!(f, "новый ключ для индекса {} не меньше текущего", i),
+            IndexMinPQError::InvalidArity(d) => write!(f, "арность кучи {} меньше 2", d),
+        }
+    }
+}
+
+impl std::error::Error for IndexMinPQError {}
+
+/// Индексированная очередь с минимальным приоритетом на d-арной куче.
+/// Каждому индексу 0..max_n соответствует не более одного ключа
+#[derive(Debug, Clone)]
+pub struct IndexMinPQ<K: PartialOrd> {
+    max_n: usize,
+    d: usize,               // арность кучи
+    n: usize,               // количество элементов в очереди
+    pq: Vec<usize>,         // pq[k] - индекс, стоящий в позиции k кучи
+    qp: Vec<Option<usize>>, // qp[i] - позиция индекса i в куче
+    keys: Vec<Option<K>>
+}
+
+impl<K: PartialOrd> IndexMinPQ<K> {
 
-    pub fn get_index_from_size(max_n: i32) -> Self {
-        //todo добавить исключение
-        let mut index = IndexMinPQ {
-           max_n,
+    pub fn get_index_from_size(max_n: usize) -> Self {
+        IndexMinPQ {
+            max_n,
+            d: 2,
             n: 0,
-            keys: vec![0.0; (max_n + 1) as usize],
-            pq: vec![0; (max_n + 1) as usize],
-            qp: vec![-1; (max_n + 1) as usize],
-        };
-        index
+            pq: Vec::with_capacity(max_n),
+            qp: vec![None; max_n],
+            keys: (0..max_n).map(|_| None).collect(),
+        }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.n == 0
+    /// очередь на d-арной куче, при большом числе decrease_key (плотные графы) выгоднее d = 4..8
+    pub fn with_arity(max_n: usize, d: usize) -> Result<Self, IndexMinPQError> {
+        if d < 2 {
+            return Err(IndexMinPQError::InvalidArity(d));
+        }
+        let mut index = Self::get_index_from_size(max_n);
+        index.d = d;
+        Ok(index)
     }
 
-    pub fn size(&self) -> i32 {
+    pub fn size(&self) -> usize {
         self.n
     }
 
     pub fn contains(&self, i: usize) -> bool {
-        //todo добавить исключение
-        self.qp[i] != -1
+        i < self.max_n && self.qp[i].is_some()
     }
 
-    pub fn insert(&mut self, i: usize, key: f64) {
+    pub fn insert(&mut self, i: usize, key: K) -> Result<(), IndexMinPQError> {
+        self.check_index(i)?;
         if self.contains(i) {
-            //todo добавить исключение
+            return Err(IndexMinPQError::AlreadyContains(i));
         }
+        self.qp[i] = Some(self.n);
+        self.pq.push(i);
+        self.keys[i] = Some(key);
         self.n += 1;
-        self.qp[i] = self.n;
-        self.pq[self.n as usize] = i as i32;
-        self.keys[i] = key;
-        Self::swim(self, self.n);
+        self.swim(self.n - 1);
+        Ok(())
     }
 
-    pub fn del_min(&mut self) -> usize {
+    pub fn min_index(&self) -> Result<usize, IndexMinPQError> {
         if self.n == 0 {
-            //todo добавить исключение
+            return Err(IndexMinPQError::Empty);
         }
-        let min = self.pq[1] as usize;
-        self.exch(1usize, self.n as usize);
-        self.n -= 1;
-        self.sink(1);
-        self.qp[min] = -1;
-        self.keys[min] = 0.0;
-        self.pq[(self.n+1) as usize] = -1;        // not needed
-        min
+        Ok(self.pq[0])
     }
 
-    pub fn change(&mut self, i: usize, key: f64) {
-        Self::change_key(self, i, key);
+    pub fn del_min(&mut self) -> Result<usize, IndexMinPQError> {
+        let min = self.min_index()?;
+        self.remove_at(0);
+        Ok(min)
     }
 
-    fn change_key(&mut self, i: usize, key: f64) {
-        //todo добавить исключение
-        if self.contains(i) {
-            //todo добавить исключение
+    pub fn decrease_key(&mut self, i: usize, key: K) -> Result<(), IndexMinPQError> {
+        let k = self.position(i)?;
+        if key.partial_cmp(self.keys[i].as_ref().unwrap()) != Some(Ordering::Less) {
+            return Err(IndexMinPQError::KeyNotDecreased(i));
         }
-        self.keys[i] = key;
-        Self::swim(self, self.qp[i]);
-        Self::sink(self, self.qp[i]);
+        self.keys[i] = Some(key);
+        self.swim(k);
+        Ok(())
+    }
+
+    fn check_index(&self, i: usize) -> Result<(), IndexMinPQError> {
+        if i >= self.max_n {
+            return Err(IndexMinPQError::IndexOutOfRange(i));
+        }
+        Ok(())
+    }
+
+    fn position(&self, i: usize) -> Result<usize, IndexMinPQError> {
+        self.check_index(i)?;
+        self.qp[i].ok_or(IndexMinPQError::NotContains(i))
+    }
+
+    fn remove_at(&mut self, k: usize) -> K {
+        let i = self.pq[k];
+        let last = self.n - 1;
+        self.exch(k, last);
+        self.pq.pop();
+        self.n -= 1;
+        self.qp[i] = None;
+        if k < self.n {
+            self.swim(k);
+            self.sink(k);
+        }
+        self.keys[i].take().unwrap()
     }
 
     fn exch(&mut self, i: usize, j: usize) {
-        (self.pq[i], self.pq[j])= (self.pq[j], self.pq[i]);
-        self.qp[self.pq[i] as usize] = i as i32;
-        self.qp[self.pq[j] as usize] = j as i32;
+        self.pq.swap(i, j);
+        self.qp[self.pq[i]] = Some(i);
+        self.qp[self.pq[j]] = Some(j);
     }
 
-    fn sink(&mut self, mut k: i32) {
-        while 2*k <= self.n {
-            let mut j = 2*k;
-            if j < self.n && Self::greater(self, j, j+1) {
-                j += 1;
+    fn sink(&mut self, mut k: usize) {
+        loop {
+            let first = self.d * k + 1;
+            if first >= self.n {
+                break;
+            }
+            let last = usize::min(first + self.d, self.n);
+            let mut j = first;
+            for c in first + 1..last {
+                if self.greater(j, c) {
+                    j = c;
+                }
             }
-            if !Self::greater(self, k, j) {
+            if !self.greater(k, j) {
                 break;
             }
-            self.exch(k as usize, j as usize);
+            self.exch(k, j);
             k = j;
         }
     }
 
-    fn greater(&self, i:i32, j:i32) -> bool {
-        let it1 = self.pq[i as usize];
-        let it2 = self.pq[j as usize];
-        Self::compare(self.keys[it1 as usize], self.keys[it2 as usize])
+    fn swim(&mut self, mut k: usize) {
+        while k > 0 {
+            let parent = (k - 1) / self.d;
+            if !self.greater(parent, k) {
+                break;
+            }
+            self.exch(k, parent);
+            k = parent;
+        }
     }
 
-    fn compare(a: f64, b: f64) -> bool {
-        if a > b {
-           return  true
+    fn greater(&self, i: usize, j: usize) -> bool {
+        let a = self.keys[self.pq[i]].as_ref().unwrap();
+        let b = self.keys[self.pq[j]].as_ref().unwrap();
+        a > b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    use super::*;
+    use crate::random::XorShiftRng;
+
+    /// Эталон: BinaryHeap с ленивым удалением, актуальный ключ индекса хранится в keys
+    struct Reference {
+        heap: BinaryHeap<Reverse<(u32, usize)>>,
+        keys: Vec<Option<u32>>
+    }
+
+    impl Reference {
+        fn new(max_n: usize) -> Reference {
+            Reference { heap: BinaryHeap::new(), keys: vec![None; max_n] }
+        }
+
+        fn set(&mut self, i: usize, key: u32) {
+            self.keys[i] = Some(key);
+            self.heap.push(Reverse((key, i)));
+        }
+
+        fn min_key(&mut self) -> Option<u32> {
+            while let Some(&Reverse((key, i))) = self.heap.peek() {
+                if self.keys[i] == Some(key) {
+                    return Some(key);
+                }
+                self.heap.pop();
+            }
+            None
+        }
+
+        fn len(&self) -> usize {
+            self.keys.iter().filter(|k| k.is_some()).count()
         }
-        false
     }
 
-    fn swim(&mut self, mut k: i32) {
-        while k > 1 && Self::greater(self, k/2, k) {
-            Self::exch(self, k as usize, (k/2) as usize);
-            k = k/2;
+    fn random_operations(arity: usize, seed: u64) {
+        let max_n = 40;
+        let mut rng = XorShiftRng::new(seed);
+        let mut pq = IndexMinPQ::with_arity(max_n, arity).unwrap();
+        let mut reference = Reference::new(max_n);
+        for _ in 0..5000 {
+            let i = rng.gen_range(0, max_n as i32 - 1) as usize;
+            let key = rng.gen_range(0, 1000) as u32;
+            match rng.gen_range(0, 2) {
+                0 => {
+                    let result = pq.insert(i, key);
+                    if reference.keys[i].is_some() {
+                        assert_eq!(result, Err(IndexMinPQError::AlreadyContains(i)));
+                    } else {
+                        assert_eq!(result, Ok(()));
+                        reference.set(i, key);
+                    }
+                }
+                1 => match reference.keys[i] {
+                    None => assert_eq!(pq.decrease_key(i, key), Err(IndexMinPQError::NotContains(i))),
+                    Some(current) if key < current => {
+                        assert_eq!(pq.decrease_key(i, key), Ok(()));
+                        reference.set(i, key);
+                    }
+                    Some(_) => assert_eq!(pq.decrease_key(i, key), Err(IndexMinPQError::KeyNotDecreased(i)))
+                },
+                _ => match reference.min_key() {
+                    None => assert_eq!(pq.del_min(), Err(IndexMinPQError::Empty)),
+                    Some(min) => {
+                        assert_eq!(pq.keys[pq.min_index().unwrap()], Some(min));
+                        // при равных ключах индексы могут выйти в любом порядке, проверяется только ключ
+                        let j = pq.del_min().unwrap();
+                        assert_eq!(reference.keys[j], Some(min));
+                        reference.keys[j] = None;
+                    }
+                }
+            }
+            assert_eq!(pq.size(), reference.len());
+            for (j, key) in reference.keys.iter().enumerate() {
+                assert_eq!(pq.contains(j), key.is_some());
+                assert_eq!(pq.keys[j], *key);
+            }
+        }
+        // оставшиеся элементы выходят в порядке неубывания ключей
+        let mut previous = 0;
+        while let Ok(j) = pq.del_min() {
+            let key = reference.keys[j].take().unwrap();
+            assert!(key >= previous);
+            previous = key;
         }
+        assert_eq!(reference.min_key(), None);
     }
-}
 
+    #[test]
+    fn matches_binary_heap_for_arity_2_to_4() {
+        for arity in 2..=4 {
+            for seed in 0..10 {
+                random_operations(arity, seed);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_bad_arity_and_index() {
+        assert_eq!(IndexMinPQ::<f64>::with_arity(10, 1).unwrap_err(), IndexMinPQError::InvalidArity(1));
+        let mut pq = IndexMinPQ::get_index_from_size(3);
+        assert_eq!(pq.insert(3, 1f64), Err(IndexMinPQError::IndexOutOfRange(3)));
+        assert_eq!(pq.min_index(), Err(IndexMinPQError::Empty));
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::delay_func_count::CostModel;
+use crate::dijkstra_find_path::DijkstraSP;
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::structure_xml::Demand;
+
+/// Загрузка одного линка (для BIDIRECTED и DIRECTED - одного направления линка)
+#[derive(Debug, Clone)]
+pub struct LinkLoad {
+    link_id: String,
+    from: String,
+    to: String,
+    load: f64,
+    capacity: f64
+}
+
+impl LinkLoad {
+
+    pub fn get_link_id(&self) -> &str {
+        &self.link_id
+    }
+
+    pub fn get_from(&self) -> &str {
+        &self.from
+    }
+
+    pub fn get_to(&self) -> &str {
+        &self.to
+    }
+
+    pub fn get_utilization(&self) -> f64 {
+        if self.load <= 0f64 {
+            0f64
+        } else if self.capacity > 0f64 {
+            self.load / self.capacity
+        } else {
+            f64::INFINITY
+        }
+    }
+}
+
+/// Распределение загрузки по всем линкам сети
+#[derive(Debug, Clone)]
+pub struct LinkUtilization {
+    links: Vec<LinkLoad>
+}
+
+impl LinkUtilization {
+
+    pub fn get_links(&self) -> &[LinkLoad] {
+        &self.links
+    }
+
+    pub fn max_utilization(&self) -> f64 {
+        self.links.iter().map(|l| l.get_utilization()).fold(0f64, f64::max)
+    }
+
+    pub fn mean_utilization(&self) -> f64 {
+        if self.links.is_empty() {
+            return 0f64;
+        }
+        self.links.iter().map(|l| l.get_utilization()).sum::<f64>() / self.links.len() as f64
+    }
+
+    /// значение функции затрат Fortz-Thorup по всем линкам
+    pub fn fortz_thorup_cost(&self) -> f64 {
+        self.cost(CostModel::FortzThorup)
+    }
+
+    /// сумма функции затрат по всем линкам
+    pub fn cost(&self, cost_model: CostModel) -> f64 {
+        self.links.iter().map(|l| cost_model.value(l.load, l.capacity)).sum()
+    }
+
+    /// количество линков в интервалах загрузки [0, 0.1), [0.1, 0.2), ..., [0.9, 1), [1, inf)
+    pub fn histogram(&self) -> Vec<usize> {
+        let mut counts = vec![0; 11];
+        for link in &self.links {
+            let bucket = (link.get_utilization() * 10f64).floor();
+            counts[f64::min(bucket, 10f64) as usize] += 1;
+        }
+        counts
+    }
+}
+
+/// загрузка линков по потокам на дугах; дуги линка UNDIRECTED делят одну емкость, поэтому их потоки складываются
+pub fn link_utilization(graph_adj: &EdgeWeightedDigraph, edge_flows: &[(DirectedEdge, f64)]) -> LinkUtilization {
+    let flows: BTreeMap<i32, f64> = edge_flows.iter().map(|(e, f)| (e.get_id(), *f)).collect();
+    let mut links = vec![];
+    let mut seen = vec![];
+    for e in graph_adj.edges() {
+        if !graph_adj.is_edge_enabled(&e) || seen.contains(&e.get_id()) {
+            continue;
+        }
+        let mut load = flows.get(&e.get_id()).copied().unwrap_or(0f64);
+        if let Some(twin) = graph_adj.get_shared_capacity_twin(&e) {
+            load += flows.get(&twin.get_id()).copied().unwrap_or(0f64);
+            seen.push(twin.get_id());
+        }
+        links.push(LinkLoad {
+            link_id: graph_adj.get_edge_label(&e),
+            from: graph_adj.get_node_label(e.from()),
+            to: graph_adj.get_node_label(e.to()),
+            load,
+            capacity: e.get_capacity()
+        });
+    }
+    LinkUtilization {
+        links
+    }
+}
+
+/// Phi_UNCAP - затраты Fortz-Thorup сети без ограничений емкости: каждый commodity идет по пути с наименьшим числом линков
+/// и платит наклон 1 на каждом линке, то есть сумма demand * число линков на пути.
+/// Нормированная стоимость Phi* = Phi / Phi_UNCAP, при Phi* > 1 есть загрузка линков выше 1/3
+pub fn phi_uncap(graph_adj: &EdgeWeightedDigraph, commodities: &[Demand]) -> f64 {
+    let mut hop_graph = graph_adj.clone();
+    for e in graph_adj.edges() {
+        hop_graph.update_edge_by_id(e.get_id(), 1f64);
+    }
+    let mut phi = 0f64;
+    for commodity in commodities {
+        let source = graph_adj.get_node_index(&commodity.get_source());
+        let target = graph_adj.get_node_index(&commodity.get_target());
+        if let (Some(s), Some(t)) = (source, target) {
+            let sp = DijkstraSP::dijkstra(&mut hop_graph, s);
+            if sp.has_path_to(t as usize) {
+                phi += commodity.get_demand_vale() * sp.dist_to(t as usize);
+            }
+        }
+    }
+    phi
+}
+
+impl fmt::Display for LinkUtilization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut links: Vec<&LinkLoad> = self.links.iter().collect();
+        links.sort_by(|a, b| b.get_utilization().total_cmp(&a.get_utilization()));
+        for link in links {
+            writeln!(f, "link - {} ({} -> {}), load: {:.2}, capacity: {:.2}, utilization: {:.4}",
+                     link.link_id, link.from, link.to, link.load, link.capacity, link.get_utilization())?;
+        }
+        for (i, count) in self.histogram().iter().enumerate() {
+            if i < 10 {
+                writeln!(f, "[{:.1}, {:.1}): {}", i as f64 / 10f64, (i + 1) as f64 / 10f64, count)?;
+            } else {
+                writeln!(f, "[1.0, inf): {}", count)?;
+            }
+        }
+        writeln!(f, "max utilization: {:.4}, mean utilization: {:.4}", self.max_utilization(), self.mean_utilization())
+    }
+}
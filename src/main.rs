@@ -1,22 +1,40 @@
 use std::collections::HashMap;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, BufRead};
 use std::ops::Deref;
 use std::path::Path;
 use std::time::Instant;
+
 use ndarray::Array1;
 
-use crate::dijkstra_find_path::DijkstraSP;
+use crate::delay_func_count::CostModel;
+use crate::dijkstra_find_path::{DijkstraSP, TieBreak};
+use crate::ecmp::{evaluate_weights, inv_cap_weights, read_weights, write_weights, MAX_LINK_WEIGHT};
+use crate::failure_analysis::analyze_failures;
+use crate::flow_solution::FlowSolution;
+use crate::frank_wolf::{assign_link_based, assign_link_based_with, FlowPolytope, FrankWolfeSettings, FrankWolfeVariant, LinearMinimizationOracle, LinkCost, Objective, StepRule};
 use crate::graph::EdgeWeightedDigraph;
-use crate::parser_xml::parse_xml_to_structure;
-use crate::projection_method::ProjectionMethod;
-use crate::structure_xml::Demand;
-use crate::utils_graph::{symmetric_difference, vec_edge_to_str};
+use crate::link_utilization::{link_utilization, phi_uncap};
+use crate::bush_assignment::{assign_bush_based, BushSettings};
+use crate::checkpoint::ProjectionCheckpoint;
+use crate::concurrent_flow::max_concurrent_flow;
+use crate::max_flow::{dinic, push_relabel, GomoryHuTree};
+use crate::network_design::design_network;
+use crate::parser_xml::{parse_model_xml, parse_xml_to_structure};
+use crate::projection_solver::{solve_projection, solve_projection_checkpointed, solve_projection_warm, CheckpointSettings, PathStep, ProjectionSettings, WarmStart};
+use crate::structure_xml::{Demand, LinkModel};
+use crate::survivable::{route_survivable, Disjointness, Protection, SurvivableSettings};
+use crate::tntp::read_tntp;
+use crate::traffic_matrix::{estimate_traffic_matrix, EstimationSettings, RoutingMatrix, TrafficPrior};
+use crate::unsplittable::route_unsplittable;
+use crate::random::XorShiftRng;
+use crate::weight_optimization::{optimize_weights, WeightSearchSettings};
 
 mod bag;
 mod dijkstra_find_path;
 mod index_min_pq;
 mod graph;
+mod csr_graph;
 mod frank_wolf;
 mod floyd_find_path;
 mod utils_graph;
@@ -24,183 +42,580 @@ mod delay_func_count;
 mod structure_xml;
 mod parser_xml;
 mod projection_method;
+mod flow_solution;
+mod projection_solver;
+mod network_design;
+mod max_flow;
+mod concurrent_flow;
+mod link_utilization;
+mod ecmp;
+mod random;
+mod weight_optimization;
+mod unsplittable;
+mod constrained_path;
+mod failure_analysis;
+mod survivable;
+mod checkpoint;
+mod traffic_matrix;
+mod bush_assignment;
+mod tntp;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    // сравнение списков смежности Bag и CSR на тестовых файлах из data: --bench-csr <файл> [арность кучи]
+    if args.len() > 2 && args[1] == "--bench-csr" {
+        let arity = args.get(3).map_or(2, |d| d.parse::<usize>().expect("Неверная арность кучи"));
+        bench_csr(&args[2], arity);
+        return;
+    }
+    // сравнение вариантов метода Франка-Вульфа на тестовых файлах из data: --bench-fw <файл> [число итераций]
+    if args.len() > 2 && args[1] == "--bench-fw" {
+        let iterations = args.get(3).map_or(500, |i| i.parse::<i32>().expect("Неверное число итераций"));
+        bench_frank_wolfe(&args[2], iterations);
+        return;
+    }
+
     let start = Instant::now();
 
-    let network = parse_xml_to_structure("C:\\Users\\Dell\\mipt\\abilene.xml");
+    // сеть TNTP с функциями BPR, равновесие пользователей: --tntp <файл сети> <файл корреспонденций>
+    if let Some(i) = args.iter().position(|a| a == "--tntp") {
+        let (network_file, trips_file) = match (args.get(i + 1), args.get(i + 2)) {
+            (Some(network_file), Some(trips_file)) => (network_file, trips_file),
+            _ => panic!("Использование: --tntp <файл сети> <файл корреспонденций>")
+        };
+        run_tntp(&args, network_file, trips_file);
+        println!("Elapsed time: {:?}", start.elapsed());
+        return;
+    }
 
-    let mut point_index = HashMap::new();
+    let network_path = arg_value(&args, "--network").unwrap_or("C:\\Users\\Dell\\mipt\\abilene.xml".to_string());
+    let network = parse_xml_to_structure(&network_path);
 
-    // индексируем вершины графа, для более быстрого расчета алгоритма Дейкстры
-    let nodes = network.get_network_structure().get_nodes().get_node_vec();
-    for (i, node) in nodes.iter().enumerate() {
-        point_index.insert(node.get_id(), i as i32);
-    }
+    // модель линков: из файла модели SNDlib, из параметра --link-model, по умолчанию BIDIRECTED
+    let link_model = match (arg_value(&args, "--link-model"), arg_value(&args, "--model")) {
+        (Some(model), _) => model.parse::<LinkModel>().expect("Неверное значение --link-model"),
+        (None, Some(path)) => parse_model_xml(&path).get_link_model().unwrap_or(LinkModel::Bidirected),
+        (None, None) => LinkModel::Bidirected
+    };
 
     let commodities = network.get_demands().get_demand_vec();
 
     // данные для расчета метода проекции
     let alpha: f64 = 0.065;
     let p = 0.99;
-    let mut LB = 0f64;
     let epsilon: f64 = 0.0001;
+    // правило выбора между кратчайшими путями одинаковой длины
+    let tie_break = TieBreak::KeepCurrent;
 
-    //определяем сеть, каждое ребро добавляется два раза, в одну сторону и в другую
+    //определяем сеть, для моделей BIDIRECTED и UNDIRECTED каждое ребро добавляется два раза, в одну сторону и в другую
+    // вершины графа индексируются в порядке описания в файле, для более быстрого расчета алгоритма Дейкстры
     let mut g = EdgeWeightedDigraph::default_graph();
-    let graph_adj = EdgeWeightedDigraph::graph_from_struct_xml(&mut g, &network, &point_index);
+    let graph_adj = EdgeWeightedDigraph::graph_from_struct_xml(&mut g, &network, link_model).compact();
+    apply_what_if(&args, graph_adj);
+
+    // максимальный поток и минимальный разрез: --max-flow <source> <target> [dinic|push-relabel]
+    if let Some(i) = args.iter().position(|a| a == "--max-flow") {
+        let usage = "Использование: --max-flow <source> <target> [dinic|push-relabel]";
+        let (source, target) = match (args.get(i + 1), args.get(i + 2)) {
+            (Some(source), Some(target)) => (source, target),
+            _ => panic!("{}", usage)
+        };
+        let s = graph_adj.get_node_index(source).unwrap_or_else(|| panic!("Вершина {} не найдена", source));
+        let t = graph_adj.get_node_index(target).unwrap_or_else(|| panic!("Вершина {} не найдена", target));
+        let flow = match args.get(i + 3).map(|a| a.as_str()) {
+            None | Some("dinic") => dinic(graph_adj, s, t),
+            Some("push-relabel") => push_relabel(graph_adj, s, t),
+            Some(other) => panic!("Неизвестный алгоритм {}. {}", other, usage)
+        };
+        println!("max flow {} -> {}: {}", source, target, flow.get_value());
+        for (e, x) in flow.get_edge_flows().iter().filter(|(_, x)| *x > 0f64) {
+            println!("flow link - {} ({} -> {}): {}", graph_adj.get_edge_label(e),
+                     graph_adj.get_node_label(e.from()), graph_adj.get_node_label(e.to()), x);
+        }
+        let cut = flow.get_min_cut();
+        let side: Vec<String> = cut.get_source_side().iter().map(|&v| graph_adj.get_node_label(v)).collect();
+        println!("source side: {}", side.join(", "));
+        println!("min cut capacity: {}", cut.get_capacity());
+        for e in cut.get_edges() {
+            println!("cut link - {} ({} -> {}), capacity: {}", graph_adj.get_edge_label(e),
+                     graph_adj.get_node_label(e.from()), graph_adj.get_node_label(e.to()), e.get_capacity());
+        }
+        return;
+    }
+
+    // дерево Гомори-Ху, минимальные разрезы для всех пар вершин: --gomory-hu [<u> <v>],
+    // если указана пара вершин, печатается только минимальный разрез между ними
+    if let Some(i) = args.iter().position(|a| a == "--gomory-hu") {
+        let tree = GomoryHuTree::build(graph_adj);
+        match (args.get(i + 1), args.get(i + 2)) {
+            (Some(u), Some(v)) if !u.starts_with("--") && !v.starts_with("--") => {
+                let x = graph_adj.get_node_index(u).unwrap_or_else(|| panic!("Вершина {} не найдена", u));
+                let y = graph_adj.get_node_index(v).unwrap_or_else(|| panic!("Вершина {} не найдена", v));
+                println!("min cut {} - {}: {}", u, v, tree.min_cut_value(x, y));
+                return;
+            }
+            _ => {}
+        }
+        for (v, parent, value) in tree.get_edges() {
+            println!("{} - {}: {}", graph_adj.get_node_label(v), graph_adj.get_node_label(parent), value);
+        }
+        return;
+    }
+
+    // максимальный одновременный поток: --concurrent-flow <epsilon>
+    if let Some(eps) = arg_value(&args, "--concurrent-flow") {
+        let eps = eps.parse::<f64>().expect("Неверное значение --concurrent-flow");
+        let result = max_concurrent_flow(graph_adj, &commodities, eps);
+        println!("lambda: {}, upper bound: {}, phases: {}, unrouted demands: {}",
+                 result.get_lambda(), result.get_upper_bound(), result.get_phases(), result.get_unrouted().len());
+        print!("{}", result.get_solution());
+        for (e, l) in result.get_dual_lengths() {
+            println!("dual length {} ({} -> {}): {:e}", graph_adj.get_edge_label(e),
+                     graph_adj.get_node_label(e.from()), graph_adj.get_node_label(e.to()), l);
+        }
+        println!("Elapsed time: {:?}", start.elapsed());
+        return;
+    }
+
+    // целевая функция: --objective kleinrock (по умолчанию), fortz-thorup или minmax - минимизация максимальной загрузки линков,
+    // max загрузки заменяется гладкой суммой exp(beta * (x / c - 1)), чем больше --beta, тем точнее приближение
+    let cost_model = match arg_value(&args, "--objective").as_deref() {
+        None | Some("kleinrock") => CostModel::Kleinrock { p },
+        Some("minmax") => {
+            let beta = arg_value(&args, "--beta").map_or(20f64, |b| b.parse::<f64>().expect("Неверное значение --beta"));
+            CostModel::MinMaxUtilization { beta }
+        }
+        Some("fortz-thorup") => CostModel::FortzThorup,
+        Some(other) => panic!("Неизвестная целевая функция: {}", other)
+    };
+    // шаг перераспределения потока между путями commodity: --path-step diagonal (по умолчанию) или newton
+    let path_step = arg_value(&args, "--path-step").map(|s| s.parse::<PathStep>().expect("Неверное значение --path-step"));
+    let mut settings = ProjectionSettings::new(alpha, p, epsilon, tie_break).with_cost_model(cost_model)
+        .with_path_step(path_step.unwrap_or(PathStep::Diagonal));
+    // остановка после --stall-iterations итераций без уменьшения задержки, для fortz-thorup по умолчанию 20
+    if let Some(stall) = arg_value(&args, "--stall-iterations") {
+        settings = settings.with_stall_iterations(stall.parse::<i32>().expect("Неверное значение --stall-iterations"));
+    }
+    // предельное число итераций метода проекции: --max-iterations, по умолчанию 1000
+    let max_iterations = arg_value(&args, "--max-iterations");
+    if let Some(iterations) = &max_iterations {
+        settings = settings.with_max_iterations(iterations.parse::<i32>().expect("Неверное значение --max-iterations"));
+    }
+
+    // загрузка сети при настроенных весах OSPF/IS-IS: --ecmp <файл весов | invcap>
+    if let Some(path) = arg_value(&args, "--ecmp") {
+        let weights = if path == "invcap" { inv_cap_weights(graph_adj, MAX_LINK_WEIGHT) } else { read_weights(graph_adj, &path) };
+        let evaluation = evaluate_weights(graph_adj, &commodities, &weights, p);
+        print!("{}", evaluation.get_utilization());
+        println!("delay: {}, unrouted demands: {}", evaluation.get_delay(), evaluation.get_routing().get_unrouted().len());
+        println!("Elapsed time: {:?}", start.elapsed());
+        return;
+    }
+
+    // подбор весов OSPF/IS-IS под маршрутизацию ECMP: --optimize-weights <число итераций>, целевая функция из --objective,
+    // наибольший вес --max-weight, по умолчанию 65535
+    if let Some(iterations) = arg_value(&args, "--optimize-weights") {
+        let iterations = iterations.parse::<i32>().expect("Неверное значение --optimize-weights");
+        let seed = arg_value(&args, "--seed").map_or(1, |s| s.parse::<u64>().expect("Неверное значение --seed"));
+        let mut search = WeightSearchSettings::new(cost_model).with_iterations(iterations).with_seed(seed);
+        if let Some(max_weight) = arg_value(&args, "--max-weight") {
+            let max_weight = match max_weight.parse::<i32>() {
+                Ok(weight) if (1..=MAX_LINK_WEIGHT).contains(&weight) => weight,
+                _ => panic!("Неверное значение --max-weight: {}, ожидается целое от 1 до {}", max_weight, MAX_LINK_WEIGHT)
+            };
+            search = search.with_max_weight(max_weight);
+        }
+        let setting = optimize_weights(graph_adj, &commodities, &search);
+        print!("{}", setting);
+        print!("{}", link_utilization(graph_adj, setting.get_routing().get_edge_flows()));
+        if let Some(path) = arg_value(&args, "--save-weights") {
+            write_weights(graph_adj, setting.get_weights(), &path);
+        }
+        println!("Elapsed time: {:?}", start.elapsed());
+        return;
+    }
 
-    // map для хранения результата индекс commodity на пару path на размер потока на соответсвующем path
-    let mut result_x = HashMap::new();
+    // отказы линков по одному: --failures links, линков и вершин: --failures all
+    if let Some(scope) = arg_value(&args, "--failures") {
+        let with_nodes = match scope.as_str() {
+            "links" => false,
+            "all" => true,
+            other => panic!("Неверное значение --failures: {}", other)
+        };
+        let analysis = analyze_failures(graph_adj, &commodities, &settings, with_nodes);
+        print!("{}", analysis);
+        println!("Elapsed time: {:?}", start.elapsed());
+        return;
+    }
+
+    // защита путей: --survivable link|node, резервирование --protection dedicated (по умолчанию) или shared,
+    // --critical <id demand через запятую> - защищаются только эти demand, --survivable-rounds - число пересчетов пар путей,
+    // --save-solution сохраняет рабочий поток
+    if let Some(disjointness) = arg_value(&args, "--survivable") {
+        let disjointness = disjointness.parse::<Disjointness>().expect("Неверное значение --survivable");
+        let protection = arg_value(&args, "--protection")
+            .map_or(Protection::Dedicated, |p| p.parse::<Protection>().expect("Неверное значение --protection"));
+        let mut survivable = SurvivableSettings::new(disjointness, protection);
+        if let Some(critical) = arg_value(&args, "--critical") {
+            survivable = survivable.with_critical(critical.split(',').map(|id| id.trim().to_string()).collect());
+        }
+        if let Some(rounds) = arg_value(&args, "--survivable-rounds") {
+            survivable = survivable.with_rounds(rounds.parse::<i32>().expect("Неверное значение --survivable-rounds"));
+        }
+        let routing = route_survivable(graph_adj, &commodities, &settings, &survivable);
+        print!("{}", routing);
+        if let Some(path) = arg_value(&args, "--save-solution") {
+            routing.get_solution().write(&path);
+        }
+        println!("Elapsed time: {:?}", start.elapsed());
+        return;
+    }
 
-    // map для хранения кратчайшего пути для соответвующего commodity, индекс commodity на вектор ребер полученных из сети
-    let mut paths_shortest = HashMap::new();
-    let mut projection_handler = ProjectionMethod::new(alpha, p);
+    // планирование емкостей: --design <целевая загрузка линков>
+    if let Some(target) = arg_value(&args, "--design") {
+        let target = target.parse::<f64>().expect("Неверное значение --design");
+        let design = design_network(&network, graph_adj, &commodities, &settings, target, 50);
+        print!("{}", design);
+        println!("Elapsed time: {:?}", start.elapsed());
+        return;
+    }
 
-    // map для хранения активных path для соответвующего commodity, индекс commodity на map path -> список ребер
-    let mut active_paths = HashMap::new();
-
-    // определям кратчайшие пути для каждого commodity и ставим потоки на данные пути, после этого пересчитываем
-    // затраты на каждом ребре, которые входят в кратчайшие пути
-    for (index, commodity) in commodities.iter().enumerate() {
-        let source: &i32 = point_index.get(&commodity.get_source()).unwrap();
-        let target: &i32 = point_index.get(&commodity.get_target()).unwrap();
-        let r_k = commodity.get_demand_vale();
-        let r_index = index as i32;
-
-        let mut sp = DijkstraSP::dijkstra(graph_adj, *source);
-        // расчет кратчайшего маршрута, через алгоритм Дейкстры
-        let path_s = sp.path_to(*target as usize).expect("Путь не найден");
-        let key_path_s = &vec_edge_to_str(&path_s);
-
-        let mut active_paths_commodity = HashMap::new();
-        active_paths_commodity.insert(key_path_s.clone(), path_s.clone());
-        active_paths.insert(r_index, active_paths_commodity);
-
-        // устанавливаем поток на найденный кратчайший путь и обновляем ребра графа расчитавая cost
-        projection_handler.set_first_commodity_to_graph(r_index, r_k, &path_s, graph_adj);
-
-        // сохраняем кратчайший путь
-        paths_shortest.insert(r_index, path_s);
-    }
-
-    println!("{}", projection_handler.get_delay_value_x());
-
-    // задаем векторы и переменные для расчета остановки алгоритма
-    // остановка расчитывается по формуле статья Adam Ouorou для метода Flow Deviation
-    let mut y_j_t: Vec<f64> = Vec::new();
-    let mut x_j_t: Vec<f64> = Vec::new();
-    let mut delay_value_t = 0f64;
-    let mut grad = Vec::new();
-
-        // запуск работы метода, проходимся по каждому commodity,
-        // определяем новый кратчайший маршрут, если он совпадает с первоначальным, то считаем что маршрут для этого commodity определен и переходим к следующему
-        // если маршрут не совпадает добавлем его в список активных путей, и запускаем метод PM
-
-    delay_value_t = projection_handler.get_delay_value_x();
-
-    loop {
-         // значение функции
-        let x_j_t_value = projection_handler.get_total_flow_vector_x(); // значение потока на всех ребрах
-        x_j_t = x_j_t_value;
-        let y_j_t_value = projection_handler.get_total_flow_vector_y(); // значение потока на всех ребрах, если мы весь поток ставим на кратчайший маршрут
-        y_j_t = y_j_t_value;
-        let grad_value = projection_handler.get_delay_gradient(); // значение градиента функции после распределения потока по ребрам
-        grad = grad_value;
-        for (index, commodity) in commodities.iter().enumerate() {
-            let source: &i32 = point_index.get(&commodity.get_source()).unwrap();
-            let target: &i32 = point_index.get(&commodity.get_target()).unwrap();
-            let r_index = index as i32;
-
-            let mut method_step_commodity = HashMap::new();
-
-            loop {
-
-                // опеределяем новый кратчайший маршрут
-                let mut sp = DijkstraSP::dijkstra(graph_adj, *source);
-                let path_s = sp.path_to(*target as usize).expect("Путь не найден");
-                let key_path_s = &vec_edge_to_str(&path_s);
-
-                //проверяем совпадает ли он с первоначальным путем
-                let path_old = paths_shortest.get(&r_index).unwrap();
-                let key_path_old = &vec_edge_to_str(path_old);
-
-                method_step_commodity.insert(key_path_old.clone(), commodity.get_demand_vale());
-
-                if key_path_s == key_path_old {
-                    // если путь совпадает, сохраняем результат и берем следующий commodity
-                    result_x.insert(r_index, method_step_commodity);
-                    break;
-                }
-
-                // добавляем найденый путь в список активных путей
-                let mut active_paths_commodity = active_paths.get_mut(&r_index).unwrap();
-                active_paths_commodity.insert(key_path_s.clone(), path_s.clone());
-
-                let d_kp_s = projection_handler.get_d_k_p(&r_index, &path_s);
-
-                let mut result = 0f64;
-
-                //определяем x_kp^(t+1) для каждого пути кроме кратчайшего
-                for (key, value) in active_paths_commodity.clone() {
-                    if &key == key_path_s {
-                        projection_handler.update_edge_flow_y(r_index, commodity.get_demand_vale(), &value);
-                        continue;
-                    }
-
-                    let d_kp_i: _ = projection_handler.get_d_k_p(&r_index, &value);
-                    let lk_p = symmetric_difference(value.clone(), path_s.clone());
-                    let h_kp_i = projection_handler.get_h_k_p(&r_index, &lk_p);
-                    let x_k_p_t = method_step_commodity.get(&key).or(Some(&0f64)).unwrap();
-
-                    let gh = x_k_p_t - projection_handler.get_alpha() * ((1f64 / h_kp_i) * (d_kp_i - d_kp_s));
-
-                    //вычисляем x_k_p
-                    let x_k_p_t_1 = &f64::max(0f64, gh);
-                    method_step_commodity.insert(key.to_string(), *x_k_p_t_1); // обновляем поток для следующего шага
-                    result += x_k_p_t_1;
-                }
-                let x_k_p_s_t = commodity.get_demand_vale() - result; // рассчитываем поток для кратчайшего пути
-                method_step_commodity.insert(key_path_s.clone(), x_k_p_s_t); // сохраняем результат
-
-                // обновляем ребра графа, для расчета нового кратчайшего маршрута
-                if x_k_p_s_t > 0f64 {
-                    projection_handler.update_edge_flow(&method_step_commodity, r_index, graph_adj, active_paths_commodity);
-                    paths_shortest.insert(r_index, path_s);
-                } else {
-                    break;
-                }
-                result_x.insert(r_index, method_step_commodity.clone());
+    // оценка матрицы трафика по нагрузкам линков: --estimate-tm <файл нагрузки | self>, self - нагрузки и маршрутизация
+    // из решения метода проекции; --prior gravity (по умолчанию) или entropy, --lambda <вес регуляризации>,
+    // --tm-iterations <предельное число итераций>,
+    // --routing <файл решения> - маршрутизация из сохраненного решения, иначе по кратчайшим путям;
+    // --save-demands <файл> - оценка в виде demands SNDlib, --save-link-loads <файл> - нагрузки линков решения для self
+    if let Some(source) = arg_value(&args, "--estimate-tm") {
+        let prior = arg_value(&args, "--prior")
+            .map_or(TrafficPrior::Gravity, |p| p.parse::<TrafficPrior>().expect("Неверное значение --prior"));
+        let mut estimation = EstimationSettings::new(prior);
+        if let Some(lambda) = arg_value(&args, "--lambda") {
+            estimation = estimation.with_lambda(lambda.parse::<f64>().expect("Неверное значение --lambda"));
+        }
+        if let Some(iterations) = arg_value(&args, "--tm-iterations") {
+            estimation = estimation.with_max_iterations(iterations.parse::<i32>().expect("Неверное значение --tm-iterations"));
+        }
+        let (routing, loads) = if source == "self" {
+            let result = solve_projection(graph_adj, &commodities, &settings);
+            let routing = RoutingMatrix::from_solution(graph_adj, result.get_solution());
+            let loads = routing.loads_from_edge_flows(result.get_edge_flows());
+            if let Some(path) = arg_value(&args, "--save-link-loads") {
+                routing.write_link_loads(&loads, &path);
             }
+            (routing, loads.into_iter().map(Some).collect())
+        } else {
+            let routing = match arg_value(&args, "--routing") {
+                Some(path) => RoutingMatrix::from_solution(graph_adj, &FlowSolution::read(&path)),
+                None => RoutingMatrix::shortest_path(graph_adj)
+            };
+            let loads = routing.read_link_loads(&source);
+            (routing, loads)
+        };
+        let estimate = estimate_traffic_matrix(&routing, &loads, &estimation);
+        print!("{}", estimate);
+        // сравнение с demand из файла сети
+        let mut actual = HashMap::new();
+        for c in &commodities {
+            *actual.entry((c.get_source(), c.get_target())).or_insert(0f64) += c.get_demand_vale();
         }
-        let delay_value_t_1 = projection_handler.get_delay_value_x();
-        let grad_a = Array1::from(grad.to_vec());
-        let y_t_a = Array1::from(y_j_t.to_vec());
-        let x_t_a = Array1::from(x_j_t.to_vec());
-        let diff = x_t_a - y_t_a;
-        let t = delay_value_t + grad_a.dot(&diff);
-        LB = f64::max(LB, t);
+        let (error, norm) = estimate.get_pairs().iter().zip(estimate.get_estimate())
+            .fold((0f64, 0f64), |(error, norm), ((s, t), x)| {
+                let d = actual.get(&(s.clone(), t.clone())).copied().unwrap_or(0f64);
+                (error + (x - d) * (x - d), norm + d * d)
+            });
+        if norm > 0f64 {
+            println!("relative error to network demands: {:.6}", (error / norm).sqrt());
+        }
+        if let Some(path) = arg_value(&args, "--save-demands") {
+            fs::write(&path, estimate.to_demands().to_xml()).expect("Не удалось записать файл demands");
+        }
+        println!("Elapsed time: {:?}", start.elapsed());
+        return;
+    }
+
+    // распределение потоков по дугам методом Франка-Вульфа: --frank-wolfe standard|line-search|short:<L>,
+    // --fw-variant classic (по умолчанию), away-step, pairwise, conjugate или bi-conjugate,
+    // --fw-iterations <число итераций>, --fw-gap <относительный зазор>, --history <файл истории сходимости>
+    if let Some(step) = arg_value(&args, "--frank-wolfe") {
+        let mut fw = FrankWolfeSettings::new(step.parse::<StepRule>().expect("Неверное значение --frank-wolfe"));
+        if let Some(variant) = arg_value(&args, "--fw-variant") {
+            fw = fw.with_variant(variant.parse::<FrankWolfeVariant>().expect("Неверное значение --fw-variant"));
+        }
+        if let Some(iterations) = arg_value(&args, "--fw-iterations") {
+            fw = fw.with_max_iterations(iterations.parse::<i32>().expect("Неверное значение --fw-iterations"));
+        }
+        if let Some(gap) = arg_value(&args, "--fw-gap") {
+            fw = fw.with_tolerance(gap.parse::<f64>().expect("Неверное значение --fw-gap"));
+        }
+        let assignment = assign_link_based(graph_adj, &commodities, cost_model, &fw);
+        print!("{}", link_utilization(graph_adj, assignment.get_edge_flows()));
+        print!("{}", assignment.get_convergence());
+        println!("unrouted demands: {}", assignment.get_unrouted().len());
+        if let Some(path) = arg_value(&args, "--history") {
+            assignment.get_convergence().write_history(&path);
+        }
+        println!("Elapsed time: {:?}", start.elapsed());
+        return;
+    }
 
-        if delay_value_t_1 <= (1f64 + epsilon) * LB {
-            break;
+    // распределение по кустам (Algorithm B): --bush, --bush-iterations <число итераций>, --bush-gap <относительный зазор>,
+    // --bush-inner <проходов переноса по кусту за итерацию>,
+    // --history <файл истории сходимости>
+    if args.iter().any(|a| a == "--bush") {
+        let assignment = assign_bush_based(graph_adj, &commodities, &LinkCost::new(graph_adj, cost_model), &bush_settings(&args));
+        print!("{}", link_utilization(graph_adj, assignment.get_edge_flows()));
+        print!("{}", assignment);
+        println!("unrouted demands: {}", assignment.get_unrouted().len());
+        if let Some(path) = arg_value(&args, "--history") {
+            assignment.write_history(&path);
         }
-        delay_value_t = delay_value_t_1;
+        println!("Elapsed time: {:?}", start.elapsed());
+        return;
+    }
+
+    // теплый старт из сохраненного решения: --warm-start <файл>, сохранение решения: --save-solution <файл>
+    let warm_start = arg_value(&args, "--warm-start")
+        .map(|path| WarmStart::from_solution(graph_adj, &commodities, &FlowSolution::read(&path)));
+    // сохранение состояния: --checkpoint-interval <число итераций> [--checkpoint <файл>], продолжение расчета: --resume <файл>
+    let checkpoint = arg_value(&args, "--checkpoint-interval").map(|interval| CheckpointSettings::new(
+        arg_value(&args, "--checkpoint").unwrap_or("projection.checkpoint".to_string()),
+        interval.parse::<i32>().expect("Неверное значение --checkpoint-interval")
+    ));
+    let resume = arg_value(&args, "--resume").map(|path| ProjectionCheckpoint::read(&path));
+    let result = match (&warm_start, &checkpoint, &resume) {
+        (None, None, None) => solve_projection(graph_adj, &commodities, &settings),
+        (Some(warm_start), None, None) => solve_projection_warm(graph_adj, &commodities, &settings, warm_start),
+        _ => solve_projection_checkpointed(
+            graph_adj,
+            &commodities,
+            &settings,
+            &warm_start.clone().unwrap_or_default(),
+            &checkpoint.clone().unwrap_or_else(|| CheckpointSettings::new(String::new(), 0)),
+            resume.as_ref()
+        )
+    };
+    if let Some(path) = arg_value(&args, "--save-solution") {
+        result.get_solution().write(&path);
+    }
 
+    // один путь на commodity: --unsplittable <число попыток округления>
+    if let Some(trials) = arg_value(&args, "--unsplittable") {
+        let trials = trials.parse::<i32>().expect("Неверное значение --unsplittable");
+        let seed = arg_value(&args, "--seed").map_or(1, |s| s.parse::<u64>().expect("Неверное значение --seed"));
+        let routing = route_unsplittable(graph_adj, &commodities, &result, cost_model, trials, seed);
+        print!("{}", routing);
+        println!("Elapsed time: {:?}", start.elapsed());
+        return;
     }
 
+    println!("{}", result.get_initial_delay());
+
     let duration = start.elapsed();
 
-    for (key, value) in result_x {
-        let d = commodities.get(key as usize).unwrap();
-        print!("source - {}, target - {} -> paths: ", d.get_source(), d.get_target());
-        for (k, v) in value {
-            print!("{} - flow: {} ", k, v.to_string());
+    print!("{}", result.get_solution());
+    if warm_start.is_some() || checkpoint.is_some() || resume.is_some() || path_step.is_some() || max_iterations.is_some() {
+        println!("iterations: {}, delay: {}, lower bound: {}", result.get_iterations(), result.get_delay(), result.get_lower_bound());
+    }
+    if cost_model != (CostModel::Kleinrock { p }) {
+        let utilization = link_utilization(graph_adj, result.get_edge_flows());
+        print!("{}", utilization);
+        let phi = utilization.fortz_thorup_cost();
+        println!("Phi: {:.2}, Phi*: {:.4}", phi, phi / phi_uncap(graph_adj, &commodities));
+    }
+
+    println!("Elapsed time: {:?}", duration);
+}
+
+/// Равновесие пользователей на сети TNTP: по умолчанию Algorithm B, с --frank-wolfe <шаг> - метод Франка-Вульфа
+/// по дугам (транзит через зоны при этом не запрещается). Потоки записываются в --save-flows <файл>
+fn run_tntp(args: &[String], net_path: &str, trips_path: &str) {
+    let network = read_tntp(net_path, trips_path);
+    let graph = network.get_graph();
+    let edge_flows = match arg_value(args, "--frank-wolfe") {
+        Some(step) => {
+            let mut fw = FrankWolfeSettings::new(step.parse::<StepRule>().expect("Неверное значение --frank-wolfe"));
+            if let Some(variant) = arg_value(args, "--fw-variant") {
+                fw = fw.with_variant(variant.parse::<FrankWolfeVariant>().expect("Неверное значение --fw-variant"));
+            }
+            if let Some(iterations) = arg_value(args, "--fw-iterations") {
+                fw = fw.with_max_iterations(iterations.parse::<i32>().expect("Неверное значение --fw-iterations"));
+            }
+            if let Some(gap) = arg_value(args, "--fw-gap") {
+                fw = fw.with_tolerance(gap.parse::<f64>().expect("Неверное значение --fw-gap"));
+            }
+            let assignment = assign_link_based_with(graph, network.get_commodities(), network.get_costs(), &fw);
+            print!("{}", assignment.get_convergence());
+            if let Some(path) = arg_value(args, "--history") {
+                assignment.get_convergence().write_history(&path);
+            }
+            assignment.get_edge_flows().to_vec()
+        }
+        None => {
+            let settings = bush_settings(args).with_first_thru_node(network.get_first_thru_node());
+            let assignment = assign_bush_based(graph, network.get_commodities(), network.get_costs(), &settings);
+            print!("{}", assignment);
+            if let Some(path) = arg_value(args, "--history") {
+                assignment.write_history(&path);
+            }
+            assignment.get_edge_flows().to_vec()
         }
-        println!()
+    };
+    if let Some(path) = arg_value(args, "--save-flows") {
+        network.write_flows(&edge_flows, &path);
     }
+}
 
+fn bush_settings(args: &[String]) -> BushSettings {
+    let mut settings = BushSettings::new();
+    if let Some(iterations) = arg_value(args, "--bush-iterations") {
+        settings = settings.with_max_iterations(iterations.parse::<i32>().expect("Неверное значение --bush-iterations"));
+    }
+    if let Some(gap) = arg_value(args, "--bush-gap") {
+        settings = settings.with_tolerance(gap.parse::<f64>().expect("Неверное значение --bush-gap"));
+    }
+    if let Some(inner) = arg_value(args, "--bush-inner") {
+        settings = settings.with_inner_iterations(inner.parse::<i32>().expect("Неверное значение --bush-inner"));
+    }
+    settings
+}
 
-    println!("Elapsed time: {:?}", duration);
+/// изменения сети без правки XML: --remove-link <id линков через запятую>, --disable-node <id вершин через запятую>,
+/// --set-capacity <линк>=<пропускная способность>[,...], --add-arc <source> <target> <стоимость> <пропускная способность>
+fn apply_what_if(args: &[String], graph_adj: &mut EdgeWeightedDigraph) {
+    let mut changed = false;
+    if let Some(links) = arg_value(args, "--remove-link") {
+        for link in links.split(',') {
+            let edges = graph_adj.get_link_edges(link);
+            if edges.is_empty() {
+                panic!("Линк {} не найден", link);
+            }
+            for e in edges {
+                graph_adj.remove_edge_by_id(e.get_id());
+            }
+        }
+        changed = true;
+    }
+    if let Some(nodes) = arg_value(args, "--disable-node") {
+        for node in nodes.split(',') {
+            let v = graph_adj.get_node_index(node).unwrap_or_else(|| panic!("Вершина {} не найдена", node));
+            graph_adj.disable_node(v);
+        }
+        changed = true;
+    }
+    if let Some(capacities) = arg_value(args, "--set-capacity") {
+        for item in capacities.split(',') {
+            let (link, capacity) = item.split_once('=').unwrap_or_else(|| panic!("Неверное значение --set-capacity: {}", item));
+            let capacity = capacity.parse::<f64>().expect("Неверная пропускная способность в --set-capacity");
+            let edges = graph_adj.get_link_edges(link);
+            if edges.is_empty() {
+                panic!("Линк {} не найден", link);
+            }
+            for e in edges {
+                graph_adj.set_capacity_by_id(e.get_id(), capacity);
+            }
+        }
+        changed = true;
+    }
+    if let Some(i) = args.iter().position(|a| a == "--add-arc") {
+        let usage = "Использование: --add-arc <source> <target> <стоимость> <пропускная способность>";
+        let values: Vec<&String> = args.iter().skip(i + 1).take(4).collect();
+        if values.len() < 4 {
+            panic!("{}", usage);
+        }
+        let node = |id: &str| graph_adj.get_node_index(id).unwrap_or_else(|| panic!("Вершина {} не найдена", id));
+        let (v, w) = (node(values[0]), node(values[1]));
+        let cost = values[2].parse::<f64>().expect(usage);
+        let capacity = values[3].parse::<f64>().expect(usage);
+        graph_adj.add_edge(v, w, cost, capacity);
+        changed = true;
+    }
+    if changed {
+        println!("вершин в работе: {} из {}, дуг в работе: {} из {}", graph_adj.get_enabled_v_count(), graph_adj.get_v_count(),
+                 graph_adj.get_enabled_e_count(), graph_adj.get_e_count());
+    }
+}
+
+/// значение параметра командной строки вида --flag value
+fn arg_value(args: &[String], flag: &str) -> Option<String> {
+    args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1)).cloned()
+}
+
+/// время построения кратчайших путей из набора источников на списках Bag и на CSR, очередь Дейкстры - куча арности arity
+fn bench_csr(path: &str, arity: usize) {
+    let lines = read_file_test_sedgewick(path.to_string());
+    let mut bag_graph = EdgeWeightedDigraph::default_graph();
+    bag_graph.graph_from_array_str(lines.clone());
+    let mut csr_graph = EdgeWeightedDigraph::default_graph();
+    csr_graph.graph_from_array_str(lines).compact();
+
+    let v_count = bag_graph.get_v_count();
+    if v_count == 0 {
+        println!("Граф {} пуст", path);
+        return;
+    }
+    let sources_count = i32::min(v_count, 100);
+    let step = v_count / sources_count;
+    for (name, graph) in [("Bag", &mut bag_graph), ("CSR", &mut csr_graph)] {
+        let start = Instant::now();
+        let mut checksum = 0f64;
+        for s in (0..sources_count).map(|i| i * step) {
+            let sp = DijkstraSP::dijkstra_with_arity(graph, s, arity);
+            checksum += (0..v_count as usize).filter(|&v| sp.has_path_to(v)).map(|v| sp.dist_to(v)).sum::<f64>();
+        }
+        println!("{}: {} источников за {:?}, сумма расстояний {:.6}", name, sources_count, start.elapsed(), checksum);
+    }
+}
+
+/// Загруженная задача на графе из data: емкость дуги обратна ее длине, тогда при малой загрузке задержка Клейнрока
+/// x / (c - x) близка к x * длина. Demand между случайными парами вершин масштабируются так, чтобы при распределении
+/// "все или ничего" по кратчайшим путям наибольшая загрузка дуги была 1
+fn congested_instance(path: &str, seed: u64) -> (EdgeWeightedDigraph, Vec<Demand>) {
+    let mut graph = EdgeWeightedDigraph::default_graph();
+    graph.graph_from_array_str(read_file_test_sedgewick(path.to_string())).compact();
+    for e in graph.edges() {
+        graph.set_capacity_by_id(e.get_id(), 1f64 / f64::max(e.get_cost(), 1e-6));
+    }
+    let v_count = graph.get_v_count();
+    let mut rng = XorShiftRng::new(seed);
+    let sources: Vec<i32> = (0..i32::min(v_count, 20)).map(|i| i * (v_count / i32::min(v_count, 20))).collect();
+    let mut commodities = vec![];
+    for &s in &sources {
+        for _ in 0..20 {
+            let t = rng.gen_range(0, v_count - 1);
+            if t != s {
+                commodities.push(Demand::new(format!("{}_{}", s, t), s.to_string(), t.to_string(), 0.5 + rng.next_f64()));
+            }
+        }
+    }
+    let cost_model = CostModel::Kleinrock { p: 0.99 };
+    let objective = LinkCost::new(&graph, cost_model);
+    let mut oracle = FlowPolytope::new(&graph, &commodities);
+    let x = oracle.minimize(&objective.gradient(&Array1::zeros(frank_wolf::edge_vector_len(&graph))));
+    let utilization = graph.edges().iter().map(|e| x[e.get_id() as usize] / e.get_capacity()).fold(0f64, f64::max);
+    let scale = if utilization > 0f64 { 1f64 / utilization } else { 1f64 };
+    let commodities = commodities.iter()
+        .map(|c| Demand::new(c.get_id(), c.get_source(), c.get_target(), c.get_demand_vale() * scale))
+        .collect();
+    (graph, commodities)
+}
+
+/// число итераций и время вариантов метода Франка-Вульфа до относительного зазора 1e-4
+fn bench_frank_wolfe(path: &str, iterations: i32) {
+    let (graph, commodities) = congested_instance(path, 1);
+    let cost_model = CostModel::Kleinrock { p: 0.99 };
+    let variants = [
+        FrankWolfeVariant::Classic,
+        FrankWolfeVariant::AwayStep,
+        FrankWolfeVariant::Pairwise,
+        FrankWolfeVariant::Conjugate,
+        FrankWolfeVariant::BiConjugate
+    ];
+    for variant in variants {
+        let settings = FrankWolfeSettings::new(StepRule::LineSearch).with_variant(variant).with_max_iterations(iterations);
+        let start = Instant::now();
+        let assignment = assign_link_based(&graph, &commodities, cost_model, &settings);
+        print!("{:?}: {:?}, {}", variant, start.elapsed(), assignment.get_convergence());
+    }
 }
 
 fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
@@ -0,0 +1,426 @@
+use std::collections::VecDeque;
+
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+
+/// Минимальный разрез: вершины на стороне источника и дуги, идущие из этой стороны в другую
+#[derive(Debug, Clone)]
+pub struct MinCut {
+    source_side: Vec<bool>,
+    edges: Vec<DirectedEdge>,
+    capacity: f64
+}
+
+impl MinCut {
+
+    pub fn is_source_side(&self, v: i32) -> bool {
+        self.source_side[v as usize]
+    }
+
+    pub fn get_source_side(&self) -> Vec<i32> {
+        (0..self.source_side.len() as i32).filter(|&v| self.source_side[v as usize]).collect()
+    }
+
+    pub fn get_edges(&self) -> &[DirectedEdge] {
+        &self.edges
+    }
+
+    pub fn get_capacity(&self) -> f64 {
+        self.capacity
+    }
+}
+
+/// Максимальный поток между парой вершин
+#[derive(Debug, Clone)]
+pub struct MaxFlowResult {
+    value: f64,
+    edge_flows: Vec<(DirectedEdge, f64)>,
+    min_cut: MinCut
+}
+
+impl MaxFlowResult {
+
+    pub fn get_value(&self) -> f64 {
+        self.value
+    }
+
+    /// поток на каждой работающей дуге графа
+    pub fn get_edge_flows(&self) -> &[(DirectedEdge, f64)] {
+        &self.edge_flows
+    }
+
+    pub fn get_min_cut(&self) -> &MinCut {
+        &self.min_cut
+    }
+}
+
+/// Остаточная сеть: каждой дуге графа соответствует пара прямая/обратная дуга с номерами 2k и 2k + 1
+struct FlowNetwork {
+    n: usize,
+    adj: Vec<Vec<usize>>,
+    to: Vec<usize>,
+    cap: Vec<f64>,
+    flow: Vec<f64>,
+    edges: Vec<DirectedEdge>,
+    eps: f64
+}
+
+impl FlowNetwork {
+
+    fn from_graph(graph: &EdgeWeightedDigraph) -> FlowNetwork {
+        let n = graph.get_v_count() as usize;
+        let mut network = FlowNetwork {
+            n,
+            adj: vec![vec![]; n],
+            to: vec![],
+            cap: vec![],
+            flow: vec![],
+            edges: vec![],
+            eps: 0f64
+        };
+        for v in 0..n {
+            for e in graph.edge_list(v) {
+                let k = network.to.len();
+                network.adj[v].push(k);
+                network.to.push(e.to() as usize);
+                network.cap.push(e.get_capacity());
+                network.adj[e.to() as usize].push(k + 1);
+                network.to.push(v);
+                network.cap.push(0f64);
+                network.edges.push(e);
+            }
+        }
+        network.flow = vec![0f64; network.to.len()];
+        let max_cap = network.cap.iter().cloned().fold(0f64, f64::max);
+        network.eps = 1e-12 * f64::max(1f64, max_cap);
+        network
+    }
+
+    fn residual(&self, k: usize) -> f64 {
+        self.cap[k] - self.flow[k]
+    }
+
+    fn push(&mut self, k: usize, amount: f64) {
+        self.flow[k] += amount;
+        self.flow[k ^ 1] -= amount;
+    }
+
+    fn result(&self, s: usize, value: f64) -> MaxFlowResult {
+        // сторона источника - вершины, достижимые из s по остаточной сети
+        let mut source_side = vec![false; self.n];
+        let mut queue = VecDeque::new();
+        source_side[s] = true;
+        queue.push_back(s);
+        while let Some(v) = queue.pop_front() {
+            for &k in &self.adj[v] {
+                let w = self.to[k];
+                if !source_side[w] && self.residual(k) > self.eps {
+                    source_side[w] = true;
+                    queue.push_back(w);
+                }
+            }
+        }
+        let cut_edges: Vec<DirectedEdge> = self.edges.iter()
+            .filter(|e| source_side[e.from() as usize] && !source_side[e.to() as usize])
+            .copied()
+            .collect();
+        MaxFlowResult {
+            value,
+            edge_flows: self.edges.iter().enumerate().map(|(i, e)| (*e, f64::max(0f64, self.flow[2 * i]))).collect(),
+            min_cut: MinCut {
+                capacity: cut_edges.iter().map(|e| e.get_capacity()).sum(),
+                source_side,
+                edges: cut_edges
+            }
+        }
+    }
+}
+
+/// Максимальный поток алгоритмом Диница, пропускные способности - поле capacity дуг
+pub fn dinic(graph: &EdgeWeightedDigraph, s: i32, t: i32) -> MaxFlowResult {
+    let mut network = FlowNetwork::from_graph(graph);
+    let (s, t) = (s as usize, t as usize);
+    let mut value = 0f64;
+    if s == t {
+        return network.result(s, value);
+    }
+    let mut level = vec![-1i32; network.n];
+    let mut next = vec![0usize; network.n];
+    while dinic_bfs(&network, s, t, &mut level) {
+        next.iter_mut().for_each(|i| *i = 0);
+        loop {
+            let pushed = dinic_dfs(&mut network, s, t, f64::INFINITY, &level, &mut next);
+            if pushed <= network.eps {
+                break;
+            }
+            value += pushed;
+        }
+    }
+    network.result(s, value)
+}
+
+fn dinic_bfs(network: &FlowNetwork, s: usize, t: usize, level: &mut [i32]) -> bool {
+    level.iter_mut().for_each(|l| *l = -1);
+    level[s] = 0;
+    let mut queue = VecDeque::new();
+    queue.push_back(s);
+    while let Some(v) = queue.pop_front() {
+        for &k in &network.adj[v] {
+            let w = network.to[k];
+            if level[w] < 0 && network.residual(k) > network.eps {
+                level[w] = level[v] + 1;
+                queue.push_back(w);
+            }
+        }
+    }
+    level[t] >= 0
+}
+
+fn dinic_dfs(network: &mut FlowNetwork, v: usize, t: usize, limit: f64, level: &[i32], next: &mut [usize]) -> f64 {
+    if v == t {
+        return limit;
+    }
+    while next[v] < network.adj[v].len() {
+        let k = network.adj[v][next[v]];
+        let w = network.to[k];
+        let residual = network.residual(k);
+        if residual > network.eps && level[w] == level[v] + 1 {
+            let pushed = dinic_dfs(network, w, t, f64::min(limit, residual), level, next);
+            if pushed > network.eps {
+                network.push(k, pushed);
+                return pushed;
+            }
+        }
+        next[v] += 1;
+    }
+    0f64
+}
+
+/// Максимальный поток алгоритмом проталкивания предпотока (FIFO, с эвристикой разрыва)
+pub fn push_relabel(graph: &EdgeWeightedDigraph, s: i32, t: i32) -> MaxFlowResult {
+    let mut network = FlowNetwork::from_graph(graph);
+    let (s, t) = (s as usize, t as usize);
+    let n = network.n;
+    if s == t {
+        return network.result(s, 0f64);
+    }
+    let mut height = vec![0usize; n];
+    let mut excess = vec![0f64; n];
+    let mut count = vec![0usize; 2 * n + 1]; // количество вершин на каждой высоте
+    let mut active = VecDeque::new();
+    let mut in_queue = vec![false; n];
+
+    height[s] = n;
+    count[0] = n - 1;
+    count[n] = 1;
+    for i in 0..network.adj[s].len() {
+        let k = network.adj[s][i];
+        let amount = network.residual(k);
+        if amount > network.eps {
+            let w = network.to[k];
+            network.push(k, amount);
+            excess[w] += amount;
+            excess[s] -= amount;
+            if w != t && !in_queue[w] {
+                in_queue[w] = true;
+                active.push_back(w);
+            }
+        }
+    }
+
+    while let Some(v) = active.pop_front() {
+        in_queue[v] = false;
+        // разгрузка вершины v
+        while excess[v] > network.eps {
+            let mut pushed = false;
+            for i in 0..network.adj[v].len() {
+                let k = network.adj[v][i];
+                let w = network.to[k];
+                let residual = network.residual(k);
+                if residual > network.eps && height[v] == height[w] + 1 {
+                    let amount = f64::min(excess[v], residual);
+                    network.push(k, amount);
+                    excess[v] -= amount;
+                    excess[w] += amount;
+                    if w != s && w != t && !in_queue[w] {
+                        in_queue[w] = true;
+                        active.push_back(w);
+                    }
+                    pushed = true;
+                    if excess[v] <= network.eps {
+                        break;
+                    }
+                }
+            }
+            if excess[v] <= network.eps || pushed {
+                continue;
+            }
+            // подъем вершины
+            let old = height[v];
+            let new_height = network.adj[v].iter()
+                .filter(|&&k| network.residual(k) > network.eps)
+                .map(|&k| height[network.to[k]] + 1)
+                .min()
+                .unwrap_or(2 * n);
+            count[old] -= 1;
+            height[v] = usize::min(new_height, 2 * n);
+            count[height[v]] += 1;
+            // эвристика разрыва: если высота old опустела, вершины выше нее не достигают стока
+            if count[old] == 0 && old < n {
+                for u in 0..n {
+                    if height[u] > old && height[u] < n && u != s {
+                        count[height[u]] -= 1;
+                        height[u] = n + 1;
+                        count[height[u]] += 1;
+                    }
+                }
+            }
+        }
+    }
+    let value = excess[t];
+    network.result(s, value)
+}
+
+/// Дерево Гомори-Ху (алгоритм Гасфилда) для графа с симметричными пропускными способностями:
+/// минимальный разрез между u и v равен минимальному весу ребра на пути между ними в дереве
+#[derive(Debug, Clone)]
+pub struct GomoryHuTree {
+    parent: Vec<i32>,
+    weight: Vec<f64>
+}
+
+impl GomoryHuTree {
+
+    pub fn build(graph: &EdgeWeightedDigraph) -> GomoryHuTree {
+        let n = graph.get_v_count() as usize;
+        let mut tree = GomoryHuTree {
+            parent: vec![0; n],
+            weight: vec![0f64; n]
+        };
+        for s in 1..n {
+            let t = tree.parent[s] as usize;
+            let flow = dinic(graph, s as i32, t as i32);
+            let cut = flow.get_min_cut();
+            tree.weight[s] = flow.get_value();
+            for i in 0..n {
+                if i != s && cut.is_source_side(i as i32) && tree.parent[i] as usize == t {
+                    tree.parent[i] = s as i32;
+                }
+            }
+            let pt = tree.parent[t] as usize;
+            if t != 0 && cut.is_source_side(pt as i32) {
+                tree.parent[s] = pt as i32;
+                tree.parent[t] = s as i32;
+                tree.weight[s] = tree.weight[t];
+                tree.weight[t] = flow.get_value();
+            }
+        }
+        tree
+    }
+
+    /// ребра дерева: (вершина, родитель, величина разреза), корень - вершина 0
+    pub fn get_edges(&self) -> Vec<(i32, i32, f64)> {
+        (1..self.parent.len()).map(|v| (v as i32, self.parent[v], self.weight[v])).collect()
+    }
+
+    pub fn min_cut_value(&self, u: i32, v: i32) -> f64 {
+        if u == v {
+            return f64::INFINITY;
+        }
+        // поднимаемся от u к корню, запоминая минимальный вес до каждой вершины
+        let n = self.parent.len();
+        let mut min_to = vec![f64::NAN; n];
+        let mut x = u as usize;
+        let mut current = f64::INFINITY;
+        min_to[x] = current;
+        while x != 0 {
+            current = f64::min(current, self.weight[x]);
+            x = self.parent[x] as usize;
+            min_to[x] = current;
+        }
+        let mut y = v as usize;
+        let mut from_v = f64::INFINITY;
+        while min_to[y].is_nan() {
+            from_v = f64::min(from_v, self.weight[y]);
+            y = self.parent[y] as usize;
+        }
+        f64::min(from_v, min_to[y])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// граф в формате Седжвика из каталога data, вес дуги берется как ее пропускная способность;
+    /// symmetric добавляет встречную дугу той же пропускной способности
+    fn sedgewick(path: &str, symmetric: bool) -> EdgeWeightedDigraph {
+        let text = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        let n = lines[0].trim().parse::<usize>().unwrap();
+        let mut arcs = vec![];
+        for line in lines[2..].iter().filter(|l| !l.trim().is_empty()) {
+            let arr: Vec<&str> = line.split_whitespace().collect();
+            let (v, w, c) = (arr[0].parse::<i32>().unwrap(), arr[1].parse::<i32>().unwrap(), arr[2].parse::<f64>().unwrap());
+            arcs.push((v, w, 1f64, c));
+            if symmetric {
+                arcs.push((w, v, 1f64, c));
+            }
+        }
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs((0..n).map(|v| v.to_string()).collect(), &arcs);
+        graph
+    }
+
+    /// поток допустим: не превышает пропускных способностей и сохраняется во всех вершинах, кроме s и t
+    fn assert_feasible(graph: &EdgeWeightedDigraph, flow: &MaxFlowResult, s: i32, t: i32) {
+        let mut balance = vec![0f64; graph.get_v_count() as usize];
+        for (e, x) in flow.get_edge_flows() {
+            assert!(*x >= -1e-9 && *x <= e.get_capacity() + 1e-9);
+            balance[e.from() as usize] -= x;
+            balance[e.to() as usize] += x;
+        }
+        for (v, b) in balance.iter().enumerate() {
+            if v as i32 != s && v as i32 != t {
+                assert!(b.abs() < 1e-9, "вершина {}: {}", v, b);
+            }
+        }
+        assert!((balance[t as usize] - flow.get_value()).abs() < 1e-9);
+        let cut = flow.get_min_cut();
+        assert!((cut.get_capacity() - flow.get_value()).abs() < 1e-9);
+        assert!(cut.is_source_side(s) && !cut.is_source_side(t));
+    }
+
+    #[test]
+    fn push_relabel_matches_dinic_on_data_graphs() {
+        for path in ["data/test_small.txt", "data/test_medium.txt", "data/test_big.txt"] {
+            let graph = sedgewick(path, false);
+            let n = graph.get_v_count();
+            for (s, t) in [(0, n - 1), (1, n / 2), (n / 3, 2), (5, 5)] {
+                let expected = dinic(&graph, s, t);
+                let actual = push_relabel(&graph, s, t);
+                assert!((expected.get_value() - actual.get_value()).abs() < 1e-9,
+                        "{}: {} -> {}: dinic {}, push-relabel {}", path, s, t, expected.get_value(), actual.get_value());
+                if s != t {
+                    assert_feasible(&graph, &expected, s, t);
+                    assert_feasible(&graph, &actual, s, t);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gomory_hu_tree_gives_pairwise_min_cuts() {
+        let graph = sedgewick("data/test_small.txt", true);
+        let tree = GomoryHuTree::build(&graph);
+        let n = graph.get_v_count();
+        for u in 0..n {
+            assert_eq!(tree.min_cut_value(u, u), f64::INFINITY);
+            for v in 0..n {
+                if u != v {
+                    let value = dinic(&graph, u, v).get_value();
+                    assert!((tree.min_cut_value(u, v) - value).abs() < 1e-9, "{} - {}", u, v);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::graph::EdgeWeightedDigraph;
+use crate::projection_solver::{solve_projection, ProjectionSettings};
+use crate::structure_xml::{AddModule, Demand, LinkModel, Network};
+
+/// Установленные на линк модули и его загрузка
+#[derive(Debug, Clone)]
+pub struct LinkDesign {
+    link_id: String,
+    base_capacity: f64,  // емкость preInstalledModule
+    modules: Vec<AddModule>,
+    counts: Vec<i32>,    // сколько модулей каждого типа установлено
+    load: f64,
+    utilization: f64
+}
+
+impl LinkDesign {
+
+    pub fn installed_capacity(&self) -> f64 {
+        self.base_capacity + self.modules.iter().zip(&self.counts).map(|(m, &n)| m.get_capacity() * n as f64).sum::<f64>()
+    }
+
+    pub fn installed_cost(&self) -> f64 {
+        self.modules.iter().zip(&self.counts).map(|(m, &n)| m.get_cost() * n as f64).sum()
+    }
+
+    /// емкость, с которой линк участвует в распределении потоков: у линка без емкости - емкость самого дешевого модуля,
+    /// иначе поток никогда не пойдет по линку, на который модуль еще не поставлен
+    fn routing_capacity(&self) -> f64 {
+        let installed = self.installed_capacity();
+        if installed > 0f64 {
+            return installed;
+        }
+        self.cheapest_module().map_or(0f64, |m| self.modules[m].get_capacity())
+    }
+
+    fn cheapest_module(&self) -> Option<usize> {
+        (0..self.modules.len())
+            .min_by(|&a, &b| self.modules[a].get_cost().total_cmp(&self.modules[b].get_cost()))
+    }
+
+    fn set_load(&mut self, load: f64) {
+        let capacity = self.installed_capacity();
+        self.load = load;
+        self.utilization = if load <= 0f64 {
+            0f64
+        } else if capacity > 0f64 {
+            load / capacity
+        } else {
+            f64::INFINITY
+        };
+    }
+
+    /// добавляет модули, чтобы загрузка линка стала не больше target, выбирается самый дешевый вариант из модулей одного типа
+    fn upgrade(&mut self, target: f64) -> bool {
+        let extra = self.load / target - self.installed_capacity();
+        let best = (0..self.modules.len())
+            .filter(|&m| self.modules[m].get_capacity() > 0f64)
+            .map(|m| (m, (extra / self.modules[m].get_capacity()).ceil().max(1f64)))
+            .min_by(|a, b| (self.modules[a.0].get_cost() * a.1).total_cmp(&(self.modules[b.0].get_cost() * b.1)));
+        match best {
+            Some((m, n)) => {
+                self.counts[m] += n as i32;
+                true
+            }
+            None => false
+        }
+    }
+}
+
+/// Результат планирования емкостей сети
+#[derive(Debug, Clone)]
+pub struct NetworkDesign {
+    links: Vec<LinkDesign>,
+    target_utilization: f64,
+    unrouted: usize, // количество commodity, которые не удалось провести по сети
+    feasible: bool
+}
+
+impl NetworkDesign {
+
+    pub fn total_cost(&self) -> f64 {
+        self.links.iter().map(|l| l.installed_cost()).sum()
+    }
+
+    pub fn max_utilization(&self) -> f64 {
+        self.links.iter().map(|l| l.utilization).fold(0f64, f64::max)
+    }
+
+    fn satisfies(&self) -> bool {
+        self.unrouted == 0 && self.max_utilization() <= self.target_utilization
+    }
+}
+
+impl fmt::Display for NetworkDesign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for link in &self.links {
+            let modules = link.modules.iter().zip(&link.counts)
+                .filter(|(_, &n)| n > 0)
+                .map(|(m, n)| format!("{}x{}", n, m.get_capacity()))
+                .collect::<Vec<String>>()
+                .join(" + ");
+            writeln!(f, "link - {}, modules: [{}], capacity: {:.2}, cost: {:.2}, load: {:.2}, utilization: {:.4}",
+                     link.link_id, modules, link.installed_capacity(), link.installed_cost(), link.load, link.utilization)?;
+        }
+        writeln!(f, "total cost: {:.2}, max utilization: {:.4} (target {:.4}), unrouted demands: {}, feasible: {}",
+                 self.total_cost(), self.max_utilization(), self.target_utilization, self.unrouted, self.feasible)
+    }
+}
+
+/// Планирование емкостей по additionalModules SNDlib: жадно добавляем модули на перегруженные линки,
+/// пока загрузка всех линков не станет не больше target_utilization, затем локальным поиском снимаем лишние модули
+pub fn design_network(
+    network: &Network,
+    graph_adj: &EdgeWeightedDigraph,
+    commodities: &[Demand],
+    settings: &ProjectionSettings,
+    target_utilization: f64,
+    max_rounds: i32
+) -> NetworkDesign {
+    let links = network.get_network_structure().get_links().get_vec_link();
+    let mut design = NetworkDesign {
+        links: links.iter().map(|link| {
+            let modules = link.get_additional_modules();
+            LinkDesign {
+                link_id: link.get_id(),
+                base_capacity: link.get_capacity(),
+                counts: vec![0; modules.len()],
+                modules,
+                load: 0f64,
+                utilization: 0f64
+            }
+        }).collect(),
+        target_utilization,
+        unrouted: 0,
+        feasible: false
+    };
+
+    // жадное добавление модулей
+    for _ in 0..max_rounds {
+        route(&mut design, graph_adj, commodities, settings);
+        let mut upgraded = false;
+        for link in design.links.iter_mut().filter(|l| l.utilization > target_utilization) {
+            upgraded |= link.upgrade(target_utilization);
+        }
+        if !upgraded {
+            break;
+        }
+    }
+    route(&mut design, graph_adj, commodities, settings);
+    design.feasible = design.satisfies();
+    if !design.feasible {
+        return design;
+    }
+
+    // локальный поиск: снимаем по одному модулю, начиная с самых дорогих, пока решение остается допустимым
+    let mut candidates = vec![];
+    for (l, link) in design.links.iter().enumerate() {
+        for (m, &n) in link.counts.iter().enumerate() {
+            if n > 0 {
+                candidates.push((l, m));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| design.links[b.0].modules[b.1].get_cost().total_cmp(&design.links[a.0].modules[a.1].get_cost()));
+    for (l, m) in candidates {
+        while design.links[l].counts[m] > 0 {
+            let previous = design.clone();
+            design.links[l].counts[m] -= 1;
+            route(&mut design, graph_adj, commodities, settings);
+            if !design.satisfies() {
+                design = previous;
+                break;
+            }
+        }
+    }
+    design
+}
+
+/// распределяет потоки при текущих емкостях и записывает загрузку линков
+fn route(design: &mut NetworkDesign, graph_adj: &EdgeWeightedDigraph, commodities: &[Demand], settings: &ProjectionSettings) {
+    let mut graph = graph_adj.clone();
+    for link in &design.links {
+        let capacity = link.routing_capacity();
+        for e in graph_adj.get_link_edges(&link.link_id) {
+            graph.set_capacity_by_id(e.get_id(), capacity);
+            graph.set_edge_enabled_by_id(e.get_id(), capacity > 0f64);
+        }
+    }
+    let result = solve_projection(&mut graph, commodities, settings);
+    design.unrouted = result.get_unrouted().len();
+
+    // для UNDIRECTED потоки обоих направлений складываются, иначе загрузка линка - загрузка самой нагруженной дуги
+    let shared = graph_adj.get_link_model() == LinkModel::Undirected;
+    let mut loads: HashMap<String, f64> = HashMap::new();
+    for (edge, flow) in result.get_edge_flows() {
+        if let Some(link) = graph_adj.get_link_id(edge) {
+            let load = loads.entry(link.to_string()).or_insert(0f64);
+            *load = if shared { *load + flow } else { f64::max(*load, *flow) };
+        }
+    }
+    for link in design.links.iter_mut() {
+        let load = loads.get(&link.link_id).copied().unwrap_or(0f64);
+        link.set_load(load);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dijkstra_find_path::TieBreak;
+
+    #[test]
+    fn single_cheapest_module_meets_target_utilization() {
+        // L1: загрузка 9 при емкости 10 выше целевой 0.8, хватает одного модуля на 5; L2 не перегружен
+        let xml = r#"<network>
+ <networkStructure>
+  <nodes coordinatesType="pixel">
+   <node id="a"><coordinates><x>0</x><y>0</y></coordinates></node>
+   <node id="b"><coordinates><x>1</x><y>0</y></coordinates></node>
+   <node id="c"><coordinates><x>2</x><y>0</y></coordinates></node>
+  </nodes>
+  <links>
+   <link id="L1"><source>a</source><target>b</target><preInstalledModule><capacity>10</capacity><cost>0</cost></preInstalledModule>
+    <additionalModules><addModule><capacity>5</capacity><cost>3</cost></addModule><addModule><capacity>40</capacity><cost>10</cost></addModule></additionalModules></link>
+   <link id="L2"><source>b</source><target>c</target><preInstalledModule><capacity>100</capacity><cost>0</cost></preInstalledModule>
+    <additionalModules><addModule><capacity>5</capacity><cost>3</cost></addModule></additionalModules></link>
+  </links>
+ </networkStructure>
+ <demands>
+  <demand id="D1"><source>a</source><target>b</target><demandValue>9</demandValue></demand>
+  <demand id="D2"><source>b</source><target>c</target><demandValue>20</demandValue></demand>
+ </demands>
+</network>"#;
+        let network: Network = serde_xml_rs::from_str(xml).unwrap();
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_struct_xml(&network, LinkModel::Undirected).compact();
+        let commodities = network.get_demands().get_demand_vec();
+        let settings = ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent);
+
+        let design = design_network(&network, &graph, &commodities, &settings, 0.8, 50);
+        assert!(design.feasible);
+        assert_eq!(design.unrouted, 0);
+        assert_eq!(design.links[0].link_id, "L1");
+        assert_eq!(design.links[0].counts, vec![1, 0]);
+        assert_eq!(design.links[1].counts, vec![0]);
+        assert!((design.links[0].installed_capacity() - 15f64).abs() < 1e-9);
+        assert!((design.links[0].utilization - 0.6).abs() < 1e-6);
+        assert!((design.total_cost() - 3f64).abs() < 1e-9);
+        assert!(design.max_utilization() <= 0.8);
+    }
+}
@@ -1,10 +1,16 @@
 use serde_xml_rs;
 use std::fs;
 
-use crate::structure_xml::Network;
+use crate::structure_xml::{Model, Network};
 
 pub fn parse_xml_to_structure(path: &str) -> Network {
     let xml_data = fs::read_to_string(path).expect("");
     let network: Network = serde_xml_rs::from_str(&xml_data).expect("Ошибка при разборе XML");
     network
-}
\ No newline at end of file
+}
+
+pub fn parse_model_xml(path: &str) -> Model {
+    let xml_data = fs::read_to_string(path).expect("");
+    let model: Model = serde_xml_rs::from_str(&xml_data).expect("Ошибка при разборе XML модели");
+    model
+}
@@ -1,28 +1,37 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::delay_func_count::{count_delay_value, count_first_derivative, count_second_derivative};
+use crate::delay_func_count::CostModel;
 use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
-use crate::utils_graph::{EdgeFlowCommodities, find_all_path};
+use crate::utils_graph::{CommodityFlows, EdgeFlowCommodities, find_all_path};
 
 pub struct ProjectionMethod {
     alpha: f64,
-    p: f64,
-    edge_commodity: HashMap<String, EdgeFlowCommodities>,
+    cost_model: CostModel,
+    edge_commodity: BTreeMap<i32, EdgeFlowCommodities>, // id дуги -> потоки commodity на ней
     path_commodity: HashMap<String, f64>,
     paths: HashMap<String, Vec<DirectedEdge>>,
+    twins: HashMap<i32, i32>, // id дуги -> id встречной дуги, с которой она делит пропускную способность
 }
 
 impl ProjectionMethod {
-    pub fn new(alpha: f64, p: f64) -> ProjectionMethod {
+    pub fn with_cost_model(alpha: f64, cost_model: CostModel) -> ProjectionMethod {
         ProjectionMethod {
             alpha,
-            p,
-            edge_commodity: HashMap::new(),
+            cost_model,
+            edge_commodity: BTreeMap::new(),
             path_commodity: HashMap::new(),
             paths: HashMap::new(),
+            twins: HashMap::new(),
         }
     }
 
+    /// для линков модели UNDIRECTED затраты считаются по суммарному потоку обоих направлений
+    pub fn set_shared_capacity(&mut self, graph_adj: &EdgeWeightedDigraph) {
+        self.twins = graph_adj.edges().iter()
+            .filter_map(|e| graph_adj.get_shared_capacity_twin(e).map(|t| (e.get_id(), t.get_id())))
+            .collect();
+    }
+
     pub fn set_first_commodity_to_graph(
         &mut self,
         r_index: i32,
@@ -30,9 +39,7 @@ impl ProjectionMethod {
         path_shortest: &Vec<DirectedEdge>,
         graph_adj: &mut EdgeWeightedDigraph) {
         for mut edge in path_shortest {
-            let to = edge.to();
-            let from = edge.from();
-            let key_edge = from.to_string() + "_" + &to.to_string();
+            let key_edge = edge.get_id();
             let check = self.edge_commodity.get_mut(&key_edge);
             match check {
                 Some(mut x) => {
@@ -40,7 +47,7 @@ impl ProjectionMethod {
                     x.update_commodity_flow_y(&r_index, flow);
                 }
                 None => {
-                    let mut commodities_flow = HashMap::new();
+                    let mut commodities_flow = BTreeMap::new();
                     commodities_flow.insert(r_index, (flow, flow));
                     let edge_flows = EdgeFlowCommodities::new(edge.to_owned(), commodities_flow);
                     self.edge_commodity.insert(key_edge, edge_flows);
@@ -48,20 +55,45 @@ impl ProjectionMethod {
             }
         }
 
-        for (_, edge_flows) in self.edge_commodity.iter_mut() {
-            let cost_total = edge_flows.get_total_flow_x();
-            let mut edge = edge_flows.get_edge_mut();
-            let cost = count_first_derivative(cost_total, edge.get_capacity(), self.p);
-            graph_adj.update_edge(edge.from(), edge.to(), cost);
-            edge.update_cost(cost);
+        self.refresh_costs(graph_adj);
+    }
+
+    /// ставит поток commodity сразу на несколько путей (теплый старт), flows - ключ пути -> поток
+    pub fn set_commodity_paths_to_graph(
+        &mut self,
+        r_index: i32,
+        flows: &BTreeMap<String, f64>,
+        paths: &BTreeMap<String, Vec<DirectedEdge>>,
+        graph_adj: &mut EdgeWeightedDigraph) {
+        let mut edge_flows: BTreeMap<i32, (DirectedEdge, f64)> = BTreeMap::new();
+        for (key, path) in paths {
+            let flow = flows.get(key).copied().unwrap_or(0f64);
+            for edge in path {
+                edge_flows.entry(edge.get_id()).or_insert((*edge, 0f64)).1 += flow;
+            }
+        }
+        for (key_edge, (edge, flow)) in edge_flows {
+            match self.edge_commodity.get_mut(&key_edge) {
+                Some(x) => {
+                    x.update_commodity_flow_x(&r_index, flow);
+                    x.update_commodity_flow_y(&r_index, flow);
+                }
+                None => {
+                    let mut commodities_flow = BTreeMap::new();
+                    commodities_flow.insert(r_index, (flow, flow));
+                    self.edge_commodity.insert(key_edge, EdgeFlowCommodities::new(edge, commodities_flow));
+                }
+            }
         }
+
+        self.refresh_costs(graph_adj);
     }
 
     pub fn update_edge_flow(&mut self,
-                            result_step: &HashMap<String, f64>,
+                            result_step: &BTreeMap<String, f64>,
                             commodity: i32,
                             graph_adj: &mut EdgeWeightedDigraph,
-                            paths: &HashMap<String, Vec<DirectedEdge>>
+                            paths: &BTreeMap<String, Vec<DirectedEdge>>
     ) {
         let mut edges_for_update = HashMap::new();
 
@@ -69,7 +101,7 @@ impl ProjectionMethod {
             for mut edge in path_vec {
 
                 let mut flow = *result_step.get(key).expect("Ошибка");
-                let key_edge = &(edge.from().to_string() + "_" + &edge.to().to_string());
+                let key_edge = &edge.get_id();
                 let check_edge = edges_for_update.get(key_edge).unwrap_or(&false);
                 let check = self.edge_commodity.get_mut(key_edge);
                 match check {
@@ -80,23 +112,63 @@ impl ProjectionMethod {
                         x.update_commodity_flow_x(&commodity, flow)
                     }
                     None => {
-                        let mut commodities = HashMap::new();
+                        let mut commodities = BTreeMap::new();
                         commodities.insert(commodity, (flow, 0f64));
                         let edge_flows = EdgeFlowCommodities::new(edge.to_owned(), commodities);
-                        self.edge_commodity.insert(key_edge.clone(), edge_flows);
+                        self.edge_commodity.insert(*key_edge, edge_flows);
                     }
                 }
-                edges_for_update.insert(key_edge.clone(), true);
+                edges_for_update.insert(*key_edge, true);
             }
         }
 
-        for (_, edge_flows) in self.edge_commodity.iter_mut() {
-            let flow = edge_flows.get_total_flow_x();
-            let mut edge = edge_flows.get_edge_mut();
-            let capacity = edge.get_capacity();
-            let cost = count_first_derivative(flow, capacity, self.p);
-            graph_adj.update_edge(edge.from(), edge.to(), cost);
-            edge.update_cost(cost);
+        self.refresh_costs(graph_adj);
+    }
+
+    /// пересчитывает стоимости дуг графа по текущим потокам x
+    fn refresh_costs(&mut self, graph_adj: &mut EdgeWeightedDigraph) {
+        let costs: Vec<(i32, f64)> = self.edge_commodity.iter()
+            .map(|(key, e)| (*key, self.cost_model.first_derivative(self.link_flow_x(*key), e.get_edge().get_capacity())))
+            .collect();
+        for (key, cost) in costs {
+            // встречная дуга без собственного потока тоже нагружена потоком линка
+            let twin = self.twins.get(&key).copied().filter(|t| !self.edge_commodity.contains_key(t));
+            graph_adj.update_edge_by_id(key, cost);
+            self.edge_commodity.get_mut(&key).unwrap().get_edge_mut().update_cost(cost);
+            if let Some(twin) = twin {
+                graph_adj.update_edge_by_id(twin, cost);
+            }
+        }
+    }
+
+    /// дугам без собственного потока ставится стоимость - производная затрат при текущем потоке линка
+    /// (в нуле или при потоке встречной дуги), чтобы стоимости всех дуг графа были градиентом задержки
+    pub fn set_idle_edge_costs(&self, graph_adj: &mut EdgeWeightedDigraph) {
+        for e in graph_adj.edges() {
+            if !self.edge_commodity.contains_key(&e.get_id()) {
+                graph_adj.update_edge_by_id(e.get_id(), self.cost_model.first_derivative(self.link_flow_x(e.get_id()), e.get_capacity()));
+            }
+        }
+    }
+
+    /// поток x линка: для общей пропускной способности - сумма потоков обоих направлений
+    fn link_flow_x(&self, key: i32) -> f64 {
+        self.sum_with_twin(key, |e| e.get_total_flow_x())
+    }
+
+    fn sum_with_twin<F: Fn(&EdgeFlowCommodities) -> f64>(&self, key: i32, f: F) -> f64 {
+        let own = self.edge_commodity.get(&key).map_or(0f64, &f);
+        match self.twins.get(&key) {
+            Some(twin) => own + self.edge_commodity.get(twin).map_or(0f64, &f),
+            None => own
+        }
+    }
+
+    /// линк с общей пропускной способностью учитывается в задержке один раз
+    fn counts_link(&self, key: i32) -> bool {
+        match self.twins.get(&key) {
+            Some(twin) => !self.edge_commodity.contains_key(twin) || key < *twin,
+            None => true
         }
     }
 
@@ -107,14 +179,14 @@ impl ProjectionMethod {
                             edges: &Vec<DirectedEdge>
     ) {
         for mut edge in edges {
-            let key_edge = edge.from().to_string() + "_" + &edge.to().to_string();
+            let key_edge = edge.get_id();
             let check = self.edge_commodity.get_mut(&key_edge);
             match check {
                 Some(mut x) => {
                     x.update_commodity_flow_y(&commodity, flow)
                 }
                 None => {
-                    let mut commodities = HashMap::new();
+                    let mut commodities = BTreeMap::new();
                     commodities.insert(commodity, (0f64, flow));
                     let edge_flows = EdgeFlowCommodities::new(edge.to_owned(), commodities);
                     self.edge_commodity.insert(key_edge, edge_flows);
@@ -135,11 +207,10 @@ impl ProjectionMethod {
     }
 
     pub fn get_delay_value_x(&self) -> f64 {
-        self.edge_commodity.iter().map(|(_, val)| self.delay(val.get_total_flow_x(), val.get_edge().get_capacity())).sum()
-    }
-
-    pub fn get_delay_value_y(&self) -> f64 {
-        self.edge_commodity.iter().map(|(_, val)| self.delay(val.get_total_flow_y(), val.get_edge().get_capacity())).sum()
+        self.edge_commodity.iter()
+            .filter(|(key, _)| self.counts_link(**key))
+            .map(|(key, val)| self.delay(self.link_flow_x(*key), val.get_edge().get_capacity()))
+            .sum()
     }
 
     pub fn get_delay_gradient(&self) -> Vec<f64> {
@@ -148,8 +219,8 @@ impl ProjectionMethod {
 
         sorted_keys
             .into_iter()
-            .filter_map(|key| self.edge_commodity.get(&key))
-            .map(|e| count_first_derivative(e.get_total_flow_x(), e.get_edge().get_capacity(), self.p))
+            .filter_map(|key| self.edge_commodity.get(&key).map(|e| (key, e)))
+            .map(|(key, e)| self.cost_model.first_derivative(self.link_flow_x(key), e.get_edge().get_capacity()))
             .collect::<Vec<f64>>()
     }
 
@@ -164,54 +235,225 @@ impl ProjectionMethod {
             .collect()
     }
 
-    pub fn get_total_flow_vector_y(&self) -> Vec<f64> {
-        let mut sorted_keys: Vec<_> = self.edge_commodity.keys().cloned().collect();
-        sorted_keys.sort(); // Сортируем ключи
+    /// потоки (x, y) каждого commodity на каждой дуге, для сохранения состояния метода
+    pub fn get_edge_commodities(&self) -> Vec<(DirectedEdge, CommodityFlows)> {
+        self.edge_commodity.values()
+            .map(|e| (e.get_edge(), e.get_commodities().clone()))
+            .collect()
+    }
 
-        sorted_keys
-            .into_iter()
-            .filter_map(|key| self.edge_commodity.get(&key))
-            .map(|e| e.get_total_flow_y())
+    /// восстанавливает потоки по дугам из сохраненного состояния, стоимости дуг графа не пересчитываются
+    pub fn restore_edge_commodities(&mut self, edge_commodities: &[(DirectedEdge, CommodityFlows)]) {
+        self.edge_commodity = edge_commodities.iter()
+            .map(|(edge, commodities)| (edge.get_id(), EdgeFlowCommodities::new(*edge, commodities.clone())))
+            .collect();
+    }
+
+    /// поток x на каждой дуге, по которой прошел хотя бы один commodity
+    pub fn get_edge_flows_x(&self) -> Vec<(DirectedEdge, f64)> {
+        self.edge_commodity.values()
+            .map(|e| (e.get_edge(), e.get_total_flow_x()))
             .collect()
     }
 
     pub fn get_d_k_p_new (&self, x: f64, path_edges: &Vec<DirectedEdge>) -> f64 {
-        path_edges.iter().map(|e| count_first_derivative(x, e.get_capacity(), self.p)).sum()
+        path_edges.iter().map(|e| self.cost_model.first_derivative(x, e.get_capacity())).sum()
     }
 
     fn get_derivative_one_edge (&self, commodity: &i32, edge: &DirectedEdge) -> f64 {
-        let key = edge.from().to_string() + "_" + &edge.to().to_string();
-        let edge_flows = self.edge_commodity.get(&key);
-        match edge_flows {
-            Some(com_flow) => {
-                let flow = com_flow.get_total_flow_x();
-                count_first_derivative(flow, edge.get_capacity(), self.p)
-            },
-            None => 0f64
-        }
+        self.cost_model.first_derivative(self.link_flow_x(edge.get_id()), edge.get_capacity())
     }
 
     fn delay(&self, flow: f64, capacity: f64) -> f64 {
-        count_delay_value(flow, capacity, self.p)
+        self.cost_model.value(flow, capacity)
+    }
+
+    /// сумма вторых производных по дугам: кривизна задержки берется при суммарном потоке линка,
+    /// поток одного commodity ее сильно занижает на перегруженных линках, и шаг перебрасывает весь поток
+    pub fn get_h_k_p(&self, path_edges: &Vec<DirectedEdge>) -> f64 {
+        path_edges.iter().map(|e| self.get_derivative_two_edge(e)).sum()
     }
 
-    pub fn get_h_k_p(&self, commodity: &i32, path_edges: &Vec<DirectedEdge>) -> f64 {
-        path_edges.iter().map(|e| self.get_derivative_two_edge(commodity, e)).sum()
+    fn get_derivative_two_edge (&self, edge: &DirectedEdge) -> f64 {
+        self.cost_model.second_derivative(self.link_flow_x(edge.get_id()), edge.get_capacity())
     }
 
-    fn get_derivative_two_edge (&self, commodity: &i32, edge: &DirectedEdge) -> f64 {
-        let key = edge.from().to_string() + "_" + &edge.to().to_string();
-        let edge_flows = self.edge_commodity.get(&key);
-        match edge_flows {
-            Some(com_flow) => {
-                let flow = com_flow.get_total_flow_by_commodity(commodity);
-                count_second_derivative(flow, edge.get_capacity(), self.p)
-            },
-            None => 0f64
+    /// гессиан задержки по потокам путей: H_pq - сумма вторых производных по линкам, общим для путей p и q
+    pub fn get_path_hessian(&self, paths: &[&Vec<DirectedEdge>]) -> Vec<Vec<f64>> {
+        let links: Vec<BTreeMap<i32, f64>> = paths.iter()
+            .map(|path| path.iter()
+                .map(|e| {
+                    let key = self.link_key(e.get_id());
+                    let h = self.cost_model.second_derivative(self.link_flow_x(key), e.get_capacity());
+                    (key, h)
+                })
+                .collect())
+            .collect();
+        links.iter()
+            .map(|p| links.iter()
+                .map(|q| p.iter().filter(|(key, _)| q.contains_key(*key)).map(|(_, h)| h).sum())
+                .collect())
+            .collect()
+    }
+
+    /// ключ линка: для общей пропускной способности обе дуги линка получают один ключ
+    fn link_key(&self, key: i32) -> i32 {
+        match self.twins.get(&key) {
+            Some(&twin) if twin < key => twin,
+            _ => key
         }
     }
 
     pub fn get_alpha(&self) -> f64{
         self.alpha
     }
-}
\ No newline at end of file
+}
+
+/// Минимум квадратичной модели g^T d + d^T H d / 2, d = x - current, на симплексе x >= 0, sum x = total:
+/// прямой метод активного множества, current - допустимая начальная точка. На свободных путях решается система
+/// KKT с равенством sum x = total, выходящий за границу шаг обрезается и путь с нулевым потоком выводится
+/// из свободных, путь с отрицательной оценкой множителя возвращается в свободные
+pub fn solve_simplex_qp(gradient: &[f64], hessian: &[Vec<f64>], current: &[f64], total: f64) -> Vec<f64> {
+    let n = current.len();
+    // линейная часть при x вместо d
+    let c: Vec<f64> = (0..n).map(|i| gradient[i] - (0..n).map(|j| hessian[i][j] * current[j]).sum::<f64>()).collect();
+    // малая регуляризация: у путей с общими линками или при кусочно-линейных затратах H вырожден
+    let ridge = 1e-10 * hessian.iter().enumerate().fold(0f64, |m, (i, row)| f64::max(m, row[i]))
+        + 1e-14 * (gradient.iter().fold(0f64, |m, g| f64::max(m, g.abs())) + 1f64);
+    let tolerance = 1e-12 * f64::max(total, 1f64);
+
+    let mut x = current.to_vec();
+    let mut free: Vec<bool> = x.iter().map(|&v| v > 0f64).collect();
+    if !free.iter().any(|&f| f) {
+        let best = (0..n).min_by(|&a, &b| gradient[a].total_cmp(&gradient[b])).unwrap();
+        x[best] = total;
+        free[best] = true;
+    }
+    for _ in 0..10 * n + 10 {
+        let index: Vec<usize> = (0..n).filter(|&i| free[i]).collect();
+        let k = index.len();
+        // [H_FF + ridge I, -1; 1^T, 0] [z; lambda] = [-c_F; total]
+        let mut a = vec![vec![0f64; k + 1]; k + 1];
+        let mut b = vec![0f64; k + 1];
+        for (r, &i) in index.iter().enumerate() {
+            for (s, &j) in index.iter().enumerate() {
+                a[r][s] = hessian[i][j];
+            }
+            a[r][r] += ridge;
+            a[r][k] = -1f64;
+            a[k][r] = 1f64;
+            b[r] = -c[i];
+        }
+        b[k] = total;
+        let solution = solve_linear(a, b);
+        let lambda = solution[k];
+
+        let direction: Vec<f64> = index.iter().enumerate().map(|(r, &i)| solution[r] - x[i]).collect();
+        if direction.iter().all(|d| d.abs() <= tolerance) {
+            // оптимум на свободных путях, проверка множителей путей с нулевым потоком
+            let entering = (0..n)
+                .filter(|&i| !free[i])
+                .map(|i| (i, c[i] + (0..n).map(|j| hessian[i][j] * x[j]).sum::<f64>() - lambda))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            match entering {
+                Some((i, w)) if w < -1e-12 * f64::max(lambda.abs(), 1f64) => free[i] = true,
+                _ => break
+            }
+            continue;
+        }
+
+        let mut step = 1f64;
+        let mut blocking = None;
+        for (r, &i) in index.iter().enumerate() {
+            if direction[r] < 0f64 && -x[i] / direction[r] < step {
+                step = -x[i] / direction[r];
+                blocking = Some(i);
+            }
+        }
+        for (r, &i) in index.iter().enumerate() {
+            x[i] = f64::max(0f64, x[i] + step * direction[r]);
+        }
+        if let Some(i) = blocking {
+            x[i] = 0f64;
+            free[i] = false;
+        }
+    }
+    x
+}
+
+/// решение системы линейных уравнений методом Гаусса с выбором главного элемента
+fn solve_linear(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs())).unwrap();
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        if a[col][col] == 0f64 {
+            continue;
+        }
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let factor = a[row][col] / pivot_row[col];
+            if factor == 0f64 {
+                continue;
+            }
+            for (value, pivot) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0f64; n];
+    for row in (0..n).rev() {
+        let rest: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = if a[row][row] != 0f64 { (b[row] - rest) / a[row][row] } else { 0f64 };
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// допустимость и условия KKT: на путях с потоком градиент модели равен множителю, на остальных не меньше его
+    fn assert_kkt(gradient: &[f64], hessian: &[Vec<f64>], current: &[f64], total: f64, x: &[f64]) {
+        assert!(x.iter().all(|&v| v >= 0f64));
+        assert!((x.iter().sum::<f64>() - total).abs() < 1e-9);
+        let reduced: Vec<f64> = (0..x.len())
+            .map(|i| gradient[i] + (0..x.len()).map(|j| hessian[i][j] * (x[j] - current[j])).sum::<f64>())
+            .collect();
+        let lambda = (0..x.len()).filter(|&i| x[i] > 0f64).map(|i| reduced[i]).fold(f64::INFINITY, f64::min);
+        for i in 0..x.len() {
+            if x[i] > 0f64 {
+                assert!((reduced[i] - lambda).abs() < 1e-8, "{:?}", reduced);
+            } else {
+                assert!(reduced[i] >= lambda - 1e-8, "{:?}", reduced);
+            }
+        }
+    }
+
+    #[test]
+    fn simplex_qp_drops_expensive_path() {
+        // H = I, c = g - H * current = (-6, 0, 10): x_i = lambda - c_i на свободных путях, sum x = 10 дает lambda = 2,
+        // у третьего пути lambda - c_3 < 0, поэтому x = (8, 2, 0)
+        let gradient = [4f64, 0f64, 10f64];
+        let hessian = vec![vec![1f64, 0f64, 0f64], vec![0f64, 1f64, 0f64], vec![0f64, 0f64, 1f64]];
+        let current = [10f64, 0f64, 0f64];
+        let x = solve_simplex_qp(&gradient, &hessian, &current, 10f64);
+        for (value, expected) in x.iter().zip([8f64, 2f64, 0f64]) {
+            assert!((value - expected).abs() < 1e-8, "{:?}", x);
+        }
+        assert_kkt(&gradient, &hessian, &current, 10f64, &x);
+    }
+
+    #[test]
+    fn simplex_qp_with_shared_links() {
+        // пути с общим линком: H x + c = lambda, c = (-12, -14) дает x_2 = x_1 + 2 и при sum x = 10 x = (4, 6)
+        let gradient = [3f64, 1f64];
+        let hessian = vec![vec![2f64, 1f64], vec![1f64, 2f64]];
+        let current = [5f64, 5f64];
+        let x = solve_simplex_qp(&gradient, &hessian, &current, 10f64);
+        assert!((x[0] - 4f64).abs() < 1e-8 && (x[1] - 6f64).abs() < 1e-8, "{:?}", x);
+        assert_kkt(&gradient, &hessian, &current, 10f64, &x);
+    }
+}
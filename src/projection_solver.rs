@@ -0,0 +1,888 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use ndarray::Array1;
+
+use crate::checkpoint::ProjectionCheckpoint;
+use crate::constrained_path::PathConstraint;
+use crate::delay_func_count::CostModel;
+use crate::dijkstra_find_path::TieBreak;
+use crate::flow_solution::FlowSolution;
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::projection_method::{solve_simplex_qp, ProjectionMethod};
+use crate::structure_xml::Demand;
+use crate::utils_graph::{path_key, symmetric_difference};
+
+/// сколько раз за итерацию можно сменить кратчайший путь одного commodity
+const MAX_PATH_SWITCHES: i32 = 100;
+
+/// Шаг перераспределения потока commodity между активными путями
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathStep {
+    /// проекция каждого пути отдельно, масштаб - сумма вторых производных по несовпадающим линкам (get_h_k_p), шаг alpha
+    Diagonal,
+    /// проекционный метод Ньютона: минимум квадратичной модели на симплексе активных путей с полным гессианом,
+    /// учитывающим общие линки путей, шаг полный
+    Newton,
+}
+
+impl FromStr for PathStep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "diagonal" => Ok(PathStep::Diagonal),
+            "newton" => Ok(PathStep::Newton),
+            other => Err(format!("Неизвестный шаг метода проекции: {}", other))
+        }
+    }
+}
+
+/// число итераций без улучшения задержки, после которого останавливается расчет с функцией Fortz-Thorup
+pub const FORTZ_THORUP_STALL_ITERATIONS: i32 = 20;
+
+/// Параметры метода проекции
+#[derive(Debug, Clone, Copy)]
+pub struct ProjectionSettings {
+    alpha: f64,
+    cost_model: CostModel,
+    epsilon: f64,
+    tie_break: TieBreak,
+    max_iterations: i32,
+    stall_iterations: Option<i32>,
+    path_step: PathStep
+}
+
+impl ProjectionSettings {
+
+    pub fn new(alpha: f64, p: f64, epsilon: f64, tie_break: TieBreak) -> ProjectionSettings {
+        ProjectionSettings {
+            alpha,
+            cost_model: CostModel::Kleinrock { p },
+            epsilon,
+            tie_break,
+            max_iterations: 1000,
+            stall_iterations: None,
+            path_step: PathStep::Diagonal
+        }
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: i32) -> ProjectionSettings {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// остановка, если задержка не уменьшилась больше чем в (1 - epsilon) раз за stall_iterations итераций подряд
+    pub fn with_stall_iterations(mut self, stall_iterations: i32) -> ProjectionSettings {
+        self.stall_iterations = Some(stall_iterations);
+        self
+    }
+
+    /// Для кусочно-линейной функции Fortz-Thorup кривизна берется у сглаженной функции, и на перегруженных сетях
+    /// шаг проекции перескакивает изломы: задержка колеблется, а линейная оценка снизу по градиенту у излома
+    /// остается далеко от нее, так что без этого ограничения расчет всегда доходил бы до max_iterations. По умолчанию для нее расчет
+    /// останавливается после FORTZ_THORUP_STALL_ITERATIONS итераций без улучшения, результатом остается последняя итерация.
+    /// Для гладких функций остановка по отсутствию улучшения включается только явно
+    fn stall_limit(&self) -> Option<i32> {
+        match self.cost_model {
+            CostModel::FortzThorup => Some(self.stall_iterations.unwrap_or(FORTZ_THORUP_STALL_ITERATIONS)),
+            _ => self.stall_iterations
+        }
+    }
+
+    pub fn with_cost_model(mut self, cost_model: CostModel) -> ProjectionSettings {
+        self.cost_model = cost_model;
+        self
+    }
+
+    pub fn with_path_step(mut self, path_step: PathStep) -> ProjectionSettings {
+        self.path_step = path_step;
+        self
+    }
+
+    pub fn get_cost_model(&self) -> CostModel {
+        self.cost_model
+    }
+}
+
+/// Результат работы метода проекции
+pub struct ProjectionResult {
+    solution: FlowSolution,
+    initial_delay: f64,
+    delay: f64,
+    lower_bound: f64,
+    iterations: i32,
+    edge_flows: Vec<(DirectedEdge, f64)>,
+    path_flows: Vec<Vec<(Vec<DirectedEdge>, f64)>>,
+    unrouted: Vec<i32>
+}
+
+impl ProjectionResult {
+
+    pub fn get_solution(&self) -> &FlowSolution {
+        &self.solution
+    }
+
+    /// значение задержки после распределения всех commodity по кратчайшим путям
+    pub fn get_initial_delay(&self) -> f64 {
+        self.initial_delay
+    }
+
+    pub fn get_delay(&self) -> f64 {
+        self.delay
+    }
+
+    pub fn get_lower_bound(&self) -> f64 {
+        self.lower_bound
+    }
+
+    pub fn get_iterations(&self) -> i32 {
+        self.iterations
+    }
+
+    /// суммарный поток x на каждой дуге, по которой прошел хотя бы один commodity
+    pub fn get_edge_flows(&self) -> &[(DirectedEdge, f64)] {
+        &self.edge_flows
+    }
+
+    /// пути с положительным потоком для каждого commodity (ребра в обратном порядке, как в DijkstraSP::path_to)
+    pub fn get_path_flows(&self) -> &[Vec<(Vec<DirectedEdge>, f64)>] {
+        &self.path_flows
+    }
+
+    /// индексы commodity, для которых в графе нет пути от источника к стоку
+    pub fn get_unrouted(&self) -> &[i32] {
+        &self.unrouted
+    }
+}
+
+/// Начальное распределение потоков для метода проекции: пути каждого commodity с потоками на них
+#[derive(Debug, Clone, Default)]
+pub struct WarmStart {
+    paths: Vec<Vec<(Vec<DirectedEdge>, f64)>>
+}
+
+impl WarmStart {
+
+    /// paths - для каждого commodity пути (ребра в обратном порядке) с потоками
+    pub fn new(paths: Vec<Vec<(Vec<DirectedEdge>, f64)>>) -> WarmStart {
+        WarmStart {
+            paths
+        }
+    }
+
+    pub fn from_result(result: &ProjectionResult) -> WarmStart {
+        Self::new(result.path_flows.clone())
+    }
+
+    /// теплый старт из ранее сохраненного решения: commodity сопоставляются по исходным id source и target
+    /// (при повторах - по порядку), пути через линки и вершины, которых больше нет в графе, отбрасываются
+    pub fn from_solution(graph_adj: &EdgeWeightedDigraph, commodities: &[Demand], solution: &FlowSolution) -> WarmStart {
+        let mut used = vec![false; solution.get_commodities().len()];
+        let paths = commodities.iter().map(|commodity| {
+            let found = solution.get_commodities().iter().enumerate().position(|(k, c)| {
+                !used[k] && c.get_source() == commodity.get_source() && c.get_target() == commodity.get_target()
+            });
+            match found {
+                Some(k) => {
+                    used[k] = true;
+                    solution.get_commodities()[k].get_paths().iter()
+                        .filter_map(|p| p.to_edges(graph_adj).map(|path| (path, p.get_flow())))
+                        .collect()
+                }
+                None => vec![]
+            }
+        }).collect();
+        Self::new(paths)
+    }
+
+    /// пути commodity, по которым еще можно пройти в графе и которые допускают ограничения commodity,
+    /// поток на них пересчитывается так, чтобы в сумме дать demand
+    fn paths_for(&self, index: usize, graph_adj: &EdgeWeightedDigraph, demand: f64, constraint: &PathConstraint) -> Vec<(Vec<DirectedEdge>, f64)> {
+        let alive: Vec<&(Vec<DirectedEdge>, f64)> = self.paths.get(index).map_or(vec![], |paths| {
+            paths.iter()
+                .filter(|(path, flow)| *flow > 0f64 && graph_adj.path_cost(path).is_finite() && constraint.allows(path))
+                .collect()
+        });
+        let total: f64 = alive.iter().map(|(_, flow)| flow).sum();
+        if total <= 0f64 {
+            return vec![];
+        }
+        alive.into_iter().map(|(path, flow)| (path.clone(), flow * demand / total)).collect()
+    }
+}
+
+/// Сохранение состояния метода проекции в файл path каждые interval итераций
+#[derive(Debug, Clone)]
+pub struct CheckpointSettings {
+    path: String,
+    interval: i32
+}
+
+impl CheckpointSettings {
+
+    pub fn new(path: String, interval: i32) -> CheckpointSettings {
+        CheckpointSettings {
+            path,
+            interval
+        }
+    }
+}
+
+/// Распределение потоков методом проекции: все commodity ставятся на кратчайшие пути,
+/// затем для каждого commodity поток перераспределяется между активными путями, пока не выполнится критерий остановки
+pub fn solve_projection(
+    graph_adj: &mut EdgeWeightedDigraph,
+    commodities: &[Demand],
+    settings: &ProjectionSettings
+) -> ProjectionResult {
+    solve_projection_warm(graph_adj, commodities, settings, &WarmStart::default())
+}
+
+/// метод проекции с теплым стартом: commodity, у которых в warm_start остались проходимые пути,
+/// начинают с распределения по этим путям, остальные - с кратчайшего пути
+pub fn solve_projection_warm(
+    graph_adj: &mut EdgeWeightedDigraph,
+    commodities: &[Demand],
+    settings: &ProjectionSettings,
+    warm_start: &WarmStart
+) -> ProjectionResult {
+    solve_projection_with(graph_adj, commodities, settings, warm_start, None, None)
+}
+
+/// метод проекции с сохранением состояния каждые checkpoint.interval итераций (0 - состояние не сохраняется);
+/// resume - ранее сохраненное состояние, с которого расчет продолжается вместо начального распределения потоков
+pub fn solve_projection_checkpointed(
+    graph_adj: &mut EdgeWeightedDigraph,
+    commodities: &[Demand],
+    settings: &ProjectionSettings,
+    warm_start: &WarmStart,
+    checkpoint: &CheckpointSettings,
+    resume: Option<&ProjectionCheckpoint>
+) -> ProjectionResult {
+    solve_projection_with(graph_adj, commodities, settings, warm_start, Some(checkpoint), resume)
+}
+
+fn solve_projection_with(
+    graph_adj: &mut EdgeWeightedDigraph,
+    commodities: &[Demand],
+    settings: &ProjectionSettings,
+    warm_start: &WarmStart,
+    checkpoint: Option<&CheckpointSettings>,
+    resume: Option<&ProjectionCheckpoint>
+) -> ProjectionResult {
+    let tie_break = settings.tie_break;
+    let mut lb = 0f64;
+
+    // map для хранения результата индекс commodity на пару path на размер потока на соответсвующем path
+    let mut result_x = BTreeMap::new();
+
+    // map для хранения кратчайшего пути для соответвующего commodity, индекс commodity на вектор ребер полученных из сети
+    let mut paths_shortest = HashMap::new();
+    let mut projection_handler = ProjectionMethod::with_cost_model(settings.alpha, settings.cost_model);
+    projection_handler.set_shared_capacity(graph_adj);
+
+    // map для хранения активных path для соответвующего commodity, индекс commodity на map path -> список ребер
+    let mut active_paths: HashMap<i32, BTreeMap<String, Vec<_>>> = HashMap::new();
+
+    // ограничения на число линков и допустимые пути каждого commodity
+    let constraints: Vec<PathConstraint> = commodities.iter().map(|c| PathConstraint::for_demand(graph_adj, c)).collect();
+
+    // source и target каждого commodity, None - commodity нельзя провести по графу
+    let mut ends = vec![];
+    let mut unrouted = vec![];
+
+    let initial_delay;
+    let mut delay_value_t;
+    let mut iterations;
+    // наименьшая задержка и итерация, на которой она получена, для остановки по отсутствию улучшения
+    let mut best_delay;
+    let mut best_iteration;
+    if let Some(state) = resume {
+        // продолжение с сохраненного состояния: потоки, пути и стоимости дуг графа берутся из файла
+        if !state.matches(graph_adj, commodities) {
+            panic!("Файл состояния сохранен для другой задачи");
+        }
+        state.restore_edge_costs(graph_adj);
+        projection_handler.restore_edge_commodities(state.get_edge_flows());
+        ends = (0..commodities.len() as i32).map(|index| state.get_ends().get(&index).copied()).collect();
+        unrouted = state.get_unrouted().to_vec();
+        for (index, path) in state.get_current_paths() {
+            paths_shortest.insert(*index, path.clone());
+        }
+        for (index, paths) in state.get_active_paths() {
+            active_paths.insert(*index, paths.iter().cloned().collect());
+        }
+        for (index, flows) in state.get_path_flows() {
+            result_x.insert(*index, flows.iter().cloned().collect());
+        }
+        lb = state.get_lower_bound();
+        initial_delay = state.get_initial_delay();
+        delay_value_t = state.get_delay();
+        iterations = state.get_iterations();
+        (best_iteration, best_delay) = state.get_best();
+    } else {
+        // определям кратчайшие пути для каждого commodity и ставим потоки на данные пути, после этого пересчитываем
+        // затраты на каждом ребре, которые входят в кратчайшие пути
+        for (index, commodity) in commodities.iter().enumerate() {
+            let r_index = index as i32;
+            let source = graph_adj.get_node_index(&commodity.get_source());
+            let target = graph_adj.get_node_index(&commodity.get_target());
+            let (source, target) = match (source, target) {
+                (Some(s), Some(t)) if graph_adj.is_node_enabled(s) && graph_adj.is_node_enabled(t) => (s, t),
+                _ => {
+                    ends.push(None);
+                    unrouted.push(r_index);
+                    continue;
+                }
+            };
+            let r_k = commodity.get_demand_vale();
+
+            let warm_paths = warm_start.paths_for(index, graph_adj, r_k, &constraints[index]);
+            if !warm_paths.is_empty() {
+                let mut flows = BTreeMap::new();
+                let mut active_paths_commodity = BTreeMap::new();
+                for (path, flow) in &warm_paths {
+                    // один и тот же путь может встретиться в решении несколько раз, потоки складываются
+                    let key_path = path_key(path);
+                    *flows.entry(key_path.clone()).or_insert(0f64) += *flow;
+                    active_paths_commodity.insert(key_path, path.clone());
+                }
+                projection_handler.set_commodity_paths_to_graph(r_index, &flows, &active_paths_commodity, graph_adj);
+                // текущим путем commodity считается путь с наибольшим потоком
+                let main_path = warm_paths.iter().max_by(|a, b| a.1.total_cmp(&b.1)).unwrap().0.clone();
+                ends.push(Some((source, target)));
+                active_paths.insert(r_index, active_paths_commodity);
+                result_x.insert(r_index, flows);
+                paths_shortest.insert(r_index, main_path);
+                continue;
+            }
+
+            // расчет кратчайшего маршрута, через алгоритм Дейкстры (Беллмана-Форда при ограничении числа линков)
+            let path_s = match constraints[index].shortest_path(graph_adj, source, target, tie_break) {
+                Some(path) if !path.is_empty() => path,
+                _ => {
+                    ends.push(None);
+                    unrouted.push(r_index);
+                    continue;
+                }
+            };
+            ends.push(Some((source, target)));
+            let key_path_s = &path_key(&path_s);
+
+            let mut active_paths_commodity = BTreeMap::new();
+            active_paths_commodity.insert(key_path_s.clone(), path_s.clone());
+            active_paths.insert(r_index, active_paths_commodity);
+
+            // устанавливаем поток на найденный кратчайший путь и обновляем ребра графа расчитавая cost
+            projection_handler.set_first_commodity_to_graph(r_index, r_k, &path_s, graph_adj);
+
+            // сохраняем кратчайший путь
+            paths_shortest.insert(r_index, path_s);
+        }
+
+
+        projection_handler.set_idle_edge_costs(graph_adj);
+
+        initial_delay = projection_handler.get_delay_value_x();
+        delay_value_t = initial_delay;
+        iterations = 0;
+        best_delay = initial_delay;
+        best_iteration = 0;
+    }
+
+    // запуск работы метода, проходимся по каждому commodity,
+    // определяем новый кратчайший маршрут, если он совпадает с первоначальным, то считаем что маршрут для этого commodity определен и переходим к следующему
+    // если маршрут не совпадает добавлем его в список активных путей, и запускаем метод PM
+    // остановка расчитывается по формуле статья Adam Ouorou для метода Flow Deviation
+
+    loop {
+        iterations += 1;
+        let x_j_t = projection_handler.get_total_flow_vector_x(); // значение потока на всех ребрах
+        let grad = projection_handler.get_delay_gradient(); // значение градиента функции после распределения потока по ребрам
+        // стоимости дуг графа - градиент задержки, поэтому кратчайшие пути дают минимум grad * y по всем распределениям y
+        let shortest_cost = all_or_nothing_cost(graph_adj, commodities, &ends, &constraints, tie_break);
+        for (index, commodity) in commodities.iter().enumerate() {
+            let (source, target) = match ends[index] {
+                Some(ends) => ends,
+                None => continue
+            };
+            let r_index = index as i32;
+
+            // шаг начинается с текущего распределения commodity, по которому посчитаны потоки дуг
+            let mut method_step_commodity = result_x.get(&r_index).cloned().unwrap_or_else(|| {
+                BTreeMap::from([(path_key(paths_shortest.get(&r_index).unwrap()), commodity.get_demand_vale())])
+            });
+            let mut switches = 0;
+
+            loop {
+                switches += 1;
+
+                // опеределяем новый кратчайший маршрут
+                let mut path_s = constraints[index].shortest_path(graph_adj, source, target, tie_break).expect("Путь не найден");
+
+                //проверяем совпадает ли он с первоначальным путем
+                let path_old = paths_shortest.get(&r_index).unwrap();
+                let key_path_old = &path_key(path_old);
+
+                // при равной стоимости остаемся на текущем пути, чтобы не переключаться между равноценными путями
+                if tie_break.prefer_current(graph_adj, path_old, &path_s) {
+                    path_s = path_old.clone();
+                }
+                let key_path_s = &path_key(&path_s);
+
+                // шаг выравнивает и уже активные пути, поэтому первый шаг итерации делается и без нового кратчайшего пути;
+                // при крутых функциях затрат (MinMaxUtilization) шаг может перебрасывать поток между двумя путями по кругу
+                if (key_path_s == key_path_old && switches > 1) || switches > MAX_PATH_SWITCHES {
+                    // если путь совпадает, сохраняем результат и берем следующий commodity
+                    result_x.insert(r_index, method_step_commodity);
+                    break;
+                }
+
+                if settings.path_step == PathStep::Newton {
+                    let active_paths_commodity = active_paths.get_mut(&r_index).unwrap();
+                    active_paths_commodity.insert(key_path_s.clone(), path_s.clone());
+                    newton_path_step(&mut projection_handler, graph_adj, r_index, commodity.get_demand_vale(),
+                                     &path_s, active_paths_commodity, &mut method_step_commodity);
+                    paths_shortest.insert(r_index, path_s);
+                    result_x.insert(r_index, method_step_commodity.clone());
+                    continue;
+                }
+
+                // добавляем найденый путь в список активных путей
+                let active_paths_commodity = active_paths.get_mut(&r_index).unwrap();
+                active_paths_commodity.insert(key_path_s.clone(), path_s.clone());
+
+                let d_kp_s = projection_handler.get_d_k_p(&r_index, &path_s);
+
+                let mut result = 0f64;
+
+                //определяем x_kp^(t+1) для каждого пути кроме кратчайшего
+                for (key, value) in active_paths_commodity.clone() {
+                    if &key == key_path_s {
+                        projection_handler.update_edge_flow_y(r_index, commodity.get_demand_vale(), &value);
+                        continue;
+                    }
+
+                    let d_kp_i = projection_handler.get_d_k_p(&r_index, &value);
+                    let lk_p = symmetric_difference(value.clone(), path_s.clone());
+                    let h_kp_i = projection_handler.get_h_k_p(&lk_p);
+                    let x_k_p_t = method_step_commodity.get(&key).unwrap_or(&0f64);
+
+                    let gh = x_k_p_t - projection_handler.get_alpha() * ((1f64 / h_kp_i) * (d_kp_i - d_kp_s));
+
+                    //вычисляем x_k_p
+                    let x_k_p_t_1 = f64::max(0f64, gh);
+                    method_step_commodity.insert(key.to_string(), x_k_p_t_1); // обновляем поток для следующего шага
+                    result += x_k_p_t_1;
+                }
+                let x_k_p_s_t = commodity.get_demand_vale() - result; // рассчитываем поток для кратчайшего пути
+                method_step_commodity.insert(key_path_s.clone(), x_k_p_s_t); // сохраняем результат
+
+                // обновляем ребра графа, для расчета нового кратчайшего маршрута
+                if x_k_p_s_t > 0f64 {
+                    projection_handler.update_edge_flow(&method_step_commodity, r_index, graph_adj, active_paths_commodity);
+                    paths_shortest.insert(r_index, path_s);
+                } else {
+                    break;
+                }
+                result_x.insert(r_index, method_step_commodity.clone());
+            }
+        }
+        let delay_value_t_1 = projection_handler.get_delay_value_x();
+        // из выпуклости задержки: f(x*) >= f(x) + grad * (y - x), y - распределение по кратчайшим путям
+        let grad_a = Array1::from(grad);
+        let x_t_a = Array1::from(x_j_t);
+        let t = delay_value_t + shortest_cost - grad_a.dot(&x_t_a);
+        lb = f64::max(lb, t);
+        if delay_value_t_1 < (1f64 - settings.epsilon) * best_delay {
+            best_delay = delay_value_t_1;
+            best_iteration = iterations;
+        }
+        let stalled = settings.stall_limit().is_some_and(|limit| iterations - best_iteration >= limit);
+
+        if delay_value_t_1 <= (1f64 + settings.epsilon) * lb || stalled || iterations >= settings.max_iterations {
+            delay_value_t = delay_value_t_1;
+            break;
+        }
+        delay_value_t = delay_value_t_1;
+
+        if let Some(checkpoint) = checkpoint.filter(|c| c.interval > 0 && iterations % c.interval == 0) {
+            ProjectionCheckpoint::new(graph_adj, commodities)
+                .with_progress(iterations, initial_delay, delay_value_t, lb)
+                .with_best(best_iteration, best_delay)
+                .with_commodities(
+                    unrouted.clone(),
+                    ends.iter().enumerate().filter_map(|(index, e)| e.map(|e| (index as i32, e))).collect(),
+                    paths_shortest.iter().map(|(index, path)| (*index, path.clone())).collect(),
+                    active_paths.iter().map(|(index, paths)| (*index, paths.clone().into_iter().collect())).collect(),
+                    result_x.iter().map(|(index, flows)| (*index, flows.clone().into_iter().collect())).collect()
+                )
+                .with_edge_flows(projection_handler.get_edge_commodities())
+                .write(&checkpoint.path);
+        }
+    }
+
+    let path_flows = (0..commodities.len() as i32).map(|r_index| {
+        match (result_x.get(&r_index), active_paths.get(&r_index)) {
+            (Some(flows), Some(paths)) => flows.iter()
+                .filter(|(_, flow)| **flow > 0f64)
+                .filter_map(|(key, flow)| paths.get(key).map(|path| (path.clone(), *flow)))
+                .collect(),
+            _ => vec![]
+        }
+    }).collect();
+
+    ProjectionResult {
+        solution: FlowSolution::from_paths(graph_adj, commodities, &result_x, &active_paths),
+        initial_delay,
+        delay: delay_value_t,
+        lower_bound: lb,
+        iterations,
+        edge_flows: projection_handler.get_edge_flows_x(),
+        path_flows,
+        unrouted
+    }
+}
+
+/// стоимость распределения всех commodity по кратчайшим путям при текущих стоимостях дуг
+fn all_or_nothing_cost(
+    graph_adj: &mut EdgeWeightedDigraph,
+    commodities: &[Demand],
+    ends: &[Option<(i32, i32)>],
+    constraints: &[PathConstraint],
+    tie_break: TieBreak
+) -> f64 {
+    let mut cost = 0f64;
+    for (index, commodity) in commodities.iter().enumerate() {
+        if let Some((source, target)) = ends[index] {
+            let path = constraints[index].shortest_path(graph_adj, source, target, tie_break).expect("Путь не найден");
+            cost += commodity.get_demand_vale() * graph_adj.path_cost(&path);
+        }
+    }
+    cost
+}
+
+/// Шаг Ньютона для commodity: потоки всех активных путей заменяются минимумом квадратичной модели задержки
+/// на симплексе sum x = demand, градиент - длины путей в первых производных, гессиан - по общим линкам путей
+fn newton_path_step(
+    projection_handler: &mut ProjectionMethod,
+    graph_adj: &mut EdgeWeightedDigraph,
+    r_index: i32,
+    demand: f64,
+    path_s: &Vec<DirectedEdge>,
+    active_paths_commodity: &BTreeMap<String, Vec<DirectedEdge>>,
+    flows: &mut BTreeMap<String, f64>
+) {
+    let keys: Vec<&String> = active_paths_commodity.keys().collect();
+    let paths: Vec<&Vec<DirectedEdge>> = active_paths_commodity.values().collect();
+    let gradient: Vec<f64> = paths.iter().map(|path| projection_handler.get_d_k_p(&r_index, path)).collect();
+    let hessian = projection_handler.get_path_hessian(&paths);
+    let current: Vec<f64> = keys.iter().map(|key| flows.get(*key).copied().unwrap_or(0f64)).collect();
+    let next = solve_simplex_qp(&gradient, &hessian, &current, demand);
+    for (key, flow) in keys.into_iter().zip(next) {
+        flows.insert(key.clone(), flow);
+    }
+    projection_handler.update_edge_flow_y(r_index, demand, path_s);
+    projection_handler.update_edge_flow(flows, r_index, graph_adj, active_paths_commodity);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_solution::{CommodityFlow, PathFlow};
+    use crate::link_utilization::link_utilization;
+    use crate::structure_xml::{LinkModel, Network};
+
+    /// решетка 6x6 с дугами в обе стороны: много путей одинаковой длины и с общими линками
+    fn grid() -> EdgeWeightedDigraph {
+        grid_with_capacity(10f64)
+    }
+
+    fn grid_with_capacity(capacity: f64) -> EdgeWeightedDigraph {
+        let side = 6;
+        let mut arcs = vec![];
+        for r in 0..side {
+            for c in 0..side {
+                let v = r * side + c;
+                if c + 1 < side {
+                    arcs.push((v, v + 1, 1f64, capacity));
+                    arcs.push((v + 1, v, 1f64, capacity));
+                }
+                if r + 1 < side {
+                    arcs.push((v, v + side, 1f64, capacity));
+                    arcs.push((v + side, v, 1f64, capacity));
+                }
+            }
+        }
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs((0..side * side).map(|v| v.to_string()).collect(), &arcs);
+        graph
+    }
+
+    fn demands() -> Vec<Demand> {
+        // все пары вершин на границе решетки, спрос перегружает центральные линки
+        let border: Vec<i32> = (0..36).filter(|v| v % 6 == 0 || v % 6 == 5 || v / 6 == 0 || v / 6 == 5).collect();
+        let mut commodities = vec![];
+        for &s in &border {
+            for &t in &border {
+                if s != t {
+                    let d = 1f64 + ((s * 7 + t * 3) % 5) as f64;
+                    commodities.push(Demand::new(format!("{}_{}", s, t), s.to_string(), t.to_string(), d));
+                }
+            }
+        }
+        commodities
+    }
+
+    fn settings() -> ProjectionSettings {
+        ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent)
+            .with_cost_model(CostModel::FortzThorup)
+            .with_max_iterations(60)
+    }
+
+    fn flow_bits(result: &ProjectionResult) -> Vec<(i32, u64)> {
+        let mut flows: Vec<(i32, u64)> = result.get_edge_flows().iter().map(|(e, f)| (e.get_id(), f.to_bits())).collect();
+        flows.sort();
+        flows
+    }
+
+    #[test]
+    fn repeated_solve_is_bit_identical() {
+        let commodities: Vec<Demand> = demands().into_iter().step_by(2).collect();
+        let first = solve_projection(&mut grid(), &commodities, &settings());
+        for _ in 0..5 {
+            let again = solve_projection(&mut grid(), &commodities, &settings());
+            assert_eq!(first.get_delay().to_bits(), again.get_delay().to_bits());
+            assert_eq!(flow_bits(&first), flow_bits(&again));
+            assert_eq!(first.get_solution().to_string(), again.get_solution().to_string());
+        }
+    }
+
+    #[test]
+    fn parallel_arcs_are_separate_paths() {
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(vec!["a".to_string(), "b".to_string()], &[(0, 1, 1f64, 10f64), (0, 1, 1f64, 10f64)]);
+        let commodities = vec![Demand::new("d".to_string(), "a".to_string(), "b".to_string(), 9f64)];
+        let settings = ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent);
+        let result = solve_projection(&mut graph, &commodities, &settings);
+        // каждая дуга - отдельный путь commodity, одинаковые параллельные дуги в оптимуме загружены поровну
+        assert_eq!(result.get_edge_flows().len(), 2);
+        for (_, flow) in result.get_edge_flows() {
+            assert!((flow - 4.5).abs() < 1e-2, "{}", flow);
+        }
+        assert_eq!(result.get_path_flows()[0].len(), 2);
+        assert!((result.get_delay() - 2f64 * 4.5 / 5.5).abs() < 1e-4);
+        // стоимости обеих дуг в графе пересчитаны по их потокам
+        for (e, flow) in result.get_edge_flows() {
+            let cost = graph.find_edge_by_id(e.get_id()).unwrap().get_cost();
+            assert!((cost - settings.get_cost_model().first_derivative(*flow, 10f64)).abs() < 1e-12);
+        }
+    }
+
+    /// a -> b напрямую (линк a_b) и через c (линки a_c, c_b)
+    fn triangle() -> EdgeWeightedDigraph {
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(vec!["a".to_string(), "b".to_string(), "c".to_string()],
+                              &[(0, 1, 1f64, 10f64), (0, 2, 1f64, 10f64), (2, 1, 1f64, 10f64)]);
+        graph
+    }
+
+    fn path(graph: &EdgeWeightedDigraph, nodes: &[&str], links: &[&str], flow: f64) -> PathFlow {
+        let path = PathFlow::new(nodes.iter().map(|n| n.to_string()).collect(), links.iter().map(|l| l.to_string()).collect(), flow);
+        assert!(path.to_edges(graph).is_some());
+        path
+    }
+
+    #[test]
+    fn repeated_warm_path_keeps_whole_demand() {
+        let mut graph = triangle();
+        let direct = graph.edges().into_iter().filter(|e| e.get_id() == 0).collect::<Vec<_>>();
+        let commodities = vec![Demand::new("d".to_string(), "a".to_string(), "b".to_string(), 5f64)];
+        let warm_start = WarmStart::new(vec![vec![(direct.clone(), 2f64), (direct, 3f64)]]);
+        let settings = ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent);
+        let result = solve_projection_warm(&mut graph, &commodities, &settings, &warm_start);
+        // начальная задержка считается по всему demand 5 на прямом линке: 5 / (10 - 5)
+        assert!((result.get_initial_delay() - 1f64).abs() < 1e-12);
+    }
+
+    #[test]
+    fn warm_start_drops_removed_link_and_rescales_flow() {
+        let graph = triangle();
+        let solution = FlowSolution::new(vec![CommodityFlow::new("a".to_string(), "b".to_string(), 4f64, vec![
+            path(&graph, &["a", "b"], &["a_b"], 3f64),
+            path(&graph, &["a", "c", "b"], &["a_c", "c_b"], 1f64)
+        ])]);
+        let commodities = vec![Demand::new("d".to_string(), "a".to_string(), "b".to_string(), 8f64)];
+        let constraint = PathConstraint::for_demand(&graph, &commodities[0]);
+
+        let warm_start = WarmStart::from_solution(&graph, &commodities, &solution);
+        let paths = warm_start.paths_for(0, &graph, 8f64, &constraint);
+        assert_eq!(paths.len(), 2);
+        assert!((paths[0].1 - 6f64).abs() < 1e-12 && (paths[1].1 - 2f64).abs() < 1e-12);
+
+        // после отказа линка a_c путь через c отбрасывается, весь demand остается на прямом пути
+        let mut failed = graph.clone();
+        failed.set_edge_enabled_by_id(1, false);
+        let paths = warm_start.paths_for(0, &failed, 8f64, &constraint);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].0.iter().map(|e| e.get_id()).collect::<Vec<i32>>(), vec![0]);
+        assert!((paths[0].1 - 8f64).abs() < 1e-12);
+
+        // без линка a_c путь не восстанавливается и при чтении решения
+        let warm_start = WarmStart::from_solution(&failed, &commodities, &solution);
+        assert_eq!(warm_start.paths_for(0, &failed, 8f64, &constraint).len(), 1);
+    }
+
+    fn assert_demands_routed(result: &ProjectionResult, commodities: &[Demand]) {
+        for (paths, commodity) in result.get_path_flows().iter().zip(commodities) {
+            let total: f64 = paths.iter().map(|(_, flow)| flow).sum();
+            assert!((total - commodity.get_demand_vale()).abs() < 1e-9, "{} {}", total, commodity.get_demand_vale());
+        }
+    }
+
+    #[test]
+    fn path_flows_sum_to_demand() {
+        let commodities: Vec<Demand> = demands().into_iter().step_by(29).collect();
+        for cost_model in [CostModel::Kleinrock { p: 0.99 }, CostModel::FortzThorup] {
+            for path_step in [PathStep::Diagonal, PathStep::Newton] {
+                let settings = settings().with_cost_model(cost_model).with_path_step(path_step).with_max_iterations(20);
+                let result = solve_projection(&mut grid_with_capacity(10f64), &commodities, &settings);
+                assert_demands_routed(&result, &commodities);
+            }
+        }
+    }
+
+    #[test]
+    fn lower_bound_stays_below_delay() {
+        let commodities: Vec<Demand> = demands().into_iter().step_by(7).collect();
+        let settings = ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent).with_path_step(PathStep::Newton);
+        let result = solve_projection(&mut grid_with_capacity(60f64), &commodities, &settings);
+        // сходится по зазору задолго до max_iterations
+        assert!(result.get_iterations() > 1 && result.get_iterations() < 100);
+        assert!(result.get_lower_bound() <= result.get_delay());
+        assert!(result.get_delay() <= (1f64 + 0.0001) * result.get_lower_bound());
+    }
+
+    #[test]
+    fn newton_step_lowers_delay_faster_than_diagonal() {
+        let commodities: Vec<Demand> = demands().into_iter().step_by(29).collect();
+        let settings = ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent).with_max_iterations(3);
+        let diagonal = solve_projection(&mut grid_with_capacity(10f64), &commodities, &settings);
+        let newton = solve_projection(&mut grid_with_capacity(10f64), &commodities, &settings.with_path_step(PathStep::Newton));
+        assert_eq!(diagonal.get_iterations(), 3);
+        assert_eq!(newton.get_iterations(), 3);
+        assert!(newton.get_delay() < diagonal.get_delay(), "{} {}", newton.get_delay(), diagonal.get_delay());
+    }
+
+    #[test]
+    fn fortz_thorup_run_stops_on_stall() {
+        let commodities: Vec<Demand> = demands().into_iter().step_by(29).collect();
+        let settings = settings().with_path_step(PathStep::Newton).with_max_iterations(100);
+        // задержка колеблется и зазор до нижней оценки не закрывается, расчет останавливается по отсутствию улучшения
+        let stalled = solve_projection(&mut grid_with_capacity(10f64), &commodities, &settings.with_stall_iterations(5));
+        assert!(stalled.get_iterations() < 100);
+        assert!(stalled.get_delay() > (1f64 + 0.0001) * stalled.get_lower_bound());
+        let by_default = solve_projection(&mut grid_with_capacity(10f64), &commodities, &settings);
+        assert!(by_default.get_iterations() >= stalled.get_iterations() && by_default.get_iterations() < 100);
+        // без ограничения расчет доходит до max_iterations
+        let unlimited = solve_projection(&mut grid_with_capacity(10f64), &commodities, &settings.with_max_iterations(40).with_stall_iterations(40));
+        assert_eq!(unlimited.get_iterations(), 40);
+    }
+
+    #[test]
+    fn resumed_solve_matches_uninterrupted_run() {
+        let commodities: Vec<Demand> = demands().into_iter().step_by(7).collect();
+        let settings = ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent)
+            .with_path_step(PathStep::Newton)
+            .with_max_iterations(100);
+        let full = solve_projection(&mut grid_with_capacity(60f64), &commodities, &settings);
+        // расчет останавливается по зазору до нижней оценки, и продолженный расчет должен остановиться на той же итерации
+        assert!(full.get_iterations() > 7 && full.get_iterations() < 100, "{}", full.get_iterations());
+        assert!(full.get_delay() <= (1f64 + 0.0001) * full.get_lower_bound());
+
+        let path = std::env::temp_dir().join(format!("projection_resume_{}.checkpoint", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let checkpoint = CheckpointSettings::new(path.clone(), 7);
+        solve_projection_checkpointed(&mut grid_with_capacity(60f64), &commodities, &settings, &WarmStart::default(), &checkpoint, None);
+        let state = ProjectionCheckpoint::read(&path);
+        std::fs::remove_file(&path).unwrap();
+        // последнее состояние сохранено до итерации, на которой расчет остановился
+        assert_eq!(state.get_iterations(), (full.get_iterations() - 1) / 7 * 7);
+
+        let no_checkpoint = CheckpointSettings::new(path, 0);
+        let resumed = solve_projection_checkpointed(&mut grid_with_capacity(60f64), &commodities, &settings, &WarmStart::default(), &no_checkpoint, Some(&state));
+        assert_eq!(resumed.get_iterations(), full.get_iterations());
+        assert_eq!(resumed.get_delay().to_bits(), full.get_delay().to_bits());
+        assert_eq!(resumed.get_lower_bound().to_bits(), full.get_lower_bound().to_bits());
+        assert_eq!(flow_bits(&resumed), flow_bits(&full));
+        assert_eq!(resumed.get_solution().to_string(), full.get_solution().to_string());
+    }
+
+    /// один линк a - b емкостью 10 в формате SNDlib и встречные demand 4 и 3
+    fn single_link(link_model: LinkModel) -> (EdgeWeightedDigraph, Vec<Demand>) {
+        let xml = r#"<network>
+ <networkStructure>
+  <nodes coordinatesType="pixel">
+   <node id="a"><coordinates><x>0</x><y>0</y></coordinates></node>
+   <node id="b"><coordinates><x>1</x><y>0</y></coordinates></node>
+  </nodes>
+  <links>
+   <link id="L1"><source>a</source><target>b</target><preInstalledModule><capacity>10</capacity><cost>1</cost></preInstalledModule></link>
+  </links>
+ </networkStructure>
+ <demands>
+  <demand id="D1"><source>a</source><target>b</target><demandValue>4</demandValue></demand>
+  <demand id="D2"><source>b</source><target>a</target><demandValue>3</demandValue></demand>
+ </demands>
+</network>"#;
+        let network: Network = serde_xml_rs::from_str(xml).unwrap();
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_struct_xml(&network, link_model).compact();
+        (graph, network.get_demands().get_demand_vec())
+    }
+
+    #[test]
+    fn undirected_link_shares_capacity_between_directions() {
+        let settings = ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent);
+        let kleinrock = CostModel::Kleinrock { p: 0.99 };
+
+        // UNDIRECTED: потоки обоих направлений ложатся на одну емкость 10, линк учитывается в задержке один раз
+        let (mut graph, commodities) = single_link(LinkModel::Undirected);
+        let result = solve_projection(&mut graph, &commodities, &settings);
+        let utilization = link_utilization(&graph, result.get_edge_flows());
+        assert_eq!(utilization.get_links().len(), 1);
+        assert!((utilization.max_utilization() - 0.7).abs() < 1e-9);
+        assert!((result.get_delay() - kleinrock.value(7f64, 10f64)).abs() < 1e-9);
+
+        // BIDIRECTED: у каждого направления своя емкость
+        let (mut graph, commodities) = single_link(LinkModel::Bidirected);
+        let result = solve_projection(&mut graph, &commodities, &settings);
+        let utilization = link_utilization(&graph, result.get_edge_flows());
+        assert_eq!(utilization.get_links().len(), 2);
+        assert!((utilization.max_utilization() - 0.4).abs() < 1e-9);
+        assert!((result.get_delay() - kleinrock.value(4f64, 10f64) - kleinrock.value(3f64, 10f64)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn min_max_utilization_balances_unequal_routes() {
+        // прямой линк a -> b емкостью 10 и обход из четырех линков емкостью 12: Клейнрок считает задержку
+        // каждого линка и грузит короткий путь, min-max выравнивает загрузку (идеал 6 / 22)
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(["a", "b", "c", "d", "e"].iter().map(|n| n.to_string()).collect(),
+                              &[(0, 1, 1f64, 10f64), (0, 2, 1f64, 12f64), (2, 3, 1f64, 12f64), (3, 4, 1f64, 12f64), (4, 1, 1f64, 12f64)]);
+        let commodities = vec![Demand::new("d".to_string(), "a".to_string(), "b".to_string(), 6f64)];
+        let settings = ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent);
+
+        let kleinrock = solve_projection(&mut graph.clone(), &commodities, &settings);
+        let kleinrock_max = link_utilization(&graph, kleinrock.get_edge_flows()).max_utilization();
+        let beta = 20f64;
+        let min_max = solve_projection(&mut graph.clone(), &commodities, &settings.with_cost_model(CostModel::MinMaxUtilization { beta }));
+        let min_max_max = link_utilization(&graph, min_max.get_edge_flows()).max_utilization();
+
+        assert!(kleinrock_max > 0.45, "{}", kleinrock_max);
+        assert!(min_max_max < kleinrock_max - 0.1, "{} >= {}", min_max_max, kleinrock_max);
+        // log-sum-exp отличается от максимума не более чем на ln(m) / beta
+        assert!(min_max_max - 6f64 / 22f64 <= 5f64.ln() / beta, "{}", min_max_max);
+    }
+}
@@ -0,0 +1,44 @@
+/// Генератор псевдослучайных чисел xorshift64*: эвристики поиска должны давать один и тот же результат при одном seed
+#[derive(Debug, Clone)]
+pub struct XorShiftRng {
+    state: u64
+}
+
+impl XorShiftRng {
+
+    pub fn new(seed: u64) -> XorShiftRng {
+        // перемешиваем seed (splitmix64), чтобы близкие seed давали разные последовательности, состояние не может быть 0
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        XorShiftRng {
+            state: if z == 0 { 0x9E37_79B9_7F4A_7C15 } else { z }
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// равномерно распределенное число из [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// равномерно распределенное целое из [low, high]
+    pub fn gen_range(&mut self, low: i32, high: i32) -> i32 {
+        let span = (high as i64 - low as i64 + 1) as u64;
+        (low as i64 + (self.next_u64() % span) as i64) as i32
+    }
+
+    /// равномерно распределенный индекс из [0, n)
+    pub fn gen_index(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
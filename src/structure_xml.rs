@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize, Clone)]
@@ -44,9 +46,20 @@ pub struct AddModule {
     cost: f64
 }
 
+impl AddModule {
 
-#[derive(Debug, Deserialize, Clone)]
+    pub fn get_capacity(&self) -> f64 {
+        self.capacity
+    }
+
+    pub fn get_cost(&self) -> f64 {
+        self.cost
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct AdditionalModules {
+    #[serde(default)]
     addModule: Vec<AddModule>
 }
 
@@ -57,11 +70,16 @@ pub struct Link {
     source: String,
     target: String,
     preInstalledModule: PreInstalledModule,
+    #[serde(default)]
     additionalModules: AdditionalModules
 }
 
 impl Link {
 
+    pub fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
     pub fn get_source(&self) -> String {
         self.source.clone()
     }
@@ -77,6 +95,11 @@ impl Link {
     pub fn get_capacity(&self) -> f64 {
         self.preInstalledModule.capacity
     }
+
+    /// модули, которые можно дополнительно установить на линк
+    pub fn get_additional_modules(&self) -> Vec<AddModule> {
+        self.additionalModules.addModule.clone()
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -110,21 +133,36 @@ impl NetworkStructure {
     pub fn get_node_count(&self) -> usize {
         self.nodes.node.len()
     }
-
-    pub fn get_link_count(&self) -> usize {
-        self.links.link.len()
-    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Demand {
+    #[serde(rename = "id", default)]
+    id: String,
     source: String,
     target: String,
-    demandValue: f64
+    demandValue: f64,
+    // максимальное число линков на пути, UNLIMITED или отсутствие - без ограничения
+    #[serde(rename = "maxPathLength", default)]
+    max_path_length: Option<String>,
+    // допустимые пути из раздела admissiblePaths, каждый путь - id линков от source к target
+    #[serde(skip)]
+    admissible_paths: Vec<Vec<String>>
 }
 
 impl Demand {
 
+    pub fn new(id: String, source: String, target: String, demand_value: f64) -> Demand {
+        Demand {
+            id,
+            source,
+            target,
+            demandValue: demand_value,
+            max_path_length: None,
+            admissible_paths: vec![]
+        }
+    }
+
     pub fn get_source(&self) -> String {
         self.source.clone()
     }
@@ -136,6 +174,27 @@ impl Demand {
     pub fn get_demand_vale(&self) -> f64 {
         self.demandValue
     }
+
+    pub fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    pub fn get_max_path_length(&self) -> Option<usize> {
+        self.max_path_length.as_ref().and_then(|l| l.trim().parse::<usize>().ok())
+    }
+
+    /// допустимые пути, пустой список - допустим любой путь
+    pub fn get_admissible_paths(&self) -> &[Vec<String>] {
+        &self.admissible_paths
+    }
+
+    /// копия demand, которая может идти только по заданному пути из id линков, ограничение числа линков снимается
+    pub fn with_fixed_path(&self, links: Vec<String>) -> Demand {
+        let mut demand = self.clone();
+        demand.max_path_length = None;
+        demand.admissible_paths = vec![links];
+        demand
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -145,20 +204,60 @@ pub struct Demands {
 
 impl Demands {
 
+    pub fn new(demand: Vec<Demand>) -> Demands {
+        Demands {
+            demand
+        }
+    }
+
     pub fn get_demand_vec(&self) -> Vec<Demand> {
         self.demand.clone()
     }
 
+    /// раздел demands в формате SNDlib, ограничения на пути не записываются
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from(" <demands>\n");
+        for d in &self.demand {
+            xml.push_str(&format!("  <demand id=\"{}\"><source>{}</source><target>{}</target><demandValue>{}</demandValue></demand>\n",
+                                  d.id, d.source, d.target, d.demandValue));
+        }
+        xml.push_str(" </demands>\n");
+        xml
+    }
+
     pub fn get_demands_count(&self) -> usize {
         self.demand.len()
     }
 
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct AdmissiblePath {
+    #[serde(rename = "linkId", default)]
+    link_id: Vec<String>
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DemandPaths {
+    // в SNDlib id раздела - id demand, к которому относятся пути
+    #[serde(rename = "id")]
+    demand_id: String,
+    #[serde(rename = "admissiblePath", default)]
+    admissible_path: Vec<AdmissiblePath>
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AdmissiblePaths {
+    #[serde(rename = "demandPaths", default)]
+    demand_paths: Vec<DemandPaths>
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Network {
     networkStructure: NetworkStructure,
-    demands: Demands
+    demands: Demands,
+    #[serde(rename = "admissiblePaths", default)]
+    admissible_paths: AdmissiblePaths
 }
 
 impl Network {
@@ -167,7 +266,111 @@ impl Network {
         self.networkStructure.clone()
     }
 
+    /// demands вместе с их допустимыми путями из раздела admissiblePaths
     pub fn get_demands(&self) -> Demands {
-        self.demands.clone()
+        let mut demands = self.demands.clone();
+        for demand in demands.demand.iter_mut() {
+            demand.admissible_paths = self.admissible_paths.demand_paths.iter()
+                .filter(|p| p.demand_id == demand.id)
+                .flat_map(|p| p.admissible_path.iter().map(|path| path.link_id.clone()))
+                .collect();
+        }
+        demands
+    }
+}
+
+/// Модель линков SNDlib: как линк превращается в дуги графа
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkModel {
+    /// одна дуга source -> target
+    Directed,
+    /// две встречные дуги, у каждой своя пропускная способность
+    Bidirected,
+    /// две встречные дуги, поток обоих направлений делит одну пропускную способность
+    Undirected,
+}
+
+impl FromStr for LinkModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "DIRECTED" => Ok(LinkModel::Directed),
+            "BIDIRECTED" => Ok(LinkModel::Bidirected),
+            "UNDIRECTED" => Ok(LinkModel::Undirected),
+            other => Err(format!("Неизвестная модель линков: {}", other))
+        }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct LinkModelSection {
+    #[serde(rename = "linkModel")]
+    link_model: Option<String>
+}
+
+/// Файл модели SNDlib (model.xml), читаются только нужные разделы
+#[derive(Debug, Deserialize, Clone)]
+pub struct Model {
+    #[serde(rename = "linkModel")]
+    link_model: Option<LinkModelSection>
+}
+
+impl Model {
+
+    pub fn get_link_model(&self) -> Option<LinkModel> {
+        self.link_model.as_ref()
+            .and_then(|m| m.link_model.as_ref())
+            .and_then(|m| m.parse().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETWORK: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<network>
+ <networkStructure>
+  <nodes coordinatesType="pixel">
+   <node id="a"><coordinates><x>0</x><y>0</y></coordinates></node>
+   <node id="b"><coordinates><x>1</x><y>0</y></coordinates></node>
+   <node id="c"><coordinates><x>2</x><y>0</y></coordinates></node>
+  </nodes>
+  <links>
+   <link id="L1"><source>a</source><target>b</target><preInstalledModule><capacity>10</capacity><cost>1</cost></preInstalledModule></link>
+   <link id="L2"><source>b</source><target>c</target><preInstalledModule><capacity>10</capacity><cost>1</cost></preInstalledModule></link>
+   <link id="L3"><source>a</source><target>c</target><preInstalledModule><capacity>10</capacity><cost>1</cost></preInstalledModule></link>
+  </links>
+ </networkStructure>
+ <demands>
+  <demand id="D1"><source>a</source><target>c</target><demandValue>1</demandValue><maxPathLength>1</maxPathLength></demand>
+  <demand id="D2"><source>a</source><target>c</target><demandValue>2</demandValue><maxPathLength>UNLIMITED</maxPathLength></demand>
+  <demand id="D3"><source>a</source><target>c</target><demandValue>3</demandValue></demand>
+ </demands>
+ <admissiblePaths>
+  <demandPaths id="D3">
+   <admissiblePath id="P1"><linkId>L1</linkId><linkId>L2</linkId></admissiblePath>
+   <admissiblePath id="P2"><linkId>L3</linkId></admissiblePath>
+  </demandPaths>
+ </admissiblePaths>
+</network>"#;
+
+    #[test]
+    fn reads_link_model() {
+        let xml = r#"<model><nodeModel><nodeModel>NODE_HARDWARE</nodeModel></nodeModel><linkModel><linkModel>UNDIRECTED</linkModel></linkModel></model>"#;
+        let model: Model = serde_xml_rs::from_str(xml).unwrap();
+        assert_eq!(model.get_link_model(), Some(LinkModel::Undirected));
+    }
+
+    #[test]
+    fn reads_path_length_and_admissible_paths() {
+        let network: Network = serde_xml_rs::from_str(NETWORK).unwrap();
+        let demands = network.get_demands().get_demand_vec();
+        assert_eq!(demands[0].get_max_path_length(), Some(1));
+        assert_eq!(demands[1].get_max_path_length(), None);
+        assert_eq!(demands[2].get_max_path_length(), None);
+        assert!(demands[0].get_admissible_paths().is_empty());
+        assert_eq!(demands[2].get_admissible_paths(), &[vec!["L1".to_string(), "L2".to_string()], vec!["L3".to_string()]]);
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::dijkstra_find_path::DijkstraSP;
+use crate::failure_analysis::FailedElement;
+use crate::flow_solution::FlowSolution;
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::index_min_pq::IndexMinPQ;
+use crate::link_utilization::{link_utilization, LinkUtilization};
+use crate::projection_solver::{solve_projection, ProjectionSettings};
+use crate::structure_xml::Demand;
+
+/// какая доля пропускной способности остается рабочему потоку, если резерв занимает весь линк
+const MIN_RESIDUAL_SHARE: f64 = 0.01;
+
+/// Требование к непересечению основного и резервного путей
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disjointness {
+    /// у путей нет общих линков
+    Link,
+    /// у путей нет общих линков и промежуточных вершин
+    Node,
+}
+
+impl FromStr for Disjointness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "link" => Ok(Disjointness::Link),
+            "node" => Ok(Disjointness::Node),
+            other => Err(format!("Неизвестный тип непересечения путей: {}", other))
+        }
+    }
+}
+
+/// Резервирование пропускной способности под резервные пути
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protection {
+    /// у каждого резервного пути своя емкость: резерв дуги - сумма demand резервных путей через нее
+    Dedicated,
+    /// резервные пути делят емкость, если их основные пути не отказывают одновременно:
+    /// резерв дуги - максимум по одиночным отказам суммы demand, которые при этом отказе переходят на дугу
+    Shared,
+}
+
+impl FromStr for Protection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "dedicated" => Ok(Protection::Dedicated),
+            "shared" => Ok(Protection::Shared),
+            other => Err(format!("Неизвестный тип резервирования: {}", other))
+        }
+    }
+}
+
+/// Дуга вспомогательного графа алгоритма Суурбалле
+#[derive(Debug, Clone, Copy)]
+struct AuxArc {
+    to: usize,
+    cost: f64,                  // приведенная стоимость, неотрицательна
+    edge: Option<DirectedEdge>, // дуга исходного графа, None - дуга внутри расщепленной вершины
+    reverse: bool               // дуга первого пути, пройденная в обратную сторону
+}
+
+/// Пара непересекающихся путей от s до t с наименьшей суммарной стоимостью (алгоритм Суурбалле).
+/// Пути в обратном порядке, как в DijkstraSP::path_to, первым идет более дешевый; None - такой пары нет.
+/// Дуги одного линка считаются одним ресурсом: второй путь не может пройти линк первого пути во встречном направлении
+pub fn disjoint_paths(graph_adj: &mut EdgeWeightedDigraph, s: i32, t: i32, disjointness: Disjointness) -> Option<(Vec<DirectedEdge>, Vec<DirectedEdge>)> {
+    if s == t {
+        return None;
+    }
+    let sp = DijkstraSP::dijkstra(graph_adj, s);
+    let mut first = sp.path_to(t as usize)?;
+    first.reverse();
+    let n = graph_adj.get_v_count() as usize;
+    let dist: Vec<f64> = (0..n).map(|v| if sp.has_path_to(v) { sp.dist_to(v) } else { f64::INFINITY }).collect();
+
+    // при непересечении по вершинам вершина v расщепляется на вход 2v и выход 2v + 1
+    let split = disjointness == Disjointness::Node;
+    let node_in = |v: i32| if split { 2 * v as usize } else { v as usize };
+    let node_out = |v: i32| if split { 2 * v as usize + 1 } else { v as usize };
+
+    // остаточный граф с приведенными стоимостями c(v, w) + dist(v) - dist(w): дуги линков первого пути
+    // заменяются обратными дугами нулевой стоимости
+    let first_links: BTreeSet<String> = first.iter().map(|e| graph_adj.get_edge_label(e)).collect();
+    let mut arcs: Vec<Vec<AuxArc>> = vec![vec![]; if split { 2 * n } else { n }];
+    for v in (0..n).filter(|&v| dist[v].is_finite()) {
+        for e in graph_adj.edge_list(v) {
+            if first_links.contains(&graph_adj.get_edge_label(&e)) {
+                continue;
+            }
+            arcs[node_out(e.from())].push(AuxArc {
+                to: node_in(e.to()),
+                cost: f64::max(0f64, e.get_cost() + dist[v] - dist[e.to() as usize]),
+                edge: Some(e),
+                reverse: false
+            });
+        }
+    }
+    for e in &first {
+        arcs[node_in(e.to())].push(AuxArc { to: node_out(e.from()), cost: 0f64, edge: Some(*e), reverse: true });
+    }
+    if split {
+        let inner: BTreeSet<i32> = first.iter().skip(1).map(|e| e.from()).collect();
+        for v in 0..n as i32 {
+            if inner.contains(&v) {
+                arcs[node_out(v)].push(AuxArc { to: node_in(v), cost: 0f64, edge: None, reverse: true });
+            } else {
+                arcs[node_in(v)].push(AuxArc { to: node_out(v), cost: 0f64, edge: None, reverse: false });
+            }
+        }
+    }
+    let second = aux_shortest_path(&arcs, node_out(s), node_in(t))?;
+
+    // дуги первого пути, пройденные вторым в обратную сторону, взаимно сокращаются, остальные дуги образуют два пути
+    let cancelled: BTreeSet<i32> = second.iter()
+        .filter(|a| a.reverse)
+        .filter_map(|a| a.edge)
+        .map(|e| e.get_id())
+        .collect();
+    let mut remaining: Vec<DirectedEdge> = first.iter()
+        .filter(|e| !cancelled.contains(&e.get_id()))
+        .copied()
+        .chain(second.iter().filter(|a| !a.reverse).filter_map(|a| a.edge))
+        .collect();
+    let a = extract_path(&mut remaining, s, t)?;
+    let b = extract_path(&mut remaining, s, t)?;
+    if graph_adj.path_cost(&b) < graph_adj.path_cost(&a) {
+        Some((b, a))
+    } else {
+        Some((a, b))
+    }
+}
+
+/// алгоритм Дейкстры на вспомогательном графе, путь из дуг в прямом порядке
+fn aux_shortest_path(arcs: &[Vec<AuxArc>], s: usize, t: usize) -> Option<Vec<AuxArc>> {
+    let mut dist = vec![f64::INFINITY; arcs.len()];
+    let mut arc_to: Vec<Option<(usize, AuxArc)>> = vec![None; arcs.len()];
+    let mut pq = IndexMinPQ::get_index_from_size(arcs.len());
+    dist[s] = 0f64;
+    pq.insert(s, 0f64).expect("Источник вне графа");
+    while let Ok(v) = pq.del_min() {
+        for arc in &arcs[v] {
+            let candidate = dist[v] + arc.cost;
+            if candidate < dist[arc.to] {
+                dist[arc.to] = candidate;
+                arc_to[arc.to] = Some((v, *arc));
+                if pq.contains(arc.to) {
+                    pq.decrease_key(arc.to, candidate).expect("Ключ в очереди не уменьшился");
+                } else {
+                    pq.insert(arc.to, candidate).expect("Вершина вне графа");
+                }
+            }
+        }
+    }
+    if !dist[t].is_finite() {
+        return None;
+    }
+    let mut path = vec![];
+    let mut v = t;
+    while let Some((from, arc)) = arc_to[v] {
+        path.push(arc);
+        v = from;
+    }
+    path.reverse();
+    Some(path)
+}
+
+/// выделяет из набора дуг путь от s до t (в обратном порядке) и удаляет его дуги из набора, циклы по пути отбрасываются
+fn extract_path(edges: &mut Vec<DirectedEdge>, s: i32, t: i32) -> Option<Vec<DirectedEdge>> {
+    let mut path: Vec<DirectedEdge> = vec![];
+    let mut v = s;
+    while v != t {
+        let k = edges.iter().position(|e| e.from() == v)?;
+        let e = edges.remove(k);
+        if let Some(cycle) = path.iter().position(|p| p.from() == e.to()) {
+            path.truncate(cycle);
+        } else {
+            path.push(e);
+        }
+        v = e.to();
+    }
+    path.reverse();
+    Some(path)
+}
+
+/// Параметры маршрутизации с защитой путей
+#[derive(Debug, Clone)]
+pub struct SurvivableSettings {
+    disjointness: Disjointness,
+    protection: Protection,
+    critical: Option<Vec<String>>, // id защищаемых demand, None - защищаются все
+    rounds: i32                    // сколько раз пары путей пересчитываются по загрузке после метода проекции
+}
+
+impl SurvivableSettings {
+
+    pub fn new(disjointness: Disjointness, protection: Protection) -> SurvivableSettings {
+        SurvivableSettings {
+            disjointness,
+            protection,
+            critical: None,
+            rounds: 5
+        }
+    }
+
+    pub fn with_critical(mut self, critical: Vec<String>) -> SurvivableSettings {
+        self.critical = Some(critical);
+        self
+    }
+
+    pub fn with_rounds(mut self, rounds: i32) -> SurvivableSettings {
+        self.rounds = rounds;
+        self
+    }
+}
+
+/// Основной и резервный путь защищенного commodity, ребра в обратном порядке
+#[derive(Debug, Clone)]
+pub struct ProtectedDemand {
+    index: i32,
+    primary: Vec<DirectedEdge>,
+    backup: Vec<DirectedEdge>
+}
+
+/// Результат маршрутизации с защитой путей
+#[derive(Debug, Clone)]
+pub struct SurvivableRouting {
+    protected: Vec<ProtectedDemand>,
+    table: Vec<(String, Vec<String>, Vec<String>)>, // id demand, вершины основного и резервного путей
+    unprotected: Vec<String>,                       // id критичных demand без пары непересекающихся путей, они идут без защиты
+    solution: FlowSolution,
+    reservations: Vec<(DirectedEdge, f64)>,         // зарезервированная под резервные пути пропускная способность дуг
+    utilization: LinkUtilization,                   // загрузка линков рабочим потоком вместе с резервом
+    objective: f64,                                 // целевая функция рабочего потока при емкости за вычетом резерва
+    rounds: i32
+}
+
+impl SurvivableRouting {
+
+    /// рабочий поток: защищенные commodity на основных путях, остальные - по решению метода проекции
+    pub fn get_solution(&self) -> &FlowSolution {
+        &self.solution
+    }
+
+    pub fn get_reserved_capacity(&self) -> f64 {
+        self.reservations.iter().fold(0f64, |sum, (_, r)| sum + r)
+    }
+}
+
+impl fmt::Display for SurvivableRouting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, primary, backup) in &self.table {
+            writeln!(f, "demand {}: primary: {}, backup: {}", id, primary.join(" -> "), backup.join(" -> "))?;
+        }
+        for id in &self.unprotected {
+            writeln!(f, "demand {}: no disjoint backup path", id)?;
+        }
+        write!(f, "{}", self.utilization)?;
+        writeln!(f, "objective: {}, reserved capacity: {:.2}, protected demands: {}, unprotected: {}, rounds: {}",
+                 self.objective, self.get_reserved_capacity(), self.protected.len(), self.unprotected.len(), self.rounds)
+    }
+}
+
+/// Маршрутизация с защитой путей: каждый критичный commodity получает основной путь и непересекающийся с ним резервный
+/// (алгоритм Суурбалле по производным целевой функции при текущей загрузке), под резервные пути резервируется емкость.
+/// Остальные commodity распределяются методом проекции по пропускной способности за вычетом резерва, критичные закреплены
+/// на основных путях. Пары путей и распределение пересчитываются по очереди, пока пары не перестанут меняться.
+/// Ограничения критичных commodity на число линков и допустимые пути не учитываются
+pub fn route_survivable(
+    graph_adj: &EdgeWeightedDigraph,
+    commodities: &[Demand],
+    projection: &ProjectionSettings,
+    settings: &SurvivableSettings
+) -> SurvivableRouting {
+    let cost_model = projection.get_cost_model();
+    let mut critical: Vec<usize> = commodities.iter().enumerate()
+        .filter(|(_, c)| settings.critical.as_ref().is_none_or(|ids| ids.contains(&c.get_id())))
+        .map(|(j, _)| j)
+        .collect();
+    critical.sort_by(|&a, &b| commodities[b].get_demand_vale().total_cmp(&commodities[a].get_demand_vale()));
+
+    let mut work = graph_adj.clone();
+    for e in graph_adj.edges() {
+        if e.get_capacity() <= 0f64 {
+            work.set_edge_enabled_by_id(e.get_id(), false);
+        }
+    }
+
+    let mut working: BTreeMap<i32, f64> = BTreeMap::new(); // рабочий поток на дуге по ее id
+    let mut pairs: BTreeMap<usize, (Vec<DirectedEdge>, Vec<DirectedEdge>)> = BTreeMap::new();
+    let mut rounds = 0;
+    let (reduced, result, reservations) = loop {
+        rounds += 1;
+        let previous = path_ids(&pairs);
+
+        // пара путей commodity ищется при загрузке без его собственного потока и резерва
+        for &j in &critical {
+            let demand = commodities[j].get_demand_vale();
+            if let Some((primary, _)) = pairs.remove(&j) {
+                for e in &primary {
+                    *working.entry(e.get_id()).or_insert(0f64) -= demand;
+                }
+            }
+            let source = graph_adj.get_node_index(&commodities[j].get_source());
+            let target = graph_adj.get_node_index(&commodities[j].get_target());
+            let (s, t) = match (source, target) {
+                (Some(s), Some(t)) => (s, t),
+                _ => continue
+            };
+            let reservations = reserve(graph_adj, commodities, &pairs, settings);
+            for e in graph_adj.edges() {
+                let cost = cost_model.first_derivative(link_sum(graph_adj, &working, &e), residual_capacity(graph_adj, &reservations, &e));
+                work.update_edge_by_id(e.get_id(), cost);
+            }
+            if let Some(pair) = disjoint_paths(&mut work, s, t, settings.disjointness) {
+                for e in &pair.0 {
+                    *working.entry(e.get_id()).or_insert(0f64) += demand;
+                }
+                pairs.insert(j, pair);
+            }
+        }
+
+        // резерв вычитается из пропускной способности, защищенные commodity закрепляются на основных путях
+        let reservations = reserve(graph_adj, commodities, &pairs, settings);
+        let mut reduced = graph_adj.clone();
+        for e in graph_adj.edges() {
+            reduced.set_capacity_by_id(e.get_id(), residual_capacity(graph_adj, &reservations, &e));
+        }
+        let pinned: Vec<Demand> = commodities.iter().enumerate().map(|(j, c)| match pairs.get(&j) {
+            Some((primary, _)) => c.with_fixed_path(primary.iter().rev().map(|e| graph_adj.get_edge_label(e)).collect()),
+            None => c.clone()
+        }).collect();
+        let result = solve_projection(&mut reduced, &pinned, projection);
+        working = result.get_edge_flows().iter().map(|(e, flow)| (e.get_id(), *flow)).collect();
+
+        if path_ids(&pairs) == previous || rounds >= settings.rounds {
+            break (reduced, result, reservations);
+        }
+    };
+
+    let objective = link_utilization(&reduced, result.get_edge_flows()).cost(cost_model);
+    let total: Vec<(DirectedEdge, f64)> = graph_adj.edges().into_iter()
+        .map(|e| {
+            let id = e.get_id();
+            (e, working.get(&id).copied().unwrap_or(0f64) + reservations.get(&id).copied().unwrap_or(0f64))
+        })
+        .collect();
+    let protected: Vec<ProtectedDemand> = pairs.iter()
+        .map(|(&j, (primary, backup))| ProtectedDemand { index: j as i32, primary: primary.clone(), backup: backup.clone() })
+        .collect();
+    let table = protected.iter()
+        .map(|p| (commodities[p.index as usize].get_id(), graph_adj.path_to_labels(&p.primary), graph_adj.path_to_labels(&p.backup)))
+        .collect();
+    let unprotected = critical.iter()
+        .filter(|j| !pairs.contains_key(j))
+        .map(|&j| commodities[j].get_id())
+        .collect();
+
+    SurvivableRouting {
+        protected,
+        table,
+        unprotected,
+        solution: result.get_solution().clone(),
+        reservations: graph_adj.edges().into_iter()
+            .filter_map(|e| reservations.get(&e.get_id()).map(|&r| (e, r)))
+            .filter(|(_, r)| *r > 0f64)
+            .collect(),
+        utilization: link_utilization(graph_adj, &total),
+        objective,
+        rounds
+    }
+}
+
+/// пары путей в виде id дуг, чтобы сравнивать пары между раундами
+fn path_ids(pairs: &BTreeMap<usize, (Vec<DirectedEdge>, Vec<DirectedEdge>)>) -> BTreeMap<usize, (Vec<i32>, Vec<i32>)> {
+    pairs.iter()
+        .map(|(&j, (primary, backup))| (j, (primary.iter().map(|e| e.get_id()).collect(), backup.iter().map(|e| e.get_id()).collect())))
+        .collect()
+}
+
+/// резерв пропускной способности на каждой дуге (по id) под резервные пути
+fn reserve(
+    graph_adj: &EdgeWeightedDigraph,
+    commodities: &[Demand],
+    pairs: &BTreeMap<usize, (Vec<DirectedEdge>, Vec<DirectedEdge>)>,
+    settings: &SurvivableSettings
+) -> BTreeMap<i32, f64> {
+    let mut reserved = BTreeMap::new();
+    match settings.protection {
+        Protection::Dedicated => {
+            for (&j, (_, backup)) in pairs {
+                for e in backup {
+                    *reserved.entry(e.get_id()).or_insert(0f64) += commodities[j].get_demand_vale();
+                }
+            }
+        }
+        Protection::Shared => {
+            // при отказе элемента основного пути commodity переходит на резервный путь
+            let mut by_failure: BTreeMap<FailedElement, BTreeMap<i32, f64>> = BTreeMap::new();
+            for (&j, (primary, backup)) in pairs {
+                for failure in primary_failures(graph_adj, primary, settings.disjointness) {
+                    let loads = by_failure.entry(failure).or_default();
+                    for e in backup {
+                        *loads.entry(e.get_id()).or_insert(0f64) += commodities[j].get_demand_vale();
+                    }
+                }
+            }
+            for loads in by_failure.values() {
+                for (&id, &load) in loads {
+                    let r = reserved.entry(id).or_insert(0f64);
+                    *r = f64::max(*r, load);
+                }
+            }
+        }
+    }
+    reserved
+}
+
+/// отказы, от которых защищает резервный путь: линки основного пути, при непересечении по вершинам - и его промежуточные вершины
+fn primary_failures(graph_adj: &EdgeWeightedDigraph, primary: &[DirectedEdge], disjointness: Disjointness) -> BTreeSet<FailedElement> {
+    let mut failures: BTreeSet<FailedElement> = primary.iter().map(|e| FailedElement::Link(graph_adj.get_edge_label(e))).collect();
+    if disjointness == Disjointness::Node {
+        // путь в обратном порядке: первая дуга входит в target, у последней from - source
+        failures.extend(primary.iter().take(primary.len().saturating_sub(1)).map(|e| FailedElement::Node(graph_adj.get_node_label(e.from()))));
+    }
+    failures
+}
+
+/// значение дуги вместе со встречной дугой, если они делят пропускную способность
+fn link_sum(graph_adj: &EdgeWeightedDigraph, values: &BTreeMap<i32, f64>, e: &DirectedEdge) -> f64 {
+    let own = values.get(&e.get_id()).copied().unwrap_or(0f64);
+    match graph_adj.get_shared_capacity_twin(e) {
+        Some(twin) => own + values.get(&twin.get_id()).copied().unwrap_or(0f64),
+        None => own
+    }
+}
+
+/// пропускная способность, которая остается рабочему потоку после резерва
+fn residual_capacity(graph_adj: &EdgeWeightedDigraph, reservations: &BTreeMap<i32, f64>, e: &DirectedEdge) -> f64 {
+    f64::max(e.get_capacity() - link_sum(graph_adj, reservations, e), MIN_RESIDUAL_SHARE * e.get_capacity())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dijkstra_find_path::TieBreak;
+
+    #[test]
+    fn zero_capacity_parallel_arc_does_not_hide_working_arc() {
+        let labels = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        // списки смежности отдают дуги в обратном порядке, рабочая дуга a->b идет раньше дуги без емкости
+        graph.graph_from_arcs(labels, &[(0, 1, 1f64, 0f64), (0, 1, 1f64, 10f64), (1, 2, 1f64, 10f64), (0, 2, 1f64, 10f64)]);
+        let commodities = vec![Demand::new("d".to_string(), "a".to_string(), "c".to_string(), 1f64)];
+        let projection = ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent);
+        let settings = SurvivableSettings::new(Disjointness::Link, Protection::Dedicated);
+
+        let routing = route_survivable(&graph, &commodities, &projection, &settings);
+        assert!(routing.unprotected.is_empty());
+        assert_eq!(routing.protected.len(), 1);
+        let protected = &routing.protected[0];
+        assert!(protected.primary.iter().chain(&protected.backup).all(|e| e.get_capacity() > 0f64));
+    }
+}
@@ -0,0 +1,247 @@
+use std::fs;
+
+use ndarray::Array1;
+
+use crate::bush_assignment::ArcCosts;
+use crate::frank_wolf::Objective;
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::structure_xml::Demand;
+
+/// Функция задержки BPR: t(x) = t0 * (1 + b * (x / c)^power)
+#[derive(Debug, Clone, Copy)]
+pub struct BprLink {
+    free_flow_time: f64,
+    b: f64,
+    capacity: f64,
+    power: f64
+}
+
+impl BprLink {
+
+    pub fn new(free_flow_time: f64, b: f64, capacity: f64, power: f64) -> BprLink {
+        BprLink {
+            free_flow_time,
+            b,
+            capacity,
+            power
+        }
+    }
+
+    fn ratio(&self, x: f64) -> f64 {
+        if self.capacity > 0f64 { f64::max(x, 0f64) / self.capacity } else { 0f64 }
+    }
+
+    pub fn time(&self, x: f64) -> f64 {
+        self.free_flow_time * (1f64 + self.b * self.ratio(x).powf(self.power))
+    }
+
+    pub fn derivative(&self, x: f64) -> f64 {
+        if self.power == 0f64 || self.capacity <= 0f64 {
+            return 0f64;
+        }
+        self.free_flow_time * self.b * self.power / self.capacity * self.ratio(x).powf(self.power - 1f64)
+    }
+
+    /// интеграл t от 0 до x, слагаемое функции Бекманна
+    pub fn integral(&self, x: f64) -> f64 {
+        let x = f64::max(x, 0f64);
+        self.free_flow_time * (x + self.b * self.capacity / (self.power + 1f64) * self.ratio(x).powf(self.power + 1f64))
+    }
+}
+
+/// Функция Бекманна: сумма интегралов BPR по дугам, ее минимум - равновесие пользователей (Wardrop).
+/// Вектор потоков индексируется id дуги
+#[derive(Debug, Clone)]
+pub struct BprCost {
+    links: Vec<BprLink>
+}
+
+impl BprCost {
+
+    pub fn new(links: Vec<BprLink>) -> BprCost {
+        BprCost {
+            links
+        }
+    }
+
+    pub fn get_links(&self) -> &[BprLink] {
+        &self.links
+    }
+}
+
+impl Objective for BprCost {
+    fn value(&self, x: &Array1<f64>) -> f64 {
+        self.links.iter().zip(x).fold(0f64, |sum, (l, &x)| sum + l.integral(x))
+    }
+
+    fn gradient(&self, x: &Array1<f64>) -> Array1<f64> {
+        Array1::from_iter(self.links.iter().zip(x).map(|(l, &x)| l.time(x)))
+    }
+
+    fn hessian_product(&self, x: &Array1<f64>, v: &Array1<f64>) -> Array1<f64> {
+        Array1::from_iter(self.links.iter().zip(x).zip(v).map(|((l, &x), &v)| l.derivative(x) * v))
+    }
+}
+
+impl ArcCosts for BprCost {
+    fn objective(&self, x: &[f64]) -> f64 {
+        self.links.iter().zip(x).fold(0f64, |sum, (l, &x)| sum + l.integral(x))
+    }
+
+    fn cost(&self, x: &[f64], arc: usize) -> f64 {
+        self.links[arc].time(x[arc])
+    }
+
+    fn derivative(&self, x: &[f64], arc: usize) -> f64 {
+        self.links[arc].derivative(x[arc])
+    }
+}
+
+/// Сеть в формате TNTP (Transportation Networks for Research): граф, функции BPR дуг и матрица корреспонденций
+#[derive(Debug, Clone)]
+pub struct TntpNetwork {
+    graph: EdgeWeightedDigraph,
+    costs: BprCost,
+    first_thru_node: i32, // номер первой вершины, через которую можно проезжать транзитом, меньшие - зоны
+    commodities: Vec<Demand>
+}
+
+impl TntpNetwork {
+
+    pub fn get_graph(&self) -> &EdgeWeightedDigraph {
+        &self.graph
+    }
+
+    pub fn get_costs(&self) -> &BprCost {
+        &self.costs
+    }
+
+    /// номер вершины графа (с 0), начиная с которого разрешен транзит
+    pub fn get_first_thru_node(&self) -> i32 {
+        self.first_thru_node
+    }
+
+    pub fn get_commodities(&self) -> &[Demand] {
+        &self.commodities
+    }
+
+    /// Потоки в формате файлов решений TNTP: "from to volume cost" по дуге
+    pub fn write_flows(&self, edge_flows: &[(DirectedEdge, f64)], path: &str) {
+        let mut data = String::from("From\tTo\tVolume\tCost\n");
+        for (e, flow) in edge_flows {
+            data.push_str(&format!("{}\t{}\t{}\t{}\n", self.graph.get_node_label(e.from()), self.graph.get_node_label(e.to()),
+                                   flow, self.costs.get_links()[e.get_id() as usize].time(*flow)));
+        }
+        fs::write(path, data).expect("Не удалось записать файл потоков");
+    }
+}
+
+/// Чтение сети TNTP: файл сети (_net.tntp) и файл корреспонденций (_trips.tntp). Вершины нумеруются с 1,
+/// в графе вершина k получает номер k - 1 и метку "k"
+pub fn read_tntp(net_path: &str, trips_path: &str) -> TntpNetwork {
+    let net = fs::read_to_string(net_path).expect("Не удалось прочитать файл сети TNTP");
+    let (metadata, body) = split_metadata(&net);
+    let node_count = metadata_value(&metadata, "NUMBER OF NODES").expect("В файле сети TNTP нет <NUMBER OF NODES>") as i32;
+    let first_thru_node = metadata_value(&metadata, "FIRST THRU NODE").map_or(1, |v| v as i32);
+
+    let mut arcs = vec![];
+    let mut links = vec![];
+    for line in body.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('~')) {
+        let fields: Vec<&str> = line.trim_end_matches(';').split_whitespace().collect();
+        if fields.len() < 7 {
+            panic!("Неверная строка в файле сети TNTP: {}", line);
+        }
+        let number = |k: usize| fields[k].parse::<f64>().unwrap_or_else(|_| panic!("Неверное число в файле сети TNTP: {}", line));
+        let node = |k: usize| {
+            let v = fields[k].parse::<i32>().unwrap_or_else(|_| panic!("Неверная вершина в файле сети TNTP: {}", line));
+            if v < 1 || v > node_count {
+                panic!("Вершина {} вне сети TNTP", v);
+            }
+            v - 1
+        };
+        let (capacity, free_flow_time, b, power) = (number(2), number(4), number(5), number(6));
+        arcs.push((node(0), node(1), free_flow_time, capacity));
+        links.push(BprLink::new(free_flow_time, b, capacity, power));
+    }
+    let mut graph = EdgeWeightedDigraph::default_graph();
+    graph.graph_from_arcs((1..=node_count).map(|v| v.to_string()).collect(), &arcs).compact();
+
+    TntpNetwork {
+        graph,
+        costs: BprCost::new(links),
+        first_thru_node: first_thru_node - 1,
+        commodities: read_trips(trips_path, node_count)
+    }
+}
+
+/// корреспонденции: "Origin k", затем пары "j : demand;", нулевые корреспонденции пропускаются
+fn read_trips(path: &str, node_count: i32) -> Vec<Demand> {
+    let trips = fs::read_to_string(path).expect("Не удалось прочитать файл корреспонденций TNTP");
+    let (_, body) = split_metadata(&trips);
+    let body: String = body.lines()
+        .filter(|l| !l.trim_start().starts_with('~'))
+        .map(|l| l.replace([':', ';'], " ") + "\n")
+        .collect();
+    let mut tokens = body.split_whitespace();
+    let mut origin = None;
+    let mut commodities = vec![];
+    while let Some(token) = tokens.next() {
+        if token == "Origin" {
+            origin = Some(tokens.next().expect("После Origin нет номера вершины").to_string());
+            continue;
+        }
+        let source = origin.clone().expect("Корреспонденция до первой строки Origin");
+        let value = tokens.next().and_then(|v| v.parse::<f64>().ok()).expect("Неверная корреспонденция в файле TNTP");
+        let target = token.parse::<i32>().ok().filter(|&t| t >= 1 && t <= node_count)
+            .unwrap_or_else(|| panic!("Неверная вершина {} в файле корреспонденций TNTP", token));
+        if value > 0f64 && source != target.to_string() {
+            commodities.push(Demand::new(format!("{}_{}", source, target), source, target.to_string(), value));
+        }
+    }
+    commodities
+}
+
+/// строки метаданных <KEY> value до <END OF METADATA> и остальная часть файла
+fn split_metadata(data: &str) -> (Vec<(String, String)>, &str) {
+    let end = data.find("<END OF METADATA>").map_or(0, |i| i + "<END OF METADATA>".len());
+    let metadata = data[..end].lines()
+        .filter_map(|l| {
+            let l = l.trim();
+            let close = l.find('>')?;
+            Some((l[1..close].trim().to_string(), l[close + 1..].trim().to_string()))
+        })
+        .collect();
+    (metadata, &data[end..])
+}
+
+fn metadata_value(metadata: &[(String, String)], key: &str) -> Option<f64> {
+    metadata.iter().find(|(k, _)| k == key).and_then(|(_, v)| v.parse::<f64>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_braess_network_with_known_equilibrium() {
+        let network = read_tntp("data/braess_net.tntp", "data/braess_trips.tntp");
+        assert_eq!(network.get_graph().get_v_count(), 4);
+        assert_eq!(network.get_first_thru_node(), 0);
+        // нулевые корреспонденции пропускаются
+        let commodities = network.get_commodities();
+        assert_eq!(commodities.len(), 1);
+        assert_eq!((commodities[0].get_source(), commodities[0].get_target()), ("1".to_string(), "4".to_string()));
+        assert_eq!(commodities[0].get_demand_vale(), 6f64);
+
+        // дуги в порядке файла: 1-2, 1-3, 2-3, 2-4, 3-4; в равновесии все три пути стоят 97.5
+        let x = Array1::from(vec![3.5, 2.5, 1f64, 2.5, 3.5]);
+        let times = network.get_costs().gradient(&x);
+        for (time, expected) in times.iter().zip([45f64, 52.5, 7.5, 52.5, 45f64]) {
+            assert!((time - expected).abs() < 1e-12, "{} != {}", time, expected);
+        }
+        for route in [vec![0, 3], vec![1, 4], vec![0, 2, 4]] {
+            assert!((route.iter().map(|&a| times[a]).sum::<f64>() - 97.5).abs() < 1e-12);
+        }
+        assert!((network.get_costs().value(&x) - 455.75).abs() < 1e-12);
+    }
+}
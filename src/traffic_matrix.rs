@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+use ndarray::{Array1, Array2, Axis};
+
+use crate::dijkstra_find_path::DijkstraSP;
+use crate::flow_solution::FlowSolution;
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::structure_xml::{Demand, Demands};
+
+/// нижняя граница оценки при энтропийной априорной матрице (доля среднего трафика пары), чтобы логарифм был определен
+const ENTROPY_FLOOR_SHARE: f64 = 1e-6;
+
+/// Матрица маршрутизации: элемент (r, k) - доля трафика пары k, которая проходит через линк r.
+/// Строки - линки в том же виде, что и в link_utilization: дуга или, для модели UNDIRECTED, обе дуги линка вместе
+#[derive(Debug, Clone)]
+pub struct RoutingMatrix {
+    links: Vec<(String, String, String)>, // линк, откуда, куда
+    shared: Vec<bool>,                    // строка объединяет обе дуги линка UNDIRECTED
+    pairs: Vec<(String, String)>,         // source и target каждого столбца
+    ends: Vec<(i32, i32)>,
+    row_of: HashMap<i32, usize>,          // id дуги -> строка
+    matrix: Array2<f64>
+}
+
+impl RoutingMatrix {
+
+    /// строки по работающим дугам графа, столбцы - все упорядоченные пары работающих вершин
+    fn empty(graph_adj: &EdgeWeightedDigraph) -> RoutingMatrix {
+        let mut links = vec![];
+        let mut shared = vec![];
+        let mut row_of = HashMap::new();
+        for e in graph_adj.edges() {
+            if !graph_adj.is_edge_enabled(&e) || row_of.contains_key(&e.get_id()) {
+                continue;
+            }
+            row_of.insert(e.get_id(), links.len());
+            let twin = graph_adj.get_shared_capacity_twin(&e);
+            if let Some(twin) = twin {
+                row_of.insert(twin.get_id(), links.len());
+            }
+            shared.push(twin.is_some());
+            links.push((graph_adj.get_edge_label(&e), graph_adj.get_node_label(e.from()), graph_adj.get_node_label(e.to())));
+        }
+        let nodes: Vec<i32> = (0..graph_adj.get_v_count()).filter(|&v| graph_adj.is_node_enabled(v)).collect();
+        let ends: Vec<(i32, i32)> = nodes.iter()
+            .flat_map(|&s| nodes.iter().filter(move |&&t| t != s).map(move |&t| (s, t)))
+            .collect();
+        RoutingMatrix {
+            matrix: Array2::zeros((links.len(), ends.len())),
+            links,
+            shared,
+            pairs: ends.iter().map(|&(s, t)| (graph_adj.get_node_label(s), graph_adj.get_node_label(t))).collect(),
+            ends,
+            row_of
+        }
+    }
+
+    /// маршрутизация по кратчайшим путям при текущих стоимостях дуг графа
+    pub fn shortest_path(graph_adj: &EdgeWeightedDigraph) -> RoutingMatrix {
+        let mut routing = Self::empty(graph_adj);
+        let mut graph = graph_adj.clone();
+        let mut sp: Option<(i32, DijkstraSP)> = None;
+        for k in 0..routing.ends.len() {
+            let (s, t) = routing.ends[k];
+            if sp.as_ref().is_none_or(|(source, _)| *source != s) {
+                sp = Some((s, DijkstraSP::dijkstra(&mut graph, s)));
+            }
+            if let Some(path) = sp.as_ref().and_then(|(_, sp)| sp.path_to(t as usize)) {
+                routing.add_path(k, &path, 1f64);
+            }
+        }
+        routing
+    }
+
+    /// маршрутизация по решению задачи распределения потоков: доли путей каждой пары source-target;
+    /// пары, которых нет в решении или пути которых не проходят по графу, идут по кратчайшим путям
+    pub fn from_solution(graph_adj: &EdgeWeightedDigraph, solution: &FlowSolution) -> RoutingMatrix {
+        let mut routing = Self::shortest_path(graph_adj);
+        let column: HashMap<(String, String), usize> = routing.pairs.iter().cloned().enumerate().map(|(k, pair)| (pair, k)).collect();
+        let mut paths: HashMap<usize, Vec<(Vec<DirectedEdge>, f64)>> = HashMap::new();
+        for commodity in solution.get_commodities() {
+            let k = match column.get(&(commodity.get_source().to_string(), commodity.get_target().to_string())) {
+                Some(&k) => k,
+                None => continue
+            };
+            for path in commodity.get_paths().iter().filter(|p| p.get_flow() > 0f64) {
+                if let Some(edges) = path.to_edges(graph_adj) {
+                    paths.entry(k).or_default().push((edges, path.get_flow()));
+                }
+            }
+        }
+        for (k, paths) in paths {
+            let total = paths.iter().fold(0f64, |sum, (_, flow)| sum + flow);
+            routing.matrix.column_mut(k).fill(0f64);
+            for (path, flow) in &paths {
+                routing.add_path(k, path, flow / total);
+            }
+        }
+        routing
+    }
+
+    fn add_path(&mut self, k: usize, path: &[DirectedEdge], share: f64) {
+        for e in path {
+            if let Some(&r) = self.row_of.get(&e.get_id()) {
+                self.matrix[[r, k]] += share;
+            }
+        }
+    }
+
+    /// нагрузка каждой строки по потокам на дугах
+    pub fn loads_from_edge_flows(&self, edge_flows: &[(DirectedEdge, f64)]) -> Vec<f64> {
+        let mut loads = vec![0f64; self.links.len()];
+        for (e, flow) in edge_flows {
+            if let Some(&r) = self.row_of.get(&e.get_id()) {
+                loads[r] += flow;
+            }
+        }
+        loads
+    }
+
+    /// Счетчики нагрузки линков из файла: строка "<линк> <нагрузка>" для линка из одной строки матрицы
+    /// или "<линк> <откуда> <куда> <нагрузка>" для одного направления; пустые строки и строки с # пропускаются.
+    /// Строки матрицы без счетчика считаются неизмеренными
+    pub fn read_link_loads(&self, path: &str) -> Vec<Option<f64>> {
+        let data = fs::read_to_string(path).expect("Не удалось прочитать файл нагрузки линков");
+        let mut loads = vec![None; self.links.len()];
+        for line in data.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let load = fields.last().unwrap().parse::<f64>().expect("Неверная нагрузка в файле нагрузки линков");
+            let rows: Vec<usize> = (0..self.links.len())
+                .filter(|&r| {
+                    let (link, from, to) = &self.links[r];
+                    match fields.len() {
+                        2 => link == fields[0],
+                        4 => link == fields[0] && from == fields[1] && to == fields[2],
+                        _ => panic!("Неверная строка в файле нагрузки линков: {}", line)
+                    }
+                })
+                .collect();
+            match rows[..] {
+                [r] => loads[r] = Some(load),
+                [] => panic!("Линк {} не найден", line),
+                _ => panic!("У линка {} несколько направлений, нужно указать откуда и куда", fields[0])
+            }
+        }
+        loads
+    }
+
+    /// запись нагрузки в формате read_link_loads, по строке на каждое направление
+    pub fn write_link_loads(&self, loads: &[f64], path: &str) {
+        let mut data = String::new();
+        for ((link, from, to), load) in self.links.iter().zip(loads) {
+            data.push_str(&format!("{} {} {} {}\n", link, from, to, load));
+        }
+        fs::write(path, data).expect("Не удалось записать файл нагрузки линков");
+    }
+}
+
+/// Априорная матрица трафика, к которой оценка стягивается там, где нагрузок линков недостаточно
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrafficPrior {
+    /// гравитационная модель, регуляризация - квадрат отклонения от априорной матрицы
+    Gravity,
+    /// гравитационная модель, регуляризация - расстояние Кульбака-Лейблера до априорной матрицы
+    Entropy,
+}
+
+impl FromStr for TrafficPrior {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "gravity" => Ok(TrafficPrior::Gravity),
+            "entropy" => Ok(TrafficPrior::Entropy),
+            other => Err(format!("Неизвестная априорная модель трафика: {}", other))
+        }
+    }
+}
+
+/// Параметры оценки матрицы трафика
+#[derive(Debug, Clone, Copy)]
+pub struct EstimationSettings {
+    prior: TrafficPrior,
+    lambda: f64,         // вес регуляризации относительно невязки по нагрузкам линков
+    max_iterations: i32,
+    tolerance: f64       // относительное изменение целевой функции, при котором расчет останавливается
+}
+
+impl EstimationSettings {
+
+    pub fn new(prior: TrafficPrior) -> EstimationSettings {
+        EstimationSettings {
+            prior,
+            lambda: 0.01,
+            max_iterations: 5000,
+            tolerance: 1e-10
+        }
+    }
+
+    pub fn with_lambda(mut self, lambda: f64) -> EstimationSettings {
+        self.lambda = lambda;
+        self
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: i32) -> EstimationSettings {
+        self.max_iterations = max_iterations;
+        self
+    }
+}
+
+/// Оценка матрицы трафика
+#[derive(Debug, Clone)]
+pub struct TrafficMatrixEstimate {
+    pairs: Vec<(String, String)>,
+    estimate: Vec<f64>,
+    prior: Vec<f64>,   // априорная (гравитационная) матрица
+    residual: f64,     // относительная невязка |Ax - y| / |y| по измеренным линкам
+    iterations: i32
+}
+
+impl TrafficMatrixEstimate {
+
+    pub fn get_pairs(&self) -> &[(String, String)] {
+        &self.pairs
+    }
+
+    /// оценка трафика каждой пары, в порядке get_pairs
+    pub fn get_estimate(&self) -> &[f64] {
+        &self.estimate
+    }
+
+    /// оценка в виде demands SNDlib, пары с нулевым трафиком пропускаются
+    pub fn to_demands(&self) -> Demands {
+        Demands::new(self.pairs.iter().zip(&self.estimate)
+            .filter(|(_, &value)| value > 0f64)
+            .map(|((s, t), &value)| Demand::new(format!("{}_{}", s, t), s.clone(), t.clone(), value))
+            .collect())
+    }
+}
+
+impl fmt::Display for TrafficMatrixEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (((s, t), estimate), prior) in self.pairs.iter().zip(&self.estimate).zip(&self.prior) {
+            writeln!(f, "{} -> {}: estimate: {:.2}, prior: {:.2}", s, t, estimate, prior)?;
+        }
+        writeln!(f, "relative residual: {:.6}, iterations: {}", self.residual, self.iterations)
+    }
+}
+
+/// Оценка матрицы трафика по измеренным нагрузкам линков (сетевая томография):
+/// min |Ax - y|^2 / |y|^2 + lambda * R(x), x >= 0, где A - матрица маршрутизации по измеренным линкам,
+/// R - отклонение от гравитационной априорной матрицы (квадратичное или энтропийное).
+/// Метод проекции градиента на неотрицательный ортант с подбором шага дроблением
+/// (проекция на ортант - покомпонентный max). Движок frank_wolf здесь не подходит: условному градиенту
+/// нужна линейная минимизация по ограниченному множеству, а неотрицательный ортант не ограничен
+pub fn estimate_traffic_matrix(routing: &RoutingMatrix, measured: &[Option<f64>], settings: &EstimationSettings) -> TrafficMatrixEstimate {
+    let rows: Vec<usize> = (0..measured.len()).filter(|&r| measured[r].is_some()).collect();
+    let a = routing.matrix.select(Axis(0), &rows);
+    let y = Array1::from(rows.iter().map(|&r| measured[r].unwrap()).collect::<Vec<f64>>());
+    let y_norm = f64::max(y.dot(&y), f64::MIN_POSITIVE);
+
+    // пары, трафик которых не проходит ни по одному измеренному линку, ненаблюдаемы и остаются нулевыми
+    let active: Vec<bool> = a.columns().into_iter().map(|c| c.sum() > 0f64).collect();
+    let mut prior = gravity_prior(routing, measured, &a, &active);
+    let floor = match settings.prior {
+        TrafficPrior::Gravity => 0f64,
+        TrafficPrior::Entropy => {
+            let count = active.iter().filter(|&&a| a).count().max(1) as f64;
+            ENTROPY_FLOOR_SHARE * prior.sum() / count
+        }
+    };
+    for (k, p) in prior.iter_mut().enumerate() {
+        *p = if active[k] { f64::max(*p, floor) } else { 0f64 };
+    }
+    let prior_norm = f64::max(prior.dot(&prior), f64::MIN_POSITIVE);
+    let prior_sum = f64::max(prior.sum(), f64::MIN_POSITIVE);
+    let lambda = settings.lambda;
+
+    let objective = |x: &Array1<f64>| {
+        let r = a.dot(x) - &y;
+        let regularization = match settings.prior {
+            TrafficPrior::Gravity => (x - &prior).mapv(|d| d * d).sum() / prior_norm,
+            TrafficPrior::Entropy => x.iter().zip(&prior)
+                .filter(|(_, &p)| p > 0f64)
+                .map(|(&x, &p)| x * (x / p).ln() - x + p)
+                .sum::<f64>() / prior_sum
+        };
+        r.dot(&r) / y_norm + lambda * regularization
+    };
+    let gradient = |x: &Array1<f64>| {
+        let data = a.t().dot(&(a.dot(x) - &y)) * (2f64 / y_norm);
+        let regularization = match settings.prior {
+            TrafficPrior::Gravity => (x - &prior) * (2f64 / prior_norm),
+            TrafficPrior::Entropy => Array1::from_iter(x.iter().zip(&prior)
+                .map(|(&x, &p)| if p > 0f64 { (x / p).ln() / prior_sum } else { 0f64 }))
+        };
+        data + regularization * lambda
+    };
+    let project = |x: Array1<f64>| Array1::from_iter(x.iter().enumerate().map(|(k, &v)| if active[k] { f64::max(v, floor) } else { 0f64 }));
+
+    // начальный шаг 1 / L по оценке |A|^2 <= |A|_1 * |A|_inf, дальше шаг подбирается дроблением
+    let norm_1 = a.columns().into_iter().map(|c| c.sum()).fold(0f64, f64::max);
+    let norm_inf = a.rows().into_iter().map(|r| r.sum()).fold(0f64, f64::max);
+    let mut step = 1f64 / f64::max(2f64 * norm_1 * norm_inf / y_norm + 2f64 * lambda / prior_norm, f64::MIN_POSITIVE);
+
+    let mut x = prior.clone();
+    let mut value = objective(&x);
+    let mut iterations = 0;
+    while iterations < settings.max_iterations {
+        iterations += 1;
+        let g = gradient(&x);
+        let (next, next_value) = loop {
+            let candidate = project(&x - &(&g * step));
+            let diff = &candidate - &x;
+            let candidate_value = objective(&candidate);
+            if candidate_value <= value + g.dot(&diff) + diff.dot(&diff) / (2f64 * step) || step < f64::MIN_POSITIVE {
+                break (candidate, candidate_value);
+            }
+            step /= 2f64;
+        };
+        let converged = (value - next_value).abs() <= settings.tolerance * f64::max(value, f64::MIN_POSITIVE);
+        x = next;
+        value = next_value;
+        if converged {
+            break;
+        }
+        step *= 1.5;
+    }
+
+    let r = a.dot(&x) - &y;
+    TrafficMatrixEstimate {
+        pairs: routing.pairs.clone(),
+        estimate: x.to_vec(),
+        prior: prior.to_vec(),
+        residual: (r.dot(&r) / y_norm).sqrt(),
+        iterations
+    }
+}
+
+/// Гравитационная модель: трафик пары s-t пропорционален исходящей нагрузке s и входящей нагрузке t,
+/// которые грубо оцениваются по измеренным линкам, инцидентным вершинам. Суммарный трафик -
+/// сумма нагрузок, деленная на среднее число измеренных линков на пути пары
+fn gravity_prior(routing: &RoutingMatrix, measured: &[Option<f64>], a: &Array2<f64>, active: &[bool]) -> Array1<f64> {
+    let mut out: HashMap<&str, f64> = HashMap::new();
+    let mut inc: HashMap<&str, f64> = HashMap::new();
+    for (r, load) in measured.iter().enumerate() {
+        let load = match load {
+            Some(load) => *load,
+            None => continue
+        };
+        let (_, from, to) = &routing.links[r];
+        if routing.shared[r] {
+            // у линка UNDIRECTED направление счетчика неизвестно, нагрузка делится поровну
+            for v in [from, to] {
+                *out.entry(v).or_insert(0f64) += load / 2f64;
+                *inc.entry(v).or_insert(0f64) += load / 2f64;
+            }
+        } else {
+            *out.entry(from).or_insert(0f64) += load;
+            *inc.entry(to).or_insert(0f64) += load;
+        }
+    }
+    let count = active.iter().filter(|&&a| a).count();
+    let links_per_pair = a.sum() / f64::max(count as f64, 1f64);
+    let total = measured.iter().flatten().sum::<f64>() / f64::max(links_per_pair, f64::MIN_POSITIVE);
+
+    let weights = Array1::from_iter(routing.pairs.iter().enumerate().map(|(k, (s, t))| {
+        if active[k] {
+            out.get(s.as_str()).copied().unwrap_or(0f64) * inc.get(t.as_str()).copied().unwrap_or(0f64)
+        } else {
+            0f64
+        }
+    }));
+    let weight_sum = weights.sum();
+    if weight_sum > 0f64 {
+        weights * (total / weight_sum)
+    } else {
+        Array1::from_iter(active.iter().map(|&a| if a { total / count as f64 } else { 0f64 }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// измеренные нагрузки всех линков при маршрутизации матрицы traffic
+    fn link_loads(routing: &RoutingMatrix, traffic: &[f64]) -> Vec<Option<f64>> {
+        routing.matrix.dot(&Array1::from(traffic.to_vec())).iter().map(|&load| Some(load)).collect()
+    }
+
+    fn graph(labels: &[&str], links: &[(i32, i32)]) -> EdgeWeightedDigraph {
+        let arcs: Vec<(i32, i32, f64, f64)> = links.iter()
+            .flat_map(|&(u, v)| [(u, v, 1f64, 100f64), (v, u, 1f64, 100f64)])
+            .collect();
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(labels.iter().map(|l| l.to_string()).collect(), &arcs);
+        graph
+    }
+
+    #[test]
+    fn recovers_matrix_when_every_pair_has_its_own_link() {
+        // в треугольнике каждая пара идет по своей дуге, матрица трафика однозначно определяется нагрузками
+        let graph = graph(&["a", "b", "c"], &[(0, 1), (1, 2), (0, 2)]);
+        let routing = RoutingMatrix::shortest_path(&graph);
+        let traffic = [5f64, 1f64, 2f64, 8f64, 3f64, 4f64];
+        let loads = link_loads(&routing, &traffic);
+
+        let settings = EstimationSettings::new(TrafficPrior::Gravity).with_lambda(1e-6);
+        let estimate = estimate_traffic_matrix(&routing, &loads, &settings);
+        assert!(estimate.residual < 1e-3, "{}", estimate.residual);
+        for (x, t) in estimate.get_estimate().iter().zip(traffic) {
+            assert!(*x >= 0f64);
+            assert!((x - t).abs() < 1e-2 * t, "{} != {}", x, t);
+        }
+    }
+
+    #[test]
+    fn estimate_is_nonnegative_and_fits_loads_on_underdetermined_network() {
+        // цепочка a - b - c: 6 пар на 4 дуги, нагрузки не определяют матрицу однозначно
+        let graph = graph(&["a", "b", "c"], &[(0, 1), (1, 2)]);
+        let routing = RoutingMatrix::shortest_path(&graph);
+        let traffic = [4f64, 0f64, 6f64, 1f64, 2f64, 7f64];
+        let loads = link_loads(&routing, &traffic);
+
+        for prior in [TrafficPrior::Gravity, TrafficPrior::Entropy] {
+            let estimate = estimate_traffic_matrix(&routing, &loads, &EstimationSettings::new(prior));
+            assert!(estimate.get_estimate().iter().all(|&x| x >= 0f64));
+            assert!(estimate.residual < 0.05, "{:?}: {}", prior, estimate.residual);
+            let fitted = link_loads(&routing, estimate.get_estimate());
+            for (f, l) in fitted.iter().zip(&loads) {
+                assert!((f.unwrap() - l.unwrap()).abs() < 0.1 * l.unwrap(), "{:?}: {:?} != {:?}", prior, f, l);
+            }
+        }
+    }
+}
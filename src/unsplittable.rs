@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::delay_func_count::CostModel;
+use crate::constrained_path::PathConstraint;
+use crate::dijkstra_find_path::TieBreak;
+use crate::flow_solution::{CommodityFlow, FlowSolution};
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::link_utilization::link_utilization;
+use crate::projection_solver::ProjectionResult;
+use crate::random::XorShiftRng;
+use crate::structure_xml::Demand;
+
+/// Маршрутизация без разделения потока: каждый commodity идет по одному пути (LSP MPLS)
+#[derive(Debug, Clone)]
+pub struct UnsplittableRouting {
+    solution: FlowSolution,
+    objective: f64,
+    rounded_objective: f64,     // значение целевой функции после округления, до локального поиска
+    fractional_objective: f64,  // значение целевой функции дробного решения, с ним сравнивается решение с одним путем
+    lower_bound: f64,           // нижняя оценка дробного оптимума: f(x) + min_y grad f(x) * (y - x) в дробном решении x
+    moves: i32
+}
+
+impl UnsplittableRouting {
+
+    /// относительный проигрыш дробному решению, может быть отрицательным, если метод проекции остановился далеко от оптимума
+    pub fn get_gap(&self) -> f64 {
+        (self.objective - self.fractional_objective) / self.fractional_objective
+    }
+
+    /// относительный проигрыш нижней оценке дробного оптимума, не меньше настоящего проигрыша оптимуму
+    pub fn get_gap_to_bound(&self) -> f64 {
+        (self.objective - self.lower_bound) / self.lower_bound
+    }
+}
+
+impl fmt::Display for UnsplittableRouting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.solution)?;
+        writeln!(f, "objective: {}, after rounding: {}, moves: {}", self.objective, self.rounded_objective, self.moves)?;
+        writeln!(f, "fractional: {}, gap: {:.4}%, fractional lower bound: {}, gap to bound: {:.4}%",
+                 self.fractional_objective, self.get_gap() * 100f64, self.lower_bound, self.get_gap_to_bound() * 100f64)
+    }
+}
+
+/// Загрузка ресурсов сети: ресурс - дуга или линк UNDIRECTED, дуги которого делят одну емкость
+struct ResourceLoads {
+    resource_of: HashMap<i32, usize>,
+    capacity: Vec<f64>,
+    load: Vec<f64>
+}
+
+impl ResourceLoads {
+
+    fn new(graph_adj: &EdgeWeightedDigraph, edges: &[DirectedEdge]) -> ResourceLoads {
+        let mut resources = ResourceLoads {
+            resource_of: HashMap::new(),
+            capacity: vec![],
+            load: vec![]
+        };
+        for e in edges {
+            let twin = graph_adj.get_shared_capacity_twin(e).and_then(|t| resources.resource_of.get(&t.get_id()).copied());
+            let r = twin.unwrap_or_else(|| {
+                resources.capacity.push(e.get_capacity());
+                resources.load.push(0f64);
+                resources.capacity.len() - 1
+            });
+            resources.resource_of.insert(e.get_id(), r);
+        }
+        resources
+    }
+
+    fn add_path(&mut self, path: &[DirectedEdge], flow: f64) {
+        for e in path {
+            self.load[self.resource_of[&e.get_id()]] += flow;
+        }
+    }
+
+    /// на сколько вырастет целевая функция, если добавить flow на дугу e
+    fn increment(&self, cost_model: CostModel, e: &DirectedEdge, flow: f64) -> f64 {
+        let r = self.resource_of[&e.get_id()];
+        cost_model.value(self.load[r] + flow, self.capacity[r]) - cost_model.value(self.load[r], self.capacity[r])
+    }
+
+    fn derivative(&self, cost_model: CostModel, e: &DirectedEdge) -> f64 {
+        let r = self.resource_of[&e.get_id()];
+        cost_model.first_derivative(self.load[r], self.capacity[r])
+    }
+
+    fn objective(&self, cost_model: CostModel) -> f64 {
+        self.load.iter().zip(&self.capacity).map(|(&x, &c)| cost_model.value(x, c)).sum()
+    }
+}
+
+/// Маршрутизация с одним путем на commodity: рандомизированное округление дробного решения метода проекции
+/// (путь выбирается с вероятностью, равной доле потока на нем, из trials попыток берется лучшая),
+/// затем локальный поиск перекладывает commodity на путь с наименьшим приращением целевой функции
+pub fn route_unsplittable(
+    graph_adj: &EdgeWeightedDigraph,
+    commodities: &[Demand],
+    fractional: &ProjectionResult,
+    cost_model: CostModel,
+    trials: i32,
+    seed: u64
+) -> UnsplittableRouting {
+    let edges: Vec<DirectedEdge> = graph_adj.edges().into_iter()
+        .filter(|e| graph_adj.is_edge_enabled(e) && e.get_capacity() > 0f64)
+        .collect();
+    let fractional_objective = link_utilization(graph_adj, fractional.get_edge_flows()).cost(cost_model);
+    let path_flows = fractional.get_path_flows();
+    let constraints: Vec<PathConstraint> = commodities.iter().map(|c| PathConstraint::for_demand(graph_adj, c)).collect();
+    let mut rng = XorShiftRng::new(seed);
+
+    let mut work = graph_adj.clone();
+    for e in graph_adj.edges() {
+        if e.get_capacity() <= 0f64 {
+            work.set_edge_enabled_by_id(e.get_id(), false);
+        }
+    }
+
+    // нижняя оценка дробного оптимума по дробному решению, в конце уточняется по найденному решению с одним путем
+    let mut fractional_loads = ResourceLoads::new(graph_adj, &edges);
+    for (e, flow) in fractional.get_edge_flows() {
+        if fractional_loads.resource_of.contains_key(&e.get_id()) {
+            fractional_loads.add_path(&[*e], *flow);
+        }
+    }
+    let fractional_bound = linearized_lower_bound(graph_adj, &mut work, &edges, commodities, &constraints, &fractional_loads, cost_model);
+
+    // рандомизированное округление
+    let mut best: Option<(f64, Vec<Option<Vec<DirectedEdge>>>)> = None;
+    for _ in 0..i32::max(1, trials) {
+        let mut loads = ResourceLoads::new(graph_adj, &edges);
+        let mut paths = vec![];
+        for (index, commodity) in commodities.iter().enumerate() {
+            let candidates = path_flows.get(index).map_or(&[][..], |p| &p[..]);
+            let total: f64 = candidates.iter().map(|(_, f)| f).sum();
+            if candidates.is_empty() || total <= 0f64 {
+                paths.push(None);
+                continue;
+            }
+            let mut u = rng.next_f64() * total;
+            let mut chosen = &candidates[candidates.len() - 1].0;
+            for (path, flow) in candidates {
+                if u < *flow {
+                    chosen = path;
+                    break;
+                }
+                u -= flow;
+            }
+            loads.add_path(chosen, commodity.get_demand_vale());
+            paths.push(Some(chosen.clone()));
+        }
+        let objective = loads.objective(cost_model);
+        if best.as_ref().is_none_or(|b| objective < b.0) {
+            best = Some((objective, paths));
+        }
+    }
+    let (rounded_objective, mut paths) = best.unwrap();
+
+    // локальный поиск: commodity снимается с пути и ставится на кратчайший путь по приращениям целевой функции,
+    // если это уменьшает целевую функцию; commodity с большим demand перекладываются первыми
+    let mut loads = ResourceLoads::new(graph_adj, &edges);
+    for (index, path) in paths.iter().enumerate() {
+        if let Some(path) = path {
+            loads.add_path(path, commodities[index].get_demand_vale());
+        }
+    }
+    let mut order: Vec<usize> = (0..commodities.len()).filter(|&j| paths[j].is_some()).collect();
+    order.sort_by(|&a, &b| commodities[b].get_demand_vale().total_cmp(&commodities[a].get_demand_vale()));
+
+    let mut moves = 0;
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for &j in &order {
+            let demand = commodities[j].get_demand_vale();
+            let current = paths[j].clone().unwrap();
+            loads.add_path(&current, -demand);
+            for e in &edges {
+                work.update_edge_by_id(e.get_id(), loads.increment(cost_model, e, demand));
+            }
+            let source = graph_adj.get_node_index(&commodities[j].get_source()).unwrap();
+            let target = graph_adj.get_node_index(&commodities[j].get_target()).unwrap();
+            let current_cost = work.path_cost(&current);
+            let candidate = constraints[j].shortest_path(&mut work, source, target, TieBreak::LowestEdgeIds)
+                .filter(|path| work.path_cost(path) < current_cost * (1f64 - 1e-9));
+            match candidate {
+                Some(path) => {
+                    loads.add_path(&path, demand);
+                    paths[j] = Some(path);
+                    moves += 1;
+                    improved = true;
+                }
+                None => loads.add_path(&current, demand)
+            }
+        }
+    }
+
+    let solution = FlowSolution::new(commodities.iter().zip(&paths).map(|(c, path)| {
+        CommodityFlow::new(
+            c.get_source(),
+            c.get_target(),
+            c.get_demand_vale(),
+            path.iter().map(|p| FlowSolution::path_flow(graph_adj, p, c.get_demand_vale())).collect()
+        )
+    }).collect());
+
+    let lower_bound = f64::max(fractional_bound, linearized_lower_bound(graph_adj, &mut work, &edges, commodities, &constraints, &loads, cost_model));
+
+    UnsplittableRouting {
+        solution,
+        objective: loads.objective(cost_model),
+        rounded_objective,
+        fractional_objective,
+        lower_bound,
+        moves
+    }
+}
+
+/// целевая функция выпуклая, поэтому для любой загрузки x оптимум дробной задачи f(x*) >= f(x) + grad f(x) * (y - x),
+/// где y - все commodity на кратчайших (с учетом ограничений) путях по производным в x
+fn linearized_lower_bound(
+    graph_adj: &EdgeWeightedDigraph,
+    work: &mut EdgeWeightedDigraph,
+    edges: &[DirectedEdge],
+    commodities: &[Demand],
+    constraints: &[PathConstraint],
+    loads: &ResourceLoads,
+    cost_model: CostModel
+) -> f64 {
+    let linear_x: f64 = loads.load.iter().zip(&loads.capacity)
+        .map(|(&x, &c)| cost_model.first_derivative(x, c) * x)
+        .sum();
+    for e in edges {
+        work.update_edge_by_id(e.get_id(), loads.derivative(cost_model, e));
+    }
+    let mut linear_y = 0f64;
+    for (commodity, constraint) in commodities.iter().zip(constraints) {
+        let source = graph_adj.get_node_index(&commodity.get_source());
+        let target = graph_adj.get_node_index(&commodity.get_target());
+        if let (Some(s), Some(t)) = (source, target) {
+            if let Some(path) = constraint.shortest_path(work, s, t, TieBreak::LowestEdgeIds) {
+                linear_y += commodity.get_demand_vale() * work.path_cost(&path);
+            }
+        }
+    }
+    loads.objective(cost_model) + linear_y - linear_x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection_solver::{solve_projection, ProjectionSettings};
+
+    #[test]
+    fn each_commodity_takes_one_path_with_full_demand() {
+        // два пути a -> b через c и через d, дробное решение делит каждый commodity между ними
+        let labels = vec!["a".to_string(), "b".to_string(), "c".to_string(), "d".to_string()];
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(labels, &[(0, 2, 1f64, 10f64), (2, 1, 1f64, 10f64), (0, 3, 1f64, 10f64), (3, 1, 1f64, 10f64), (2, 3, 1f64, 10f64)]);
+        let commodities = vec![
+            Demand::new("d1".to_string(), "a".to_string(), "b".to_string(), 4f64),
+            Demand::new("d2".to_string(), "a".to_string(), "b".to_string(), 3f64),
+            Demand::new("d3".to_string(), "a".to_string(), "b".to_string(), 5f64),
+            Demand::new("d4".to_string(), "c".to_string(), "b".to_string(), 2f64),
+        ];
+        let cost_model = CostModel::Kleinrock { p: 0.99 };
+        let settings = ProjectionSettings::new(0.065, 0.99, 0.0001, TieBreak::KeepCurrent);
+        let fractional = solve_projection(&mut graph.clone(), &commodities, &settings);
+        assert!(fractional.get_path_flows().iter().any(|p| p.len() > 1));
+
+        let routing = route_unsplittable(&graph, &commodities, &fractional, cost_model, 10, 1);
+        let flows = routing.solution.get_commodities();
+        assert_eq!(flows.len(), commodities.len());
+        for (flow, commodity) in flows.iter().zip(&commodities) {
+            assert_eq!(flow.get_paths().len(), 1, "{}", commodity.get_id());
+            let path = &flow.get_paths()[0];
+            assert_eq!(path.get_flow(), commodity.get_demand_vale());
+            let edges = path.to_edges(&graph).unwrap();
+            assert_eq!(graph.get_node_label(edges.last().unwrap().from()), commodity.get_source());
+            assert_eq!(graph.get_node_label(edges[0].to()), commodity.get_target());
+        }
+        assert!(routing.lower_bound <= routing.fractional_objective + 1e-9, "{} > {}", routing.lower_bound, routing.fractional_objective);
+        assert!(routing.lower_bound <= routing.objective + 1e-9);
+        assert!(routing.objective <= routing.rounded_objective + 1e-9);
+    }
+}
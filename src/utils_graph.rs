@@ -1,5 +1,5 @@
 use std::cell::{RefCell, RefMut};
-use std::collections::{HashSet, HashMap};
+use std::collections::{BTreeMap, HashSet, HashMap};
 use std::hash::{Hash, Hasher};
 
 use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
@@ -33,7 +33,7 @@ impl EdgeCapacityProduct {
 
 impl PartialEq for EdgeCapacityProduct {
     fn eq(&self, other: &Self) -> bool {
-        self.from == other.from && self.to == self.to
+        self.from == other.from && self.to == other.to
     }
 }
 
@@ -77,10 +77,9 @@ fn dfs(
         paths.insert(vec_to_str(current_path), current_path.clone());
     } else {
         // Iterate over all neighbors
-        let mut edges = &graph.edge_list(to as usize);
-        for gr in edges.iter() {
+        for gr in graph.edge_list(to as usize) {
             if !visited.contains(&gr.to()) {
-                dfs(gr, end, visited, current_path, paths, graph);
+                dfs(&gr, end, visited, current_path, paths, graph);
             }
         }
     }
@@ -90,14 +89,12 @@ fn dfs(
     visited.remove(&to);
 }
 
-pub fn vec_edge_to_str(edges: &Vec<DirectedEdge>) -> String{
-    let mut result = String::new();
-    result = edges[0].to().to_string();
-    for i in 1..edges.len() {
-        result = edges[i].to().to_string() + "_" + &result;
-    }
-    result = edges[edges.len() - 1].from().to_string() + "_" + &result;
-    result
+/// ключ пути по id дуг в порядке хранения: пути по разным параллельным дугам получают разные ключи
+pub fn path_key(edges: &[DirectedEdge]) -> String {
+    edges.iter()
+        .map(|e| e.get_id().to_string())
+        .collect::<Vec<String>>()
+        .join("_")
 }
 
 fn vec_to_str(v: &Vec<DirectedEdge>) -> String {
@@ -107,21 +104,25 @@ fn vec_to_str(v: &Vec<DirectedEdge>) -> String {
         .join("_")
 }
 
-pub fn symmetric_difference<T: Eq + std::hash::Hash>(vec1: Vec<T>, vec2: Vec<T>) -> Vec<T> {
-    let combined: HashSet<_> = vec1.into_iter().chain(vec2).collect();
-    combined.into_iter().collect()
+pub fn symmetric_difference<T: Eq + std::hash::Hash + Clone>(vec1: Vec<T>, vec2: Vec<T>) -> Vec<T> {
+    // порядок элементов сохраняется, чтобы суммы по результату не зависели от порядка обхода HashSet
+    let mut seen = HashSet::new();
+    vec1.into_iter().chain(vec2).filter(|e| seen.insert(e.clone())).collect()
 }
 
 
+/// потоки (x, y) на дуге по индексу commodity
+pub type CommodityFlows = BTreeMap<i32, (f64, f64)>;
+
 #[derive(Debug)]
 pub struct EdgeFlowCommodities {
     edge: DirectedEdge,
-    commodities: HashMap<i32, (f64, f64)>
+    commodities: BTreeMap<i32, (f64, f64)>
 }
 
 impl EdgeFlowCommodities {
 
-    pub fn new(edge: DirectedEdge, commodities: HashMap<i32, (f64,f64)>) -> EdgeFlowCommodities {
+    pub fn new(edge: DirectedEdge, commodities: BTreeMap<i32, (f64,f64)>) -> EdgeFlowCommodities {
         EdgeFlowCommodities {
             edge,
             commodities
@@ -178,9 +179,28 @@ impl EdgeFlowCommodities {
         self.commodities.values().map(|pair| pair.0).sum()
     }
 
-    pub fn get_total_flow_y(&self) -> f64 {
-        self.commodities.values().map(|pair| pair.1).sum()
+    /// потоки (x, y) каждого commodity на дуге
+    pub fn get_commodities(&self) -> &CommodityFlows {
+        &self.commodities
     }
 
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symmetric_difference_keeps_edges_with_common_tail() {
+        // дуги из одной вершины различаются только концом, ни одна не должна потеряться
+        let fan: Vec<DirectedEdge> = (1..=300).map(|w| DirectedEdge::new(w - 1, 0, w, 1f64, 1f64)).collect();
+        let (first, second) = fan.split_at(150);
+        for _ in 0..20 {
+            let union = symmetric_difference(first.to_vec(), second.to_vec());
+            assert_eq!(union.len(), fan.len());
+        }
+        let shared = symmetric_difference(fan.clone(), fan.clone());
+        assert_eq!(shared, fan);
+    }
+}
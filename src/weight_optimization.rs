@@ -0,0 +1,311 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::delay_func_count::CostModel;
+use crate::ecmp::{inv_cap_weights, route_ecmp, EcmpRouting, MAX_LINK_WEIGHT};
+use crate::graph::{DirectedEdge, EdgeWeightedDigraph};
+use crate::link_utilization::link_utilization;
+use crate::random::XorShiftRng;
+use crate::structure_xml::Demand;
+
+/// Параметры локального поиска весов OSPF/IS-IS
+#[derive(Debug, Clone, Copy)]
+pub struct WeightSearchSettings {
+    cost_model: CostModel,
+    max_weight: i32,
+    iterations: i32,
+    sample_size: usize,  // сколько соседних весовых векторов оценивается за итерацию
+    restart_after: i32,  // через сколько итераций без улучшения делается случайное возмущение
+    seed: u64
+}
+
+impl WeightSearchSettings {
+
+    pub fn new(cost_model: CostModel) -> WeightSearchSettings {
+        WeightSearchSettings {
+            cost_model,
+            max_weight: MAX_LINK_WEIGHT,
+            iterations: 1000,
+            sample_size: 20,
+            restart_after: 100,
+            seed: 1
+        }
+    }
+
+    pub fn with_iterations(mut self, iterations: i32) -> WeightSearchSettings {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn with_max_weight(mut self, max_weight: i32) -> WeightSearchSettings {
+        self.max_weight = max_weight;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> WeightSearchSettings {
+        self.seed = seed;
+        self
+    }
+}
+
+/// Найденные веса дуг и их оценка
+#[derive(Debug, Clone)]
+pub struct WeightSetting {
+    weights: BTreeMap<i32, i32>,
+    table: Vec<(String, String, String, i32)>, // линк, откуда, куда, вес
+    cost: f64,
+    initial_cost: f64,
+    evaluations: i32,
+    routing: EcmpRouting
+}
+
+impl WeightSetting {
+
+    /// вес каждой дуги по ее id
+    pub fn get_weights(&self) -> &BTreeMap<i32, i32> {
+        &self.weights
+    }
+
+    pub fn get_routing(&self) -> &EcmpRouting {
+        &self.routing
+    }
+}
+
+impl fmt::Display for WeightSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (link, from, to, weight) in &self.table {
+            writeln!(f, "link - {} ({} -> {}), weight: {}", link, from, to, weight)?;
+        }
+        writeln!(f, "cost: {}, InvCap cost: {}, evaluations: {}", self.cost, self.initial_cost, self.evaluations)
+    }
+}
+
+/// Локальный поиск весов дуг (Fortz-Thorup): на каждой итерации оценивается выборка соседних весовых векторов,
+/// полученных изменением одного веса или выравниванием путей из вершины к получателю.
+/// Просмотренные весовые векторы запоминаются по хешу и повторно не оцениваются
+pub fn optimize_weights(graph_adj: &EdgeWeightedDigraph, commodities: &[Demand], settings: &WeightSearchSettings) -> WeightSetting {
+    let edges: Vec<DirectedEdge> = graph_adj.edges().into_iter().filter(|e| graph_adj.is_edge_enabled(e)).collect();
+    let mut rng = XorShiftRng::new(settings.seed);
+    let mut visited = HashSet::new();
+    let mut evaluations = 0;
+    let mut evaluate = |weights: &BTreeMap<i32, i32>| {
+        evaluations += 1;
+        let routing = route_ecmp(graph_adj, commodities, weights);
+        let cost = link_utilization(graph_adj, routing.get_edge_flows()).cost(settings.cost_model);
+        (cost, routing)
+    };
+
+    // начальные веса InvCap
+    let mut current = inv_cap_weights(graph_adj, settings.max_weight);
+    visited.insert(weights_hash(&current));
+    let (initial_cost, initial_routing) = evaluate(&current);
+    let mut current_cost = initial_cost;
+    let mut current_routing = initial_routing.clone();
+    let mut best = (current.clone(), initial_cost, initial_routing);
+    let mut stalled = 0;
+
+    for _ in 0..settings.iterations {
+        let mut best_neighbour: Option<(BTreeMap<i32, i32>, f64, EcmpRouting)> = None;
+        for _ in 0..settings.sample_size {
+            let neighbour = if rng.next_f64() < 0.5 {
+                single_weight_change(&current, &edges, settings.max_weight, &mut rng)
+            } else {
+                balance_paths(&current, graph_adj, &current_routing, settings.max_weight, &mut rng)
+            };
+            let neighbour = match neighbour {
+                Some(weights) if visited.insert(weights_hash(&weights)) => weights,
+                _ => continue
+            };
+            let (cost, routing) = evaluate(&neighbour);
+            if best_neighbour.as_ref().is_none_or(|b| cost < b.1) {
+                best_neighbour = Some((neighbour, cost, routing));
+            }
+        }
+
+        match best_neighbour {
+            Some((weights, cost, routing)) if cost < current_cost => {
+                current = weights;
+                current_cost = cost;
+                current_routing = routing;
+                if current_cost < best.1 {
+                    best = (current.clone(), current_cost, current_routing.clone());
+                    stalled = 0;
+                }
+            }
+            _ => stalled += 1
+        }
+
+        // диверсификация: случайно меняем десятую часть весов лучшего решения
+        if stalled >= settings.restart_after && !edges.is_empty() {
+            current = best.0.clone();
+            for _ in 0..usize::max(1, edges.len() / 10) {
+                let e = edges[rng.gen_index(edges.len())];
+                current.insert(e.get_id(), rng.gen_range(1, settings.max_weight));
+            }
+            visited.insert(weights_hash(&current));
+            let (cost, routing) = evaluate(&current);
+            current_cost = cost;
+            current_routing = routing;
+            stalled = 0;
+        }
+    }
+
+    let (weights, cost, routing) = best;
+    let table = edges.iter()
+        .map(|e| (graph_adj.get_edge_label(e), graph_adj.get_node_label(e.from()), graph_adj.get_node_label(e.to()), weights[&e.get_id()]))
+        .collect();
+    WeightSetting {
+        weights,
+        table,
+        cost,
+        initial_cost,
+        evaluations,
+        routing
+    }
+}
+
+fn weights_hash(weights: &BTreeMap<i32, i32>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    weights.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// вес случайной дуги заменяется случайным значением: чаще близким к текущему, иногда из всего диапазона
+fn single_weight_change(weights: &BTreeMap<i32, i32>, edges: &[DirectedEdge], max_weight: i32, rng: &mut XorShiftRng) -> Option<BTreeMap<i32, i32>> {
+    if edges.is_empty() {
+        return None;
+    }
+    let id = edges[rng.gen_index(edges.len())].get_id();
+    let old = weights[&id];
+    let new = if rng.next_f64() < 0.8 {
+        rng.gen_range(i32::max(1, old / 2), i32::min(max_weight, 2 * old + 1))
+    } else {
+        rng.gen_range(1, max_weight)
+    };
+    if new == old {
+        return None;
+    }
+    let mut neighbour = weights.clone();
+    neighbour.insert(id, new);
+    Some(neighbour)
+}
+
+/// выравнивание путей: для случайной вершины v и получателя t веса случайного подмножества исходящих дуг
+/// подбираются так, чтобы пути через них к t стали равными по длине и самыми короткими, поток делится между ними поровну
+fn balance_paths(
+    weights: &BTreeMap<i32, i32>,
+    graph_adj: &EdgeWeightedDigraph,
+    routing: &EcmpRouting,
+    max_weight: i32,
+    rng: &mut XorShiftRng
+) -> Option<BTreeMap<i32, i32>> {
+    let n = graph_adj.get_v_count();
+    let v = rng.gen_range(0, n - 1);
+    let t = rng.gen_range(0, n - 1);
+    if v == t {
+        return None;
+    }
+    let out: Vec<DirectedEdge> = graph_adj.edge_list(v as usize)
+        .filter(|e| routing.get_distance(e.to(), t).is_finite())
+        .collect();
+    if out.len() < 2 {
+        return None;
+    }
+    let chosen: Vec<bool> = out.iter().map(|_| rng.next_f64() < 0.5).collect();
+    if chosen.iter().filter(|&&c| c).count() < 2 {
+        return None;
+    }
+    // длина пути w* через любую выбранную дугу; дуги вне подмножества должны давать путь длиннее
+    let target = out.iter().zip(&chosen)
+        .filter(|(_, &c)| c)
+        .map(|(e, _)| routing.get_distance(e.to(), t))
+        .fold(0f64, f64::max) + 1f64;
+    let mut neighbour = weights.clone();
+    for (e, &c) in out.iter().zip(&chosen) {
+        let dist = routing.get_distance(e.to(), t);
+        let weight = weights[&e.get_id()] as f64;
+        let new = if c {
+            target - dist
+        } else if weight + dist <= target {
+            target - dist + 1f64
+        } else {
+            weight
+        };
+        if new > max_weight as f64 {
+            return None;
+        }
+        neighbour.insert(e.get_id(), new as i32);
+    }
+    if &neighbour == weights {
+        return None;
+    }
+    Some(neighbour)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// решетка 4x4 с дугами в обе стороны и разными пропускными способностями, нагрузка между вершинами на границе
+    fn grid() -> (EdgeWeightedDigraph, Vec<Demand>) {
+        let side = 4;
+        let mut arcs = vec![];
+        for v in 0..side * side {
+            let capacity = 10f64 + ((v * 7) % 5) as f64 * 5f64;
+            if v % side + 1 < side {
+                arcs.push((v, v + 1, 1f64, capacity));
+                arcs.push((v + 1, v, 1f64, capacity));
+            }
+            if v + side < side * side {
+                arcs.push((v, v + side, 1f64, capacity));
+                arcs.push((v + side, v, 1f64, capacity));
+            }
+        }
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs((0..side * side).map(|v| v.to_string()).collect(), &arcs);
+        let border: Vec<i32> = (0..side * side).filter(|v| v % side == 0 || v % side == side - 1).collect();
+        let commodities = border.iter()
+            .flat_map(|&s| border.iter().filter(move |&&t| t != s).map(move |&t| (s, t)))
+            .map(|(s, t)| Demand::new(format!("{}_{}", s, t), s.to_string(), t.to_string(), 1f64 + ((s + 2 * t) % 3) as f64))
+            .collect();
+        (graph, commodities)
+    }
+
+    #[test]
+    fn search_never_loses_to_inv_cap_and_respects_weight_range() {
+        let (graph, commodities) = grid();
+        let settings = WeightSearchSettings::new(CostModel::FortzThorup).with_iterations(30).with_max_weight(20).with_seed(7);
+        let setting = optimize_weights(&graph, &commodities, &settings);
+        assert!(setting.cost <= setting.initial_cost, "{} > {}", setting.cost, setting.initial_cost);
+        let inv_cap = route_ecmp(&graph, &commodities, &inv_cap_weights(&graph, 20));
+        assert_eq!(setting.initial_cost, link_utilization(&graph, inv_cap.get_edge_flows()).cost(CostModel::FortzThorup));
+        // стоимость относится к возвращенным весам
+        let routing = route_ecmp(&graph, &commodities, &setting.weights);
+        assert_eq!(setting.cost, link_utilization(&graph, routing.get_edge_flows()).cost(CostModel::FortzThorup));
+
+        assert_eq!(setting.weights.len(), graph.edges().len());
+        assert!(setting.weights.values().all(|w| (1..=20).contains(w)));
+
+        // при том же seed поиск повторяется в точности
+        let again = optimize_weights(&graph, &commodities, &settings);
+        assert_eq!(again.weights, setting.weights);
+        assert_eq!(again.cost.to_bits(), setting.cost.to_bits());
+        assert_eq!(again.evaluations, setting.evaluations);
+    }
+
+    #[test]
+    fn search_splits_overloaded_direct_link() {
+        // при InvCap весь спрос a -> b идет по прямому линку с загрузкой 1.5, вес 2 делит его с обходом через c
+        let labels = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut graph = EdgeWeightedDigraph::default_graph();
+        graph.graph_from_arcs(labels, &[(0, 1, 1f64, 10f64), (0, 2, 1f64, 10f64), (2, 1, 1f64, 10f64)]);
+        let commodities = vec![Demand::new("d".to_string(), "a".to_string(), "b".to_string(), 15f64)];
+        let settings = WeightSearchSettings::new(CostModel::FortzThorup).with_iterations(20).with_max_weight(20);
+        let setting = optimize_weights(&graph, &commodities, &settings);
+        assert!(setting.cost < setting.initial_cost);
+        assert_eq!(setting.weights[&0], setting.weights[&1] + setting.weights[&2]);
+        assert!(setting.routing.get_edge_flows().iter().all(|(_, f)| (f - 7.5).abs() < 1e-9));
+    }
+}